@@ -0,0 +1,572 @@
+use crate::attribute::Attribute;
+use crate::error::{ImportErrorKind, JavaImportError};
+use crate::import::{load_u16, load_u8, store_len_u16, store_len_u8, store_u16, store_u8, OffsetReader, Utf8};
+/// Nested annotations and arrays deeper than this are rejected, so a malicious file can't overflow the stack.
+const MAX_DEPTH: u16 = 256;
+/// Annotation of a class, field, method or parameter, e.g. `@Deprecated(since = "9")`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Annotation {
+    pub(crate) type_index: u16,
+    pub(crate) elements: Box<[AnnotationElement]>,
+}
+/// Element of an [`Annotation`] together with its value, e.g. `since = "9"`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AnnotationElement {
+    pub(crate) name_index: u16,
+    pub(crate) value: ElementValue,
+}
+/// Value of an annotation element, or the default value of an annotation interface method.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ElementValue {
+    /// Primitive or `String` constant. `tag` is the descriptor of its type(e.g. `b'I'` or `b'Z'`), or `b's'` for
+    /// strings, and `value` is the index of an `Integer`, `Long`, `Float`, `Double` or (for strings) `Utf8` constant.
+    Const { tag: u8, value: u16 },
+    /// Enum constant, e.g. `ElementType.TYPE`.
+    Enum { type_descriptor: Utf8, const_name: Utf8 },
+    /// Class literal, given as a return descriptor, e.g. `Ljava/lang/String;` or `V` for `void.class`.
+    Class(Utf8),
+    Annotation(Annotation),
+    Array(Box<[ElementValue]>),
+}
+impl Annotation {
+    /// Field descriptor of the annotation interface, e.g. `Ljava/lang/Deprecated;`.
+    pub fn type_descriptor(&self) -> Utf8 {
+        Utf8(self.type_index)
+    }
+    /// Elements with an explicit value. Elements left at their default value are not stored in the class file.
+    pub fn elements(&self) -> &[AnnotationElement] {
+        &self.elements
+    }
+    fn read<R: std::io::Read>(src: &mut OffsetReader<R>, depth: u16) -> Result<Self, JavaImportError> {
+        if depth > MAX_DEPTH {
+            return Err(src.error(ImportErrorKind::InvalidAnnotation));
+        }
+        let type_index = load_u16(src)?;
+        let element_count = load_u16(src)?;
+        let mut elements = Vec::new();
+        for _ in 0..element_count {
+            let name_index = load_u16(src)?;
+            let value = ElementValue::read(src, depth + 1)?;
+            elements.push(AnnotationElement { name_index, value });
+        }
+        Ok(Self {
+            type_index,
+            elements: elements.into(),
+        })
+    }
+    /// Reads the body of a `RuntimeVisibleAnnotations` or `RuntimeInvisibleAnnotations` attribute.
+    pub(crate) fn read_list<R: std::io::Read>(src: &mut OffsetReader<R>) -> Result<Box<[Self]>, JavaImportError> {
+        let count = load_u16(src)?;
+        (0..count).map(|_| Self::read(src, 0)).collect()
+    }
+    /// Reads the body of a `RuntimeVisibleParameterAnnotations` or `RuntimeInvisibleParameterAnnotations` attribute.
+    pub(crate) fn read_parameters<R: std::io::Read>(
+        src: &mut OffsetReader<R>,
+    ) -> Result<Box<[Box<[Self]>]>, JavaImportError> {
+        let count = load_u8(src)?;
+        (0..count).map(|_| Self::read_list(src)).collect()
+    }
+    fn write<W: std::io::Write>(&self, dst: &mut W) -> std::io::Result<()> {
+        store_u16(dst, self.type_index)?;
+        store_len_u16(dst, self.elements.len())?;
+        for element in self.elements.iter() {
+            store_u16(dst, element.name_index)?;
+            element.value.write(dst)?;
+        }
+        Ok(())
+    }
+    pub(crate) fn write_list<W: std::io::Write>(list: &[Self], dst: &mut W) -> std::io::Result<()> {
+        store_len_u16(dst, list.len())?;
+        list.iter().try_for_each(|annotation| annotation.write(dst))
+    }
+    pub(crate) fn write_parameters<W: std::io::Write>(parameters: &[Box<[Self]>], dst: &mut W) -> std::io::Result<()> {
+        store_len_u8(dst, parameters.len())?;
+        parameters.iter().try_for_each(|list| Self::write_list(list, dst))
+    }
+}
+impl AnnotationElement {
+    pub fn name(&self) -> Utf8 {
+        Utf8(self.name_index)
+    }
+    pub fn value(&self) -> &ElementValue {
+        &self.value
+    }
+}
+impl ElementValue {
+    pub(crate) fn read<R: std::io::Read>(src: &mut OffsetReader<R>, depth: u16) -> Result<Self, JavaImportError> {
+        if depth > MAX_DEPTH {
+            return Err(src.error(ImportErrorKind::InvalidAnnotation));
+        }
+        Ok(match load_u8(src)? {
+            tag @ (b'B' | b'C' | b'D' | b'F' | b'I' | b'J' | b'S' | b'Z' | b's') => Self::Const {
+                tag,
+                value: load_u16(src)?,
+            },
+            b'e' => Self::Enum {
+                type_descriptor: Utf8(load_u16(src)?),
+                const_name: Utf8(load_u16(src)?),
+            },
+            b'c' => Self::Class(Utf8(load_u16(src)?)),
+            b'@' => Self::Annotation(Annotation::read(src, depth + 1)?),
+            b'[' => {
+                let count = load_u16(src)?;
+                let values: Result<_, _> = (0..count).map(|_| Self::read(src, depth + 1)).collect();
+                Self::Array(values?)
+            }
+            _ => return Err(src.error(ImportErrorKind::InvalidAnnotation)),
+        })
+    }
+    pub(crate) fn write<W: std::io::Write>(&self, dst: &mut W) -> std::io::Result<()> {
+        match self {
+            Self::Const { tag, value } => {
+                store_u8(dst, *tag)?;
+                store_u16(dst, *value)
+            }
+            Self::Enum {
+                type_descriptor,
+                const_name,
+            } => {
+                store_u8(dst, b'e')?;
+                store_u16(dst, type_descriptor.0)?;
+                store_u16(dst, const_name.0)
+            }
+            Self::Class(class) => {
+                store_u8(dst, b'c')?;
+                store_u16(dst, class.0)
+            }
+            Self::Annotation(annotation) => {
+                store_u8(dst, b'@')?;
+                annotation.write(dst)
+            }
+            Self::Array(values) => {
+                store_u8(dst, b'[')?;
+                store_len_u16(dst, values.len())?;
+                values.iter().try_for_each(|value| value.write(dst))
+            }
+        }
+    }
+}
+/// Annotation of a use of a type(JSR 308), e.g. `List<@Nullable String>`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TypeAnnotation {
+    pub(crate) target: TypeAnnotationTarget,
+    pub(crate) type_path: Box<[TypePathEntry]>,
+    pub(crate) annotation: Annotation,
+}
+/// Kind of type an annotation is on(the `target_type` and `target_info` of a type annotation).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TypeAnnotationTarget {
+    ClassTypeParameter { index: u8 },
+    MethodTypeParameter { index: u8 },
+    /// Type in the `extends`(index 65535) or `implements`(index into the interfaces) clause of a class.
+    Supertype { index: u16 },
+    ClassTypeParameterBound { type_parameter: u8, bound: u8 },
+    MethodTypeParameterBound { type_parameter: u8, bound: u8 },
+    Field,
+    /// Return type of a method, or the type of a newly constructed object.
+    Return,
+    Receiver,
+    FormalParameter { index: u8 },
+    /// Type in the `throws` clause, `index` being an index into the `Exceptions` attribute.
+    Throws { index: u16 },
+    LocalVariable(Box<[LocalVariableTarget]>),
+    /// Variable declared in a `try`-with-resources statement.
+    ResourceVariable(Box<[LocalVariableTarget]>),
+    /// Type in a `catch` clause, given as an index into the exception table of the code.
+    ExceptionParameter { exception_table_index: u16 },
+    InstanceOf { offset: u16 },
+    New { offset: u16 },
+    /// Method reference to a constructor, e.g. `String::new`.
+    ConstructorReference { offset: u16 },
+    MethodReference { offset: u16 },
+    /// Type in a cast, `index` telling apart the types of an intersection cast.
+    Cast { offset: u16, index: u8 },
+    ConstructorInvocationTypeArgument { offset: u16, index: u8 },
+    MethodInvocationTypeArgument { offset: u16, index: u8 },
+    ConstructorReferenceTypeArgument { offset: u16, index: u8 },
+    MethodReferenceTypeArgument { offset: u16, index: u8 },
+}
+/// Range of code in which a local variable with an annotated type is live.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LocalVariableTarget {
+    pub(crate) start_pc: u16,
+    pub(crate) length: u16,
+    pub(crate) index: u16,
+}
+/// Step from a type to the part of it an annotation is on, e.g. from `List<String>` to `String`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TypePathEntry {
+    /// Element type of an array.
+    Array,
+    /// Type nested in this one, e.g. `Inner` in `Outer.Inner`.
+    Nested,
+    /// Bound of a wildcard, e.g. `String` in `? extends String`.
+    Wildcard,
+    /// Type argument at this index.
+    TypeArgument(u8),
+}
+impl TypeAnnotation {
+    pub fn target(&self) -> &TypeAnnotationTarget {
+        &self.target
+    }
+    /// Path to the annotated part of the target type. Empty if the whole type is annotated.
+    pub fn type_path(&self) -> &[TypePathEntry] {
+        &self.type_path
+    }
+    pub fn annotation(&self) -> &Annotation {
+        &self.annotation
+    }
+    fn read<R: std::io::Read>(src: &mut OffsetReader<R>) -> Result<Self, JavaImportError> {
+        let target = TypeAnnotationTarget::read(src)?;
+        let path_length = load_u8(src)?;
+        let mut type_path = Vec::with_capacity(path_length as usize);
+        for _ in 0..path_length {
+            let kind = load_u8(src)?;
+            let argument = load_u8(src)?;
+            type_path.push(match (kind, argument) {
+                (0, 0) => TypePathEntry::Array,
+                (1, 0) => TypePathEntry::Nested,
+                (2, 0) => TypePathEntry::Wildcard,
+                (3, argument) => TypePathEntry::TypeArgument(argument),
+                _ => return Err(src.error(ImportErrorKind::InvalidAnnotation)),
+            });
+        }
+        Ok(Self {
+            target,
+            type_path: type_path.into(),
+            annotation: Annotation::read(src, 0)?,
+        })
+    }
+    /// Reads the body of a `RuntimeVisibleTypeAnnotations` or `RuntimeInvisibleTypeAnnotations` attribute.
+    pub(crate) fn read_list<R: std::io::Read>(src: &mut OffsetReader<R>) -> Result<Box<[Self]>, JavaImportError> {
+        let count = load_u16(src)?;
+        (0..count).map(|_| Self::read(src)).collect()
+    }
+    fn write<W: std::io::Write>(&self, dst: &mut W) -> std::io::Result<()> {
+        self.target.write(dst)?;
+        store_len_u8(dst, self.type_path.len())?;
+        for entry in self.type_path.iter() {
+            let (kind, argument) = match entry {
+                TypePathEntry::Array => (0, 0),
+                TypePathEntry::Nested => (1, 0),
+                TypePathEntry::Wildcard => (2, 0),
+                TypePathEntry::TypeArgument(argument) => (3, *argument),
+            };
+            store_u8(dst, kind)?;
+            store_u8(dst, argument)?;
+        }
+        self.annotation.write(dst)
+    }
+    pub(crate) fn write_list<W: std::io::Write>(list: &[Self], dst: &mut W) -> std::io::Result<()> {
+        store_len_u16(dst, list.len())?;
+        list.iter().try_for_each(|annotation| annotation.write(dst))
+    }
+}
+impl TypeAnnotationTarget {
+    /// The `target_type` byte this target is encoded with.
+    pub fn target_type(&self) -> u8 {
+        match self {
+            Self::ClassTypeParameter { .. } => 0x00,
+            Self::MethodTypeParameter { .. } => 0x01,
+            Self::Supertype { .. } => 0x10,
+            Self::ClassTypeParameterBound { .. } => 0x11,
+            Self::MethodTypeParameterBound { .. } => 0x12,
+            Self::Field => 0x13,
+            Self::Return => 0x14,
+            Self::Receiver => 0x15,
+            Self::FormalParameter { .. } => 0x16,
+            Self::Throws { .. } => 0x17,
+            Self::LocalVariable(_) => 0x40,
+            Self::ResourceVariable(_) => 0x41,
+            Self::ExceptionParameter { .. } => 0x42,
+            Self::InstanceOf { .. } => 0x43,
+            Self::New { .. } => 0x44,
+            Self::ConstructorReference { .. } => 0x45,
+            Self::MethodReference { .. } => 0x46,
+            Self::Cast { .. } => 0x47,
+            Self::ConstructorInvocationTypeArgument { .. } => 0x48,
+            Self::MethodInvocationTypeArgument { .. } => 0x49,
+            Self::ConstructorReferenceTypeArgument { .. } => 0x4A,
+            Self::MethodReferenceTypeArgument { .. } => 0x4B,
+        }
+    }
+    /// Offset of the instruction this target is on, for targets within an expression.
+    pub fn offset(&self) -> Option<u16> {
+        match self {
+            Self::InstanceOf { offset }
+            | Self::New { offset }
+            | Self::ConstructorReference { offset }
+            | Self::MethodReference { offset }
+            | Self::Cast { offset, .. }
+            | Self::ConstructorInvocationTypeArgument { offset, .. }
+            | Self::MethodInvocationTypeArgument { offset, .. }
+            | Self::ConstructorReferenceTypeArgument { offset, .. }
+            | Self::MethodReferenceTypeArgument { offset, .. } => Some(*offset),
+            _ => None,
+        }
+    }
+    /// Live ranges of the local variable this target is on.
+    pub fn local_variables(&self) -> &[LocalVariableTarget] {
+        match self {
+            Self::LocalVariable(ranges) | Self::ResourceVariable(ranges) => ranges,
+            _ => &[],
+        }
+    }
+    /// Checks if this target applies to the code at `pc`: a local variable live at `pc`, or the instruction at `pc`.
+    pub fn covers(&self, pc: u16) -> bool {
+        self.offset() == Some(pc) || self.local_variables().iter().any(|range| range.contains(pc))
+    }
+    fn read<R: std::io::Read>(src: &mut OffsetReader<R>) -> Result<Self, JavaImportError> {
+        let target_type = load_u8(src)?;
+        Ok(match target_type {
+            0x00 => Self::ClassTypeParameter { index: load_u8(src)? },
+            0x01 => Self::MethodTypeParameter { index: load_u8(src)? },
+            0x10 => Self::Supertype { index: load_u16(src)? },
+            0x11 | 0x12 => {
+                let type_parameter = load_u8(src)?;
+                let bound = load_u8(src)?;
+                if target_type == 0x11 {
+                    Self::ClassTypeParameterBound { type_parameter, bound }
+                } else {
+                    Self::MethodTypeParameterBound { type_parameter, bound }
+                }
+            }
+            0x13 => Self::Field,
+            0x14 => Self::Return,
+            0x15 => Self::Receiver,
+            0x16 => Self::FormalParameter { index: load_u8(src)? },
+            0x17 => Self::Throws { index: load_u16(src)? },
+            0x40 | 0x41 => {
+                let count = load_u16(src)?;
+                let mut ranges = Vec::new();
+                for _ in 0..count {
+                    ranges.push(LocalVariableTarget {
+                        start_pc: load_u16(src)?,
+                        length: load_u16(src)?,
+                        index: load_u16(src)?,
+                    });
+                }
+                if target_type == 0x40 {
+                    Self::LocalVariable(ranges.into())
+                } else {
+                    Self::ResourceVariable(ranges.into())
+                }
+            }
+            0x42 => Self::ExceptionParameter {
+                exception_table_index: load_u16(src)?,
+            },
+            0x43..=0x46 => {
+                let offset = load_u16(src)?;
+                match target_type {
+                    0x43 => Self::InstanceOf { offset },
+                    0x44 => Self::New { offset },
+                    0x45 => Self::ConstructorReference { offset },
+                    _ => Self::MethodReference { offset },
+                }
+            }
+            0x47..=0x4B => {
+                let offset = load_u16(src)?;
+                let index = load_u8(src)?;
+                match target_type {
+                    0x47 => Self::Cast { offset, index },
+                    0x48 => Self::ConstructorInvocationTypeArgument { offset, index },
+                    0x49 => Self::MethodInvocationTypeArgument { offset, index },
+                    0x4A => Self::ConstructorReferenceTypeArgument { offset, index },
+                    _ => Self::MethodReferenceTypeArgument { offset, index },
+                }
+            }
+            _ => return Err(src.error(ImportErrorKind::InvalidAnnotation)),
+        })
+    }
+    fn write<W: std::io::Write>(&self, dst: &mut W) -> std::io::Result<()> {
+        store_u8(dst, self.target_type())?;
+        match self {
+            Self::ClassTypeParameter { index } | Self::MethodTypeParameter { index } | Self::FormalParameter { index } => {
+                store_u8(dst, *index)
+            }
+            Self::Supertype { index } | Self::Throws { index } => store_u16(dst, *index),
+            Self::ClassTypeParameterBound { type_parameter, bound }
+            | Self::MethodTypeParameterBound { type_parameter, bound } => {
+                store_u8(dst, *type_parameter)?;
+                store_u8(dst, *bound)
+            }
+            Self::Field | Self::Return | Self::Receiver => Ok(()),
+            Self::LocalVariable(ranges) | Self::ResourceVariable(ranges) => {
+                store_len_u16(dst, ranges.len())?;
+                for range in ranges.iter() {
+                    store_u16(dst, range.start_pc)?;
+                    store_u16(dst, range.length)?;
+                    store_u16(dst, range.index)?;
+                }
+                Ok(())
+            }
+            Self::ExceptionParameter { exception_table_index } => store_u16(dst, *exception_table_index),
+            Self::InstanceOf { offset }
+            | Self::New { offset }
+            | Self::ConstructorReference { offset }
+            | Self::MethodReference { offset } => store_u16(dst, *offset),
+            Self::Cast { offset, index }
+            | Self::ConstructorInvocationTypeArgument { offset, index }
+            | Self::MethodInvocationTypeArgument { offset, index }
+            | Self::ConstructorReferenceTypeArgument { offset, index }
+            | Self::MethodReferenceTypeArgument { offset, index } => {
+                store_u16(dst, *offset)?;
+                store_u8(dst, *index)
+            }
+        }
+    }
+}
+impl LocalVariableTarget {
+    pub fn start_pc(&self) -> u16 {
+        self.start_pc
+    }
+    pub fn length(&self) -> u16 {
+        self.length
+    }
+    /// Index of the local variable in the frame.
+    pub fn index(&self) -> u16 {
+        self.index
+    }
+    /// Checks if the variable is live at `pc`.
+    pub fn contains(&self, pc: u16) -> bool {
+        (self.start_pc as u32..self.start_pc as u32 + self.length as u32).contains(&(pc as u32))
+    }
+}
+/// Annotations stored in `attributes`, either the runtime visible or the runtime invisible ones.
+pub(crate) fn annotations(attributes: &[(u16, Attribute)], visible: bool) -> &[Annotation] {
+    attributes
+        .iter()
+        .find_map(|(_, attribute)| match (attribute, visible) {
+            (Attribute::RuntimeVisibleAnnotations { annotations }, true)
+            | (Attribute::RuntimeInvisibleAnnotations { annotations }, false) => Some(&annotations[..]),
+            _ => None,
+        })
+        .unwrap_or_default()
+}
+/// Type annotations stored in `attributes`, either the runtime visible or the runtime invisible ones.
+pub(crate) fn type_annotations(attributes: &[(u16, Attribute)], visible: bool) -> &[TypeAnnotation] {
+    attributes
+        .iter()
+        .find_map(|(_, attribute)| match (attribute, visible) {
+            (Attribute::RuntimeVisibleTypeAnnotations { annotations }, true)
+            | (Attribute::RuntimeInvisibleTypeAnnotations { annotations }, false) => Some(&annotations[..]),
+            _ => None,
+        })
+        .unwrap_or_default()
+}
+#[test]
+fn component_annotation() {
+    let mut file = std::fs::File::open("test/Annotations.class").unwrap();
+    let class = crate::import_class_file(&mut file).unwrap();
+    let pool = class.constant_pool();
+    let [component] = class.annotations() else {
+        panic!("expected a single visible annotation");
+    };
+    assert_eq!(class.get_utf8(component.type_descriptor()), Some("LAnnotations$Component;"));
+    let elements: Vec<_> = component
+        .elements()
+        .iter()
+        .map(|element| (class.get_utf8(element.name()).unwrap(), element.value()))
+        .collect();
+    assert!(matches!(elements[0], ("name", ElementValue::Const { tag: b's', value }) if pool.utf8(*value) == Some("annotations")));
+    assert!(matches!(elements[1], ("scope", ElementValue::Enum { type_descriptor, const_name })
+        if class.get_utf8(*type_descriptor) == Some("Ljava/lang/annotation/ElementType;")
+            && class.get_utf8(*const_name) == Some("TYPE")));
+    let ("tags", ElementValue::Array(tags)) = elements[2] else {
+        panic!("expected an array of tags");
+    };
+    assert_eq!(tags.len(), 2);
+    assert!(matches!(elements[3], ("factory", ElementValue::Class(class_name)) if class.get_utf8(*class_name) == Some("Ljava/lang/String;")));
+    let [marker] = class.invisible_annotations() else {
+        panic!("expected a single invisible annotation");
+    };
+    assert_eq!(class.get_utf8(marker.type_descriptor()), Some("LAnnotations$Marker;"));
+    // `counter` is both `@Deprecated` and `@Inject`.
+    let field = &class.fields()[0];
+    let names: Vec<_> = field
+        .annotations()
+        .iter()
+        .map(|annotation| class.get_utf8(annotation.type_descriptor()).unwrap())
+        .collect();
+    assert_eq!(names, ["Ljava/lang/Deprecated;", "LAnnotations$Inject;"]);
+    let set = &class.methods()[1];
+    let visible: Vec<_> = set.parameter_annotations().iter().map(|list| list.len()).collect();
+    let invisible: Vec<_> = set.invisible_parameter_annotations().iter().map(|list| list.len()).collect();
+    assert_eq!((visible, invisible), (vec![1, 0], vec![0, 1]));
+    let mut written = Vec::new();
+    class.write(&mut written).unwrap();
+    assert_eq!(written, std::fs::read("test/Annotations.class").unwrap());
+}
+#[test]
+fn annotation_defaults() {
+    let mut file = std::fs::File::open("test/Annotations$Inject.class").unwrap();
+    let class = crate::import_class_file(&mut file).unwrap();
+    let value = class.methods()[0].annotation_default();
+    assert!(matches!(value, Some(ElementValue::Annotation(marker))
+        if class.get_utf8(marker.type_descriptor()) == Some("LAnnotations$Marker;") && marker.elements().is_empty()));
+    // An array nested deeper than the limit is rejected instead of overflowing the stack.
+    let mut nested = vec![b'['; 1000];
+    nested.extend([0; 2]);
+    let err = ElementValue::read(&mut OffsetReader::new(&nested[..], 0), 0).unwrap_err();
+    assert!(matches!(err.kind(), ImportErrorKind::InvalidAnnotation));
+}
+#[test]
+fn type_annotation_targets() {
+    let mut file = std::fs::File::open("test/TypeAnnotations.class").unwrap();
+    let class = crate::import_class_file(&mut file).unwrap();
+    let targets: Vec<_> = class.type_annotations().iter().map(TypeAnnotation::target).collect();
+    assert_eq!(
+        targets,
+        [
+            &TypeAnnotationTarget::Supertype { index: 65535 },
+            &TypeAnnotationTarget::Supertype { index: 0 },
+            &TypeAnnotationTarget::ClassTypeParameter { index: 0 },
+            &TypeAnnotationTarget::ClassTypeParameterBound { type_parameter: 0, bound: 0 },
+        ]
+    );
+    // List<@Nullable String> names, and String @Nullable [] @Tainted [] grid.
+    let [names] = class.fields()[0].type_annotations() else {
+        panic!("expected a single type annotation on `names`");
+    };
+    assert_eq!(names.type_path(), [TypePathEntry::TypeArgument(0)]);
+    let [tainted] = class.fields()[1].invisible_type_annotations() else {
+        panic!("expected a single invisible type annotation on `grid`");
+    };
+    assert_eq!((tainted.target(), tainted.type_path()), (&TypeAnnotationTarget::Field, &[TypePathEntry::Array][..]));
+    assert_eq!(class.get_utf8(tainted.annotation().type_descriptor()), Some("LTainted;"));
+    let find = &class.methods()[2];
+    let targets: Vec<_> = find.type_annotations().iter().map(TypeAnnotation::target).collect();
+    assert_eq!(
+        targets,
+        [
+            &TypeAnnotationTarget::Throws { index: 0 },
+            &TypeAnnotationTarget::Return,
+            &TypeAnnotationTarget::FormalParameter { index: 0 }
+        ]
+    );
+    let code = find.code().unwrap();
+    let targets: Vec<_> = code.type_annotations().iter().map(TypeAnnotation::target).collect();
+    assert!(matches!(
+        targets[..],
+        [
+            TypeAnnotationTarget::Cast { offset: 1, index: 0 },
+            TypeAnnotationTarget::InstanceOf { offset: 6 },
+            TypeAnnotationTarget::ConstructorReference { offset: 12 },
+            TypeAnnotationTarget::MethodInvocationTypeArgument { offset: 47, index: 0 },
+            TypeAnnotationTarget::LocalVariable(_),
+            TypeAnnotationTarget::ExceptionParameter { exception_table_index: 0 },
+        ]
+    ));
+    // `found` is stored to slot 2 at offset 4, and live from offset 5 to the end of the code.
+    let found = targets[4];
+    assert_eq!(found.local_variables()[0].index(), 2);
+    assert!(!found.covers(4) && found.covers(5) && found.covers(61) && !found.covers(62));
+    assert!(targets[1].covers(6) && matches!(code.instruction_at(6), Some(crate::OpCode::InstanceOf(_))));
+    let [new] = code.invisible_type_annotations() else {
+        panic!("expected a single invisible type annotation in the code");
+    };
+    assert!(matches!(new.target(), TypeAnnotationTarget::New { offset: 18 }));
+    let mut written = Vec::new();
+    class.write(&mut written).unwrap();
+    assert_eq!(written, std::fs::read("test/TypeAnnotations.class").unwrap());
+}
@@ -1,26 +1,315 @@
 #![allow(dead_code)]
-use crate::opcodes::{load_ops, OpCode};
-use crate::import::{load_u16, load_u32, load_u8, AccessFlags, ConstantItem};
+use crate::annotation::{type_annotations, Annotation, ElementValue, TypeAnnotation};
+use crate::opcodes::{load_ops, store_ops, OpCode};
+use crate::module::Module;
+use crate::record::RecordComponent;
+use crate::stack_map::StackMapFrame;
+use crate::constant_pool::ConstantPool;
+use crate::error::{ImportErrorKind, JavaImportError};
+use crate::import::{
+    load_u16, load_u32, load_u8, store_len_u16, store_len_u32, store_len_u8, store_u16, AccessFlags, ClassInfo,
+    ConstantItem, OffsetReader, Utf8,
+};
+/// Entry of a `LocalVariableTable` or `LocalVariableTypeTable` attribute, giving the name and type of a local
+/// variable in a range of code.
 #[derive(Debug)]
-pub(crate) struct LocalVariable {
+pub struct LocalVariable {
+    pub(crate) start_pc: u16,
+    pub(crate) length: u16,
+    pub(crate) name_index: u16,
+    /// Field descriptor in a `LocalVariableTable`, generic signature in a `LocalVariableTypeTable`.
+    pub(crate) descriptor_index: u16,
+    pub(crate) index: u16,
+}
+impl LocalVariable {
+    /// Offset at which the variable gets its value.
+    pub fn start_pc(&self) -> u16 {
+        self.start_pc
+    }
+    pub fn length(&self) -> u16 {
+        self.length
+    }
+    pub fn name(&self) -> Utf8 {
+        Utf8(self.name_index)
+    }
+    /// Erased field descriptor of the variable, e.g. `Ljava/util/List;`. For entries of a `LocalVariableTypeTable`,
+    /// it is the generic signature instead, e.g. `Ljava/util/List<TT;>;`.
+    pub fn descriptor(&self) -> Utf8 {
+        Utf8(self.descriptor_index)
+    }
+    /// Slot of the variable in the frame. `long` and `double` variables also take the next slot.
+    pub fn index(&self) -> u16 {
+        self.index
+    }
+    /// Checks if the variable is live at `pc`.
+    pub fn contains(&self, pc: u16) -> bool {
+        (self.start_pc as u32..self.start_pc as u32 + self.length as u32).contains(&(pc as u32))
+    }
+}
+/// Debug information about the local variable in a slot, created by [`Code::local_variable`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LocalVariableInfo {
     start_pc: u16,
     length: u16,
-    name_index: u16,
-    descriptor_index: u16,
-    index: u16,
+    name: Utf8,
+    descriptor: Utf8,
+    signature: Option<Utf8>,
 }
+impl LocalVariableInfo {
+    pub fn start_pc(&self) -> u16 {
+        self.start_pc
+    }
+    pub fn length(&self) -> u16 {
+        self.length
+    }
+    pub fn name(&self) -> Utf8 {
+        self.name
+    }
+    /// Erased field descriptor of the variable, e.g. `Ljava/util/List;`.
+    pub fn descriptor(&self) -> Utf8 {
+        self.descriptor
+    }
+    /// Generic signature of the variable, e.g. `Ljava/util/List<TT;>;`, if its type is generic.
+    pub fn signature(&self) -> Option<Utf8> {
+        self.signature
+    }
+}
+/// Entry of the exception table of a [`Code`] attribute.
 #[derive(Debug)]
-pub(crate) struct CodeException {
-    start_pc: u16,
-    end_pc: u16,
-    handler_pc: u16,
-    catch_type: u16,
+pub struct CodeException {
+    pub(crate) start_pc: u16,
+    pub(crate) end_pc: u16,
+    pub(crate) handler_pc: u16,
+    pub(crate) catch_type: u16,
+}
+impl CodeException {
+    /// Start of the protected range, inclusive.
+    pub fn start_pc(&self) -> u16 {
+        self.start_pc
+    }
+    /// End of the protected range, exclusive.
+    pub fn end_pc(&self) -> u16 {
+        self.end_pc
+    }
+    pub fn handler_pc(&self) -> u16 {
+        self.handler_pc
+    }
+    /// Class of exceptions this handler catches. Index 0 means it catches everything(used by `finally`).
+    pub fn catch_type(&self) -> ClassInfo {
+        ClassInfo(self.catch_type)
+    }
 }
 #[derive(Debug)]
-pub(crate) struct MethodParameter {
+pub struct MethodParameter {
     name_index: u16,
     access_flags: AccessFlags,
 }
+impl MethodParameter {
+    /// Name of this parameter. Index 0 means the parameter has no name.
+    pub fn name(&self) -> Utf8 {
+        Utf8(self.name_index)
+    }
+    pub fn access_flags(&self) -> AccessFlags {
+        self.access_flags
+    }
+}
+/// Entry of the `InnerClasses` attribute, describing a nested class which a class declares or refers to.
+#[derive(Debug)]
+pub struct InnerClass {
+    pub(crate) inner_class_info_index: u16,
+    pub(crate) outer_class_info_index: u16,
+    pub(crate) inner_name_index: u16,
+    pub(crate) inner_class_access_flags: AccessFlags,
+}
+impl InnerClass {
+    pub fn inner_class(&self) -> ClassInfo {
+        ClassInfo(self.inner_class_info_index)
+    }
+    /// Class which declares this one as a member. Index 0 for local and anonymous classes.
+    pub fn outer_class(&self) -> ClassInfo {
+        ClassInfo(self.outer_class_info_index)
+    }
+    /// Simple name of the class, e.g. `Inner`. Index 0 for anonymous classes.
+    pub fn inner_name(&self) -> Utf8 {
+        Utf8(self.inner_name_index)
+    }
+    /// Flags of the class as declared in the source, including `private`, `protected` and `static`.
+    pub fn access_flags(&self) -> AccessFlags {
+        self.inner_class_access_flags
+    }
+}
+/// Bytecode of a method, together with its exception table.
+#[derive(Debug)]
+pub struct Code {
+    pub(crate) max_stack: u16,
+    pub(crate) max_locals: u16,
+    pub(crate) ops: Box<[(OpCode, u16)]>,
+    /// Opcode byte each instruction in `ops` was encoded with.
+    pub(crate) opcodes: Box<[u8]>,
+    pub(crate) exceptions: Box<[CodeException]>,
+    pub(crate) attributes: Box<[(u16, Attribute)]>,
+}
+impl Code {
+    pub fn max_stack(&self) -> u16 {
+        self.max_stack
+    }
+    pub fn max_locals(&self) -> u16 {
+        self.max_locals
+    }
+    pub fn exceptions(&self) -> &[CodeException] {
+        &self.exceptions
+    }
+    /// Frames of the `StackMapTable` of this code, if it has one.
+    pub fn stack_map(&self) -> Option<&[StackMapFrame]> {
+        self.attributes.iter().find_map(|(_, attribute)| match attribute {
+            Attribute::StackMapTable { frames } => Some(&frames[..]),
+            _ => None,
+        })
+    }
+    /// Type annotations with `RUNTIME` retention on types used within the code, e.g. in casts or local variables.
+    pub fn type_annotations(&self) -> &[TypeAnnotation] {
+        type_annotations(&self.attributes, true)
+    }
+    /// Type annotations with `CLASS` retention on types used within the code.
+    pub fn invisible_type_annotations(&self) -> &[TypeAnnotation] {
+        type_annotations(&self.attributes, false)
+    }
+    /// Entries of the `LocalVariableTable`, present if the class was compiled with `-g`.
+    pub fn local_variables(&self) -> impl Iterator<Item = &LocalVariable> + '_ {
+        self.attributes
+            .iter()
+            .filter_map(|(_, attribute)| match attribute {
+                Attribute::LocalVariableTable { local_vars } => Some(local_vars.iter()),
+                _ => None,
+            })
+            .flatten()
+    }
+    /// Entries of the `LocalVariableTypeTable`, giving generic signatures of the local variables with a generic type.
+    pub fn local_variable_types(&self) -> impl Iterator<Item = &LocalVariable> + '_ {
+        self.attributes
+            .iter()
+            .filter_map(|(_, attribute)| match attribute {
+                Attribute::LocalVariableTypeTable { local_vars } => Some(local_vars.iter()),
+                _ => None,
+            })
+            .flatten()
+    }
+    /// Name and type of the local variable in slot `index` at `pc`, if the code has debug information for it.
+    pub fn local_variable(&self, index: u16, pc: u16) -> Option<LocalVariableInfo> {
+        let variable = self
+            .local_variables()
+            .find(|variable| variable.index == index && variable.contains(pc))?;
+        // Both tables describe the same variable with the same range and slot.
+        let signature = self
+            .local_variable_types()
+            .find(|typed| {
+                (typed.index, typed.start_pc, typed.length) == (variable.index, variable.start_pc, variable.length)
+            })
+            .map(LocalVariable::descriptor);
+        Some(LocalVariableInfo {
+            start_pc: variable.start_pc,
+            length: variable.length,
+            name: variable.name(),
+            descriptor: variable.descriptor(),
+            signature,
+        })
+    }
+    /// Decoded instructions of this method, paired with their byte offsets.
+    pub fn ops(&self) -> &[(OpCode, u16)] {
+        &self.ops
+    }
+    /// Iterates over `(offset, instruction)` pairs, in the order they appear in the bytecode.
+    pub fn instructions(&self) -> Instructions<'_> {
+        Instructions {
+            ops: self.ops.iter(),
+        }
+    }
+    /// Iterates over the instructions together with the exact opcode bytes they were encoded with.
+    pub fn raw_instructions(&self) -> impl ExactSizeIterator<Item = RawInstruction<'_>> + DoubleEndedIterator + '_ {
+        self.ops
+            .iter()
+            .zip(self.opcodes.iter())
+            .map(|((op, offset), opcode)| RawInstruction {
+                offset: *offset,
+                opcode: *opcode,
+                op,
+            })
+    }
+    fn write_body<W: std::io::Write>(&self, dst: &mut W) -> std::io::Result<()> {
+        store_u16(dst, self.max_stack)?;
+        store_u16(dst, self.max_locals)?;
+        let mut code = Vec::new();
+        store_ops(&self.ops, &self.opcodes, &mut code)?;
+        store_len_u32(dst, code.len())?;
+        dst.write_all(&code)?;
+        store_len_u16(dst, self.exceptions.len())?;
+        for exception in self.exceptions.iter() {
+            store_u16(dst, exception.start_pc)?;
+            store_u16(dst, exception.end_pc)?;
+            store_u16(dst, exception.handler_pc)?;
+            store_u16(dst, exception.catch_type)?;
+        }
+        Attribute::write_all(&self.attributes, dst)
+    }
+    /// Instruction starting at byte `offset`, if there is one.
+    pub fn instruction_at(&self, offset: u16) -> Option<&OpCode> {
+        let index = self
+            .ops
+            .binary_search_by_key(&offset, |(_, op_offset)| *op_offset)
+            .ok()?;
+        Some(&self.ops[index].0)
+    }
+}
+/// Instruction as it is encoded in the class file, created by [`Code::raw_instructions`].
+///
+/// The opcode byte tells apart forms which decode to the same [`OpCode`], e.g. `iload_1` and `iload 1`, or `ldc`
+/// and `ldc_w`.
+#[derive(Debug, Clone, Copy)]
+pub struct RawInstruction<'a> {
+    offset: u16,
+    opcode: u8,
+    op: &'a OpCode,
+}
+impl<'a> RawInstruction<'a> {
+    pub fn offset(&self) -> u16 {
+        self.offset
+    }
+    /// Opcode byte of this instruction, `0xc4` for instructions prefixed by `wide`.
+    pub fn opcode(&self) -> u8 {
+        self.opcode
+    }
+    pub fn op(&self) -> &'a OpCode {
+        self.op
+    }
+    pub fn is_wide(&self) -> bool {
+        self.opcode == 0xc4
+    }
+    /// Size of this instruction in bytes, including its operands and padding.
+    pub fn size(&self) -> u16 {
+        // Loaded instructions are inside of the bytecode, which is at most 65535 bytes long.
+        self.op.encoded_size(self.opcode, self.offset) as u16
+    }
+}
+/// Iterator over the instructions of a [`Code`] attribute, created by [`Code::instructions`].
+#[derive(Debug, Clone)]
+pub struct Instructions<'a> {
+    ops: std::slice::Iter<'a, (OpCode, u16)>,
+}
+impl<'a> Iterator for Instructions<'a> {
+    type Item = (u16, &'a OpCode);
+    fn next(&mut self) -> Option<Self::Item> {
+        self.ops.next().map(|(op, offset)| (*offset, op))
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.ops.size_hint()
+    }
+}
+impl DoubleEndedIterator for Instructions<'_> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.ops.next_back().map(|(op, offset)| (*offset, op))
+    }
+}
+impl ExactSizeIterator for Instructions<'_> {}
 #[derive(Debug)]
 pub(crate) struct BootstrapMethod {
     pub(crate) bootstrap_method_ref: u16,
@@ -28,29 +317,35 @@ pub(crate) struct BootstrapMethod {
 }
 #[derive(Debug)]
 pub(crate) enum Attribute {
-    Unknown,
-    Code {
-        max_stack: u16,
-        max_locals: u16,
-        ops: Box<[(OpCode, u16)]>,
-        exceptions: Box<[CodeException]>,
-        attributes: Box<[Attribute]>,
+    /// Attribute which is not decoded, kept as raw bytes so it can be written back.
+    Unknown {
+        data: Box<[u8]>,
     },
+    Code(Code),
     LineNumberTable {
         pc_lines: Box<[(u16, u16)]>,
     },
+    StackMapTable {
+        frames: Box<[StackMapFrame]>,
+    },
     SourceFile {
         sourcefile_index: u16,
     },
     LocalVariableTable {
         local_vars: Box<[LocalVariable]>,
     },
+    LocalVariableTypeTable {
+        local_vars: Box<[LocalVariable]>,
+    },
     NestHost {
         host_class_index: u16,
     },
     NestMembers {
         classes: Box<[u16]>,
     },
+    PermittedSubclasses {
+        classes: Box<[u16]>,
+    },
     MethodParameters {
         parameters: Box<[MethodParameter]>,
     },
@@ -71,13 +366,47 @@ pub(crate) enum Attribute {
         method_index: u16,
     },
     Deprecated,
+    InnerClasses {
+        classes: Box<[InnerClass]>,
+    },
+    Record {
+        components: Box<[RecordComponent]>,
+    },
+    Module(Module),
+    ModulePackages {
+        packages: Box<[u16]>,
+    },
+    ModuleMainClass {
+        main_class_index: u16,
+    },
+    RuntimeVisibleAnnotations {
+        annotations: Box<[Annotation]>,
+    },
+    RuntimeInvisibleAnnotations {
+        annotations: Box<[Annotation]>,
+    },
+    RuntimeVisibleParameterAnnotations {
+        parameters: Box<[Box<[Annotation]>]>,
+    },
+    RuntimeInvisibleParameterAnnotations {
+        parameters: Box<[Box<[Annotation]>]>,
+    },
+    AnnotationDefault {
+        value: ElementValue,
+    },
+    RuntimeVisibleTypeAnnotations {
+        annotations: Box<[TypeAnnotation]>,
+    },
+    RuntimeInvisibleTypeAnnotations {
+        annotations: Box<[TypeAnnotation]>,
+    },
 }
 impl Attribute {
     fn decode_attribute<R: std::io::Read>(
-        src: &mut R,
+        src: &mut OffsetReader<R>,
         attribute_name: &str,
         const_items: &[ConstantItem],
-    ) -> Result<Self, std::io::Error> {
+    ) -> Result<Self, JavaImportError> {
         match attribute_name {
             "LineNumberTable" => {
                 let line_number_table_length = load_u16(src)?;
@@ -91,7 +420,7 @@ impl Attribute {
                     pc_lines: pc_lines.into(),
                 })
             }
-            "LocalVariableTable" => {
+            "LocalVariableTable" | "LocalVariableTypeTable" => {
                 let length = load_u16(src)? as usize;
                 let mut local_vars = Vec::with_capacity(length);
                 for _ in 0..length {
@@ -108,9 +437,12 @@ impl Attribute {
                         index,
                     })
                 }
-                Ok(Self::LocalVariableTable {
-                    local_vars: local_vars.into(),
-                })
+                let local_vars = local_vars.into();
+                if attribute_name == "LocalVariableTable" {
+                    Ok(Self::LocalVariableTable { local_vars })
+                } else {
+                    Ok(Self::LocalVariableTypeTable { local_vars })
+                }
             }
             "MethodParameters" => {
                 let parameters_count = load_u8(src)? as usize;
@@ -128,11 +460,55 @@ impl Attribute {
                 })
             }
             "Deprecated" => Ok(Self::Deprecated),
-            "Record" => Ok(Self::Unknown),        // IDK what it does.
-            "StackMapTable" => Ok(Self::Unknown), //Not worth the effort.
-            "RuntimeVisibleAnnotations" => Ok(Self::Unknown), //TODO: Handle this at some point.
-            "LocalVariableTypeTable" => Ok(Self::Unknown), //TODO: Not needed, but nice to have.
-            "InnerClasses" => Ok(Self::Unknown),  //TODO: Handle inner classes!
+            "Record" => {
+                let components_count = load_u16(src)? as usize;
+                let mut components = Vec::with_capacity(components_count);
+                for _ in 0..components_count {
+                    components.push(RecordComponent::read(src, const_items)?);
+                }
+                Ok(Self::Record {
+                    components: components.into(),
+                })
+            }
+            "StackMapTable" => Ok(Self::StackMapTable {
+                frames: StackMapFrame::read_table(src)?,
+            }),
+            "RuntimeVisibleAnnotations" => Ok(Self::RuntimeVisibleAnnotations {
+                annotations: Annotation::read_list(src)?,
+            }),
+            "RuntimeInvisibleAnnotations" => Ok(Self::RuntimeInvisibleAnnotations {
+                annotations: Annotation::read_list(src)?,
+            }),
+            "RuntimeVisibleParameterAnnotations" => Ok(Self::RuntimeVisibleParameterAnnotations {
+                parameters: Annotation::read_parameters(src)?,
+            }),
+            "RuntimeInvisibleParameterAnnotations" => Ok(Self::RuntimeInvisibleParameterAnnotations {
+                parameters: Annotation::read_parameters(src)?,
+            }),
+            "AnnotationDefault" => Ok(Self::AnnotationDefault {
+                value: ElementValue::read(src, 0)?,
+            }),
+            "RuntimeVisibleTypeAnnotations" => Ok(Self::RuntimeVisibleTypeAnnotations {
+                annotations: TypeAnnotation::read_list(src)?,
+            }),
+            "RuntimeInvisibleTypeAnnotations" => Ok(Self::RuntimeInvisibleTypeAnnotations {
+                annotations: TypeAnnotation::read_list(src)?,
+            }),
+            "InnerClasses" => {
+                let number_of_classes = load_u16(src)? as usize;
+                let mut classes = Vec::with_capacity(number_of_classes);
+                for _ in 0..number_of_classes {
+                    classes.push(InnerClass {
+                        inner_class_info_index: load_u16(src)?,
+                        outer_class_info_index: load_u16(src)?,
+                        inner_name_index: load_u16(src)?,
+                        inner_class_access_flags: AccessFlags::read(src)?,
+                    });
+                }
+                Ok(Self::InnerClasses {
+                    classes: classes.into(),
+                })
+            }
             "Exceptions" => {
                 let number_exceptions = load_u16(src)? as usize;
                 let mut exceptions = Vec::with_capacity(number_exceptions);
@@ -180,6 +556,31 @@ impl Attribute {
                     classes: classes.into(),
                 })
             }
+            "Module" => Ok(Self::Module(Module::read(src)?)),
+            "ModulePackages" => {
+                let package_count = load_u16(src)? as usize;
+                let mut packages = Vec::with_capacity(package_count);
+                for _ in 0..package_count {
+                    packages.push(load_u16(src)?);
+                }
+                Ok(Self::ModulePackages {
+                    packages: packages.into(),
+                })
+            }
+            "ModuleMainClass" => {
+                let main_class_index = load_u16(src)?;
+                Ok(Self::ModuleMainClass { main_class_index })
+            }
+            "PermittedSubclasses" => {
+                let class_count = load_u16(src)? as usize;
+                let mut classes = Vec::with_capacity(class_count);
+                for _ in 0..class_count {
+                    classes.push(load_u16(src)?);
+                }
+                Ok(Self::PermittedSubclasses {
+                    classes: classes.into(),
+                })
+            }
             "SourceFile" => {
                 let sourcefile_index = load_u16(src)?;
                 Ok(Self::SourceFile { sourcefile_index })
@@ -188,7 +589,7 @@ impl Attribute {
                 let max_stack = load_u16(src)?;
                 let max_locals = load_u16(src)?;
                 let code_length = load_u32(src)?;
-                let ops = load_ops(src, code_length)?;
+                let (ops, opcodes) = load_ops(src, code_length)?;
                 let exception_table_length = load_u16(src)? as usize;
                 //'assert_eq!(exception_table_length, 0, "Exceptions not supported yet!");
                 let mut exceptions = Vec::with_capacity(exception_table_length);
@@ -209,15 +610,16 @@ impl Attribute {
                 //println!("ac:{attributes_count}");
                 let mut attributes = Vec::with_capacity(attributes_count as usize);
                 for _ in 0..attributes_count {
-                    attributes.push(Self::read(src, const_items)?);
+                    attributes.push(Self::read_nested(src, const_items)?);
                 }
-                Ok(Self::Code {
+                Ok(Self::Code(Code {
                     max_stack,
                     max_locals,
                     ops: ops.into(),
+                    opcodes: opcodes.into(),
                     attributes: attributes.into(),
                     exceptions: exceptions.into(),
-                })
+                }))
             }
             "ConstantValue" => {
                 let value_index = load_u16(src)?;
@@ -231,55 +633,239 @@ impl Attribute {
                     method_index,
                 })
             }
-            "RuntimeVisibleParameterAnnotations" => Ok(Self::Unknown), //TODO: Needed in the future.
-            "RuntimeVisibleTypeAnnotations" => Ok(Self::Unknown),      //TODO: Needed in the future.
-            "AnnotationDefault" => Ok(Self::Unknown),                  //TODO: Needed in the future.
-            "PermittedSubclasses" => Ok(Self::Unknown),                //TODO: Needed in the future.
-            "RuntimeInvisibleTypeAnnotations" => Ok(Self::Unknown), //TODO: Not needed, but might be needed in the future.
-            "RuntimeInvisibleParameterAnnotations" => Ok(Self::Unknown), //TODO: Not needed, but might be needed in the future.
-            "RuntimeInvisibleAnnotations" => Ok(Self::Unknown), //TODO: Not needed, but might be needed in the future.
-            _ => {
-                if attribute_name.len() < 8 || attribute_name.contains(".java") {
-                    return Err(std::io::Error::new(
-                        std::io::ErrorKind::Other,
-                        format!("Nonsense attribute \"{attribute_name}\""),
-                    ));
-                }
-                todo!("Can't read attributes of type {attribute_name}!")
-            }
+            // JVMS requires unrecognized attributes to be silently ignored.
+            _ => Self::read_unknown(src),
         }
     }
     pub(crate) fn read<R: std::io::Read>(
-        src: &mut R,
+        src: &mut OffsetReader<R>,
+        const_items: &[ConstantItem],
+    ) -> Result<(u16, Self), JavaImportError> {
+        Self::read_checked(src, const_items, true)
+    }
+    /// Reads an attribute of a `Code` attribute or a record component.
+    pub(crate) fn read_nested<R: std::io::Read>(
+        src: &mut OffsetReader<R>,
         const_items: &[ConstantItem],
-    ) -> Result<Self, std::io::Error> {
+    ) -> Result<(u16, Self), JavaImportError> {
+        Self::read_checked(src, const_items, false)
+    }
+    /// Reads an attribute. `Code` and `Record` attributes contain other attributes, so they are only decoded at the
+    /// `top_level`(in a class, field or method): otherwise a malicious file could nest them deep enough to overflow
+    /// the stack. A nested `Code` is rejected, and a nested `Record` kept as raw bytes.
+    fn read_checked<R: std::io::Read>(
+        src: &mut OffsetReader<R>,
+        const_items: &[ConstantItem],
+        top_level: bool,
+    ) -> Result<(u16, Self), JavaImportError> {
         let attribute_name_index = load_u16(src)?;
-        //assert!(attribute_name_index > 0);
-        if attribute_name_index == 0 {
-            return Err(std::io::Error::new(
-                std::io::ErrorKind::Other,
-                "AttributeNameIndex is 0!",
-            ));
+        let Some(attribute_name) = ConstantPool::new(const_items).utf8(attribute_name_index) else {
+            return Err(src.error(ImportErrorKind::BadConstantIndex(attribute_name_index)));
+        };
+        if !top_level && attribute_name == "Code" {
+            return Err(src.error(ImportErrorKind::NestedCode).in_attribute(attribute_name));
+        }
+        let attribute_length = load_u32(src)?;
+        let attribute_start = src.offset();
+        // The length is not trusted for the allocation: a truncated file may claim an attribute of up to 4 GiB.
+        let mut attibute_data = Vec::new();
+        let mut attribute_src = <&mut OffsetReader<R> as std::io::Read>::take(src, attribute_length as u64);
+        std::io::Read::read_to_end(&mut attribute_src, &mut attibute_data)?;
+        if attibute_data.len() as u64 != attribute_length as u64 {
+            return Err(src.error(ImportErrorKind::UnexpectedEof).in_attribute(attribute_name));
         }
-        let attribute_name = &const_items
-            .get((attribute_name_index - 1) as usize)
-            .ok_or_else(|| {
-                std::io::Error::new(
-                    std::io::ErrorKind::Other,
-                    "AttributeNameIndex is outside ConstItem.",
-                )
+        let mut attribute_src = OffsetReader::new(&attibute_data[..], attribute_start);
+        let attribute = match attribute_name {
+            "Record" if !top_level => Self::read_unknown(&mut attribute_src),
+            _ => Self::decode_attribute(&mut attribute_src, attribute_name, const_items),
+        }
+        .map_err(|err| {
+                let err = match err.kind() {
+                    ImportErrorKind::UnexpectedEof => err.with_kind(ImportErrorKind::TruncatedAttribute {
+                        length: attribute_length,
+                    }),
+                    _ => err,
+                };
+                err.at(attribute_src.offset()).in_attribute(attribute_name)
             })?;
-        let attribute_name = if let ConstantItem::Utf8(attribute_name) = attribute_name {
-            attribute_name
-        } else {
-            return Err(std::io::Error::new(
-                std::io::ErrorKind::Other,
-                "Atribute name must be a UTF8 string!",
-            ));
-        };
-        let attribute_length = load_u32(src)? as usize;
-        let mut attibute_data = vec![0; attribute_length];
-        src.read_exact(&mut attibute_data)?;
-        Self::decode_attribute(&mut &attibute_data[..], attribute_name, const_items)
+        let used = (attribute_src.offset() - attribute_start) as u32;
+        if used != attribute_length {
+            return Err(attribute_src
+                .error(ImportErrorKind::WrongAttributeLength {
+                    length: attribute_length,
+                    used,
+                })
+                .in_attribute(attribute_name));
+        }
+        Ok((attribute_name_index, attribute))
+    }
+    /// Keeps the rest of the attribute as raw bytes.
+    fn read_unknown<R: std::io::Read>(src: &mut OffsetReader<R>) -> Result<Self, JavaImportError> {
+        let mut data = Vec::new();
+        std::io::Read::read_to_end(src, &mut data)?;
+        Ok(Self::Unknown { data: data.into() })
+    }
+    /// Writes an attribute count followed by `attributes`.
+    pub(crate) fn write_all<W: std::io::Write>(attributes: &[(u16, Self)], dst: &mut W) -> std::io::Result<()> {
+        store_len_u16(dst, attributes.len())?;
+        for (name_index, attribute) in attributes {
+            let mut body = Vec::new();
+            attribute.write_body(&mut body)?;
+            store_u16(dst, *name_index)?;
+            store_len_u32(dst, body.len())?;
+            dst.write_all(&body)?;
+        }
+        Ok(())
     }
+    fn write_body<W: std::io::Write>(&self, dst: &mut W) -> std::io::Result<()> {
+        match self {
+            Self::Unknown { data } => dst.write_all(data),
+            Self::Code(code) => code.write_body(dst),
+            Self::LineNumberTable { pc_lines } => {
+                store_len_u16(dst, pc_lines.len())?;
+                for (start_pc, line_number) in pc_lines.iter() {
+                    store_u16(dst, *start_pc)?;
+                    store_u16(dst, *line_number)?;
+                }
+                Ok(())
+            }
+            Self::StackMapTable { frames } => StackMapFrame::write_table(frames, dst),
+            Self::LocalVariableTable { local_vars } | Self::LocalVariableTypeTable { local_vars } => {
+                store_len_u16(dst, local_vars.len())?;
+                for local_var in local_vars.iter() {
+                    store_u16(dst, local_var.start_pc)?;
+                    store_u16(dst, local_var.length)?;
+                    store_u16(dst, local_var.name_index)?;
+                    store_u16(dst, local_var.descriptor_index)?;
+                    store_u16(dst, local_var.index)?;
+                }
+                Ok(())
+            }
+            Self::MethodParameters { parameters } => {
+                store_len_u8(dst, parameters.len())?;
+                for parameter in parameters.iter() {
+                    store_u16(dst, parameter.name_index)?;
+                    parameter.access_flags.write(dst)?;
+                }
+                Ok(())
+            }
+            Self::BootstrapMethods { bootstrap_methods } => {
+                store_len_u16(dst, bootstrap_methods.len())?;
+                for bootstrap_method in bootstrap_methods.iter() {
+                    store_u16(dst, bootstrap_method.bootstrap_method_ref)?;
+                    store_len_u16(dst, bootstrap_method.bootstrap_args.len())?;
+                    for arg in bootstrap_method.bootstrap_args.iter() {
+                        store_u16(dst, *arg)?;
+                    }
+                }
+                Ok(())
+            }
+            Self::NestMembers { classes: indices }
+            | Self::PermittedSubclasses { classes: indices }
+            | Self::ModulePackages { packages: indices }
+            | Self::Exceptions { exceptions: indices } => {
+                store_len_u16(dst, indices.len())?;
+                for index in indices.iter() {
+                    store_u16(dst, *index)?;
+                }
+                Ok(())
+            }
+            Self::SourceFile {
+                sourcefile_index: index,
+            }
+            | Self::NestHost {
+                host_class_index: index,
+            }
+            | Self::Signature { signature: index }
+            | Self::ConstantValue { value_index: index }
+            | Self::ModuleMainClass {
+                main_class_index: index,
+            } => store_u16(dst, *index),
+            Self::Module(module) => module.write(dst),
+            Self::EnclosingMethod {
+                class_index,
+                method_index,
+            } => {
+                store_u16(dst, *class_index)?;
+                store_u16(dst, *method_index)
+            }
+            Self::Deprecated => Ok(()),
+            Self::Record { components } => {
+                store_len_u16(dst, components.len())?;
+                components.iter().try_for_each(|component| component.write(dst))
+            }
+            Self::InnerClasses { classes } => {
+                store_len_u16(dst, classes.len())?;
+                for class in classes.iter() {
+                    store_u16(dst, class.inner_class_info_index)?;
+                    store_u16(dst, class.outer_class_info_index)?;
+                    store_u16(dst, class.inner_name_index)?;
+                    class.inner_class_access_flags.write(dst)?;
+                }
+                Ok(())
+            }
+            Self::RuntimeVisibleAnnotations { annotations } | Self::RuntimeInvisibleAnnotations { annotations } => {
+                Annotation::write_list(annotations, dst)
+            }
+            Self::RuntimeVisibleParameterAnnotations { parameters }
+            | Self::RuntimeInvisibleParameterAnnotations { parameters } => Annotation::write_parameters(parameters, dst),
+            Self::AnnotationDefault { value } => value.write(dst),
+            Self::RuntimeVisibleTypeAnnotations { annotations }
+            | Self::RuntimeInvisibleTypeAnnotations { annotations } => TypeAnnotation::write_list(annotations, dst),
+        }
+    }
+}
+#[test]
+fn raw_instruction_sizes() {
+    for path in ["test/Identity.class", "test/Sieve.class", "test/ConstantPool.class"] {
+        let mut file = std::fs::File::open(path).unwrap();
+        let class = crate::import_class_file(&mut file).unwrap();
+        for code in class.methods().iter().filter_map(|method| method.code()) {
+            let raw: Vec<_> = code.raw_instructions().collect();
+            for pair in raw.windows(2) {
+                assert_eq!(pair[0].offset() + pair[0].size(), pair[1].offset(), "{:?}", pair[0]);
+            }
+        }
+    }
+    let mut file = std::fs::File::open("test/Identity.class").unwrap();
+    let class = crate::import_class_file(&mut file).unwrap();
+    // identityLong: lload_0, lreturn
+    let code = class.methods()[2].code().unwrap();
+    let opcodes: Vec<_> = code.raw_instructions().map(|raw| raw.opcode()).collect();
+    assert_eq!(opcodes, [0x1e, 0xad]);
+    assert!(matches!(code.raw_instructions().next().unwrap().op(), OpCode::LLoad(0)));
+    let (ops, _) = load_ops(&mut OffsetReader::new(&[0x7c, 0x7d][..], 0), 2).unwrap();
+    assert!(matches!((&ops[0].0, &ops[1].0), (OpCode::IUShr, OpCode::LUShr)));
+}
+#[test]
+fn local_variable_debug_info() {
+    let mut file = std::fs::File::open("test/Locals.class").unwrap();
+    let class = crate::import_class_file(&mut file).unwrap();
+    let code = class.methods()[1].code().unwrap();
+    let describe = |index, pc| {
+        code.local_variable(index, pc).map(|variable| {
+            (
+                class.get_utf8(variable.name()).unwrap(),
+                class.get_utf8(variable.descriptor()).unwrap(),
+                variable.signature().and_then(|signature| class.get_utf8(signature)),
+            )
+        })
+    };
+    assert_eq!(describe(0, 0), Some(("value", "Ljava/lang/Object;", Some("TT;"))));
+    assert_eq!(describe(1, 56), Some(("count", "I", None)));
+    // `values` is stored at offset 7, and only live from the next instruction.
+    assert_eq!(describe(2, 7), None);
+    assert_eq!(describe(2, 8), Some(("values", "Ljava/util/List;", Some("Ljava/util/List<TT;>;"))));
+    // Slot 3 holds `i` in the loop, and `total` after it.
+    assert_eq!(describe(3, 20), Some(("i", "I", None)));
+    assert_eq!(describe(3, 40), Some(("total", "J", None)));
+    assert_eq!(describe(4, 40), None);
+    assert_eq!(
+        describe(5, 50),
+        Some(("byName", "Ljava/util/Map;", Some("Ljava/util/Map<Ljava/lang/String;Ljava/util/List<TT;>;>;")))
+    );
+    assert_eq!((code.local_variables().count(), code.local_variable_types().count()), (6, 3));
+    let mut written = Vec::new();
+    class.write(&mut written).unwrap();
+    assert_eq!(written, std::fs::read("test/Locals.class").unwrap());
 }
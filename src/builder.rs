@@ -0,0 +1,1041 @@
+use crate::attribute::{Attribute, Code, CodeException};
+use crate::constant_pool::ConstantPool;
+use crate::descriptor::MethodDescriptor;
+use crate::field::Field;
+use crate::import::{AccessFlags, ConstantItem, JavaClassFile};
+use crate::method::Method;
+use crate::opcodes::{LookupSwitch, OpCode, TableSwitch};
+use crate::stack_map::{compute_frames, ClassHierarchy, FrameContext};
+use crate::IString;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+/// Reason why a class or method could not be built.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BuildError {
+    /// The constant pool needs more than 65534 entries, or a string is longer than 65535 bytes when encoded.
+    ConstantPoolOverflow,
+    /// The class has more than 65535 fields, methods or interfaces.
+    TooManyMembers,
+    /// The descriptor of a method is not valid.
+    InvalidDescriptor(IString),
+    /// Instruction at this offset has no encoding, e.g. `IConst(7)`.
+    Unencodable(u16),
+    /// A label was bound twice.
+    DuplicateLabel,
+    /// A label was used, but never bound.
+    UnboundLabel,
+    /// A label was created by a different [`CodeBuilder`].
+    ForeignLabel,
+    /// A `lookupswitch` has the same key twice, or a `tableswitch` has no cases or its keys overflow an `i32`.
+    InvalidSwitch,
+    /// Target of the branch at this offset is too far away to be encoded.
+    BranchTooFar(u16),
+    /// The bytecode is empty or longer than 65535 bytes.
+    InvalidCodeLength,
+    /// A branch or handler at this offset does not point to the start of an instruction.
+    InvalidBranchTarget(u16),
+    /// Instruction at this offset pops more values than there are on the stack.
+    StackUnderflow(u16),
+    /// Instruction at this offset is reached with different stack depths.
+    StackMismatch(u16),
+    /// The stack effect of the instruction at this offset is unknown, because it refers to a bad constant.
+    UnknownStackEffect(u16),
+    /// Execution can continue past the last instruction.
+    FallsOffEnd,
+    /// `jsr` or `ret` at this offset, which class files with stack map frames can't use.
+    Subroutine(u16),
+}
+impl std::fmt::Display for BuildError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::ConstantPoolOverflow => write!(f, "constant pool overflow"),
+            Self::TooManyMembers => write!(f, "class has more than 65535 fields, methods or interfaces"),
+            Self::InvalidDescriptor(descriptor) => write!(f, "invalid method descriptor {descriptor}"),
+            Self::Unencodable(offset) => write!(f, "instruction at {offset} can't be encoded"),
+            Self::DuplicateLabel => write!(f, "label bound twice"),
+            Self::UnboundLabel => write!(f, "label used, but never bound"),
+            Self::ForeignLabel => write!(f, "label belongs to another code builder"),
+            Self::InvalidSwitch => write!(f, "switch has duplicate keys or no cases"),
+            Self::BranchTooFar(offset) => write!(f, "branch target of instruction at {offset} is too far"),
+            Self::InvalidCodeLength => write!(f, "code is empty or too long"),
+            Self::InvalidBranchTarget(offset) => {
+                write!(f, "target {offset} is not the start of an instruction")
+            }
+            Self::StackUnderflow(offset) => write!(f, "stack underflow at {offset}"),
+            Self::StackMismatch(offset) => write!(f, "inconsistent stack depth at {offset}"),
+            Self::UnknownStackEffect(offset) => write!(f, "unknown stack effect at {offset}"),
+            Self::FallsOffEnd => write!(f, "execution falls off the end of code"),
+            Self::Subroutine(offset) => write!(f, "subroutine at {offset} can't be used with stack map frames"),
+        }
+    }
+}
+impl std::error::Error for BuildError {}
+/// Constant pool of a class being built. Each constant is only added once, so adding the same constant again
+/// returns the index of the existing entry.
+#[derive(Debug, Default)]
+pub struct ConstantPoolBuilder {
+    items: Vec<ConstantItem>,
+    // Constants are keyed by their encoding, which also tells apart floats with different bit patterns.
+    indices: HashMap<Vec<u8>, u16>,
+    overflowed: bool,
+}
+impl ConstantPoolBuilder {
+    /// Pool holding the constants of an existing class, which are reused when they are added again.
+    pub(crate) fn from_items(items: Vec<ConstantItem>) -> Self {
+        let mut indices = HashMap::with_capacity(items.len());
+        for (index, item) in items.iter().enumerate() {
+            let mut key = Vec::new();
+            if matches!(item, ConstantItem::Padding) || item.write(&mut key).is_err() {
+                continue;
+            }
+            indices.entry(key).or_insert(index as u16 + 1);
+        }
+        Self {
+            items,
+            indices,
+            overflowed: false,
+        }
+    }
+    pub(crate) fn into_items(self) -> Vec<ConstantItem> {
+        self.items
+    }
+    pub(crate) fn overflowed(&self) -> bool {
+        self.overflowed
+    }
+    fn add(&mut self, item: ConstantItem) -> u16 {
+        let mut key = Vec::new();
+        if item.write(&mut key).is_err() {
+            self.overflowed = true;
+            return 0;
+        }
+        if let Some(index) = self.indices.get(&key) {
+            return *index;
+        }
+        let wide = matches!(item, ConstantItem::Long(_) | ConstantItem::Double(_));
+        let index = self.items.len() + 1;
+        if index + wide as usize > u16::MAX as usize - 1 {
+            self.overflowed = true;
+            return 0;
+        }
+        self.items.push(item);
+        if wide {
+            self.items.push(ConstantItem::Padding);
+        }
+        self.indices.insert(key, index as u16);
+        index as u16
+    }
+    pub fn utf8(&mut self, string: &str) -> u16 {
+        self.add(ConstantItem::Utf8(string.into()))
+    }
+    /// Class with the internal name `name`, e.g. `java/lang/String`.
+    pub fn class(&mut self, name: &str) -> u16 {
+        let name_index = self.utf8(name);
+        self.add(ConstantItem::Class { name_index })
+    }
+    pub fn string(&mut self, value: &str) -> u16 {
+        let string_index = self.utf8(value);
+        self.add(ConstantItem::ConstString { string_index })
+    }
+    pub fn integer(&mut self, value: i32) -> u16 {
+        self.add(ConstantItem::Intiger(value))
+    }
+    pub fn float(&mut self, value: f32) -> u16 {
+        self.add(ConstantItem::Float(value))
+    }
+    pub fn long(&mut self, value: i64) -> u16 {
+        self.add(ConstantItem::Long(value))
+    }
+    pub fn double(&mut self, value: f64) -> u16 {
+        self.add(ConstantItem::Double(value))
+    }
+    pub fn name_and_type(&mut self, name: &str, descriptor: &str) -> u16 {
+        let name_index = self.utf8(name);
+        let descriptor_index = self.utf8(descriptor);
+        self.add(ConstantItem::NameAndType {
+            name_index,
+            descriptor_index,
+        })
+    }
+    pub fn field_ref(&mut self, class: &str, name: &str, descriptor: &str) -> u16 {
+        let class_index = self.class(class);
+        let name_and_type_index = self.name_and_type(name, descriptor);
+        self.add(ConstantItem::FieldRef {
+            class_index,
+            name_and_type_index,
+        })
+    }
+    pub fn method_ref(&mut self, class: &str, name: &str, descriptor: &str) -> u16 {
+        let class_index = self.class(class);
+        let name_and_type_index = self.name_and_type(name, descriptor);
+        self.add(ConstantItem::MethodRef {
+            class_index,
+            name_and_type_index,
+        })
+    }
+    pub fn interface_method_ref(&mut self, class: &str, name: &str, descriptor: &str) -> u16 {
+        let class_index = self.class(class);
+        let name_and_type_index = self.name_and_type(name, descriptor);
+        self.add(ConstantItem::InterfaceMethodRef {
+            class_index,
+            name_and_type_index,
+        })
+    }
+    pub fn method_type(&mut self, descriptor: &str) -> u16 {
+        let descriptor_index = self.utf8(descriptor);
+        self.add(ConstantItem::MethodType { descriptor_index })
+    }
+    /// Read only view of the constants added so far.
+    pub fn pool(&self) -> ConstantPool<'_> {
+        ConstantPool::new(&self.items)
+    }
+    fn is_wide(&self, index: u16) -> bool {
+        index != 0
+            && matches!(
+                self.items.get(index as usize - 1),
+                Some(ConstantItem::Long(_) | ConstantItem::Double(_))
+            )
+    }
+}
+/// Builds a [`JavaClassFile`] from scratch.
+///
+/// Constants are added to the constant pool as they are needed. Classes are public, extend `java/lang/Object` and
+/// use version 52.0 unless told otherwise. From version 50.0 on, methods get a `StackMapTable`.
+#[derive(Debug)]
+pub struct ClassBuilder {
+    name: IString,
+    super_name: IString,
+    interface_names: Vec<IString>,
+    hierarchy: Option<Hierarchy>,
+    version: (u16, u16),
+    flags: AccessFlags,
+    pool: ConstantPoolBuilder,
+    this_class: u16,
+    super_class: u16,
+    interfaces: Vec<u16>,
+    fields: Vec<Field>,
+    methods: Vec<Method>,
+    attributes: Vec<(u16, Attribute)>,
+}
+/// Hierarchy set by [`ClassBuilder::hierarchy`].
+struct Hierarchy(Box<dyn ClassHierarchy>);
+impl std::fmt::Debug for Hierarchy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Hierarchy")
+    }
+}
+impl ClassBuilder {
+    /// Starts building the class with the internal name `name`, e.g. `com/example/Main`.
+    pub fn new(name: &str) -> Self {
+        let mut pool = ConstantPoolBuilder::default();
+        let this_class = pool.class(name);
+        let super_class = pool.class("java/lang/Object");
+        Self {
+            name: name.into(),
+            super_name: "java/lang/Object".into(),
+            interface_names: Vec::new(),
+            hierarchy: None,
+            version: (52, 0),
+            flags: AccessFlags::PUBLIC | AccessFlags::SUPER,
+            pool,
+            this_class,
+            super_class,
+            interfaces: Vec::new(),
+            fields: Vec::new(),
+            methods: Vec::new(),
+            attributes: Vec::new(),
+        }
+    }
+    pub fn version(&mut self, major: u16, minor: u16) -> &mut Self {
+        self.version = (major, minor);
+        self
+    }
+    pub fn access_flags(&mut self, flags: AccessFlags) -> &mut Self {
+        self.flags = flags;
+        self
+    }
+    pub fn super_class(&mut self, name: &str) -> &mut Self {
+        self.super_class = self.pool.class(name);
+        self.super_name = name.into();
+        self
+    }
+    /// Sets the super types of other classes, used when computing stack map frames.
+    pub fn hierarchy(&mut self, hierarchy: impl ClassHierarchy + 'static) -> &mut Self {
+        self.hierarchy = Some(Hierarchy(Box::new(hierarchy)));
+        self
+    }
+    pub fn interface(&mut self, name: &str) -> &mut Self {
+        let interface = self.pool.class(name);
+        self.interfaces.push(interface);
+        self.interface_names.push(name.into());
+        self
+    }
+    /// Adds a `SourceFile` attribute, e.g. `Main.java`.
+    pub fn source_file(&mut self, name: &str) -> &mut Self {
+        let name_index = self.pool.utf8("SourceFile");
+        let sourcefile_index = self.pool.utf8(name);
+        self.attributes.push((name_index, Attribute::SourceFile { sourcefile_index }));
+        self
+    }
+    pub fn field(&mut self, flags: AccessFlags, name: &str, descriptor: &str) -> &mut Self {
+        let name_index = self.pool.utf8(name);
+        let descriptor_index = self.pool.utf8(descriptor);
+        self.fields.push(Field {
+            flags,
+            name_index,
+            descriptor_index,
+            attributes: Box::new([]),
+        });
+        self
+    }
+    /// Starts building a method. It is added to the class once [`MethodBuilder::finish`] is called.
+    pub fn method(&mut self, flags: AccessFlags, name: &str, descriptor: &str) -> MethodBuilder<'_> {
+        let name_index = self.pool.utf8(name);
+        let descriptor_index = self.pool.utf8(descriptor);
+        MethodBuilder {
+            class: self,
+            name: name.into(),
+            flags,
+            name_index,
+            descriptor_index,
+            descriptor: descriptor.into(),
+            code: None,
+            exceptions: Vec::new(),
+        }
+    }
+    pub fn pool(&mut self) -> &mut ConstantPoolBuilder {
+        &mut self.pool
+    }
+    pub fn build(self) -> Result<JavaClassFile, BuildError> {
+        if self.pool.overflowed {
+            return Err(BuildError::ConstantPoolOverflow);
+        }
+        let limit = u16::MAX as usize;
+        if self.fields.len() > limit || self.methods.len() > limit || self.interfaces.len() > limit {
+            return Err(BuildError::TooManyMembers);
+        }
+        Ok(JavaClassFile {
+            major_version: self.version.0,
+            minor_version: self.version.1,
+            const_items: self.pool.items.into(),
+            this_class: self.this_class,
+            super_class: self.super_class,
+            fields: self.fields.into(),
+            methods: self.methods.into(),
+            interfaces: self.interfaces.into(),
+            attributes: self.attributes.into(),
+            flags: self.flags,
+        })
+    }
+}
+/// Method being built, created by [`ClassBuilder::method`].
+#[derive(Debug)]
+#[must_use = "the method is only added to the class by `finish`"]
+pub struct MethodBuilder<'a> {
+    class: &'a mut ClassBuilder,
+    name: IString,
+    flags: AccessFlags,
+    name_index: u16,
+    descriptor_index: u16,
+    descriptor: IString,
+    code: Option<Code>,
+    exceptions: Vec<u16>,
+}
+impl<'a> MethodBuilder<'a> {
+    /// Adds `class` to the `throws` clause of this method.
+    pub fn throws(&mut self, class: &str) -> &mut Self {
+        let exception = self.class.pool.class(class);
+        self.exceptions.push(exception);
+        self
+    }
+    pub fn pool(&mut self) -> &mut ConstantPoolBuilder {
+        &mut self.class.pool
+    }
+    /// Starts building the bytecode of this method, replacing any previously built code.
+    pub fn code(&mut self) -> CodeBuilder<'_> {
+        CodeBuilder {
+            pool: &mut self.class.pool,
+            target: &mut self.code,
+            this_class: &self.class.name,
+            super_name: &self.class.super_name,
+            interfaces: &self.class.interface_names,
+            hierarchy: self.class.hierarchy.as_ref(),
+            major_version: self.class.version.0,
+            name: &self.name,
+            descriptor: &self.descriptor,
+            is_static: self.flags.is_static(),
+            ops: Vec::new(),
+            opcodes: Vec::new(),
+            length: 0,
+            id: NEXT_CODE_BUILDER_ID.fetch_add(1, Ordering::Relaxed),
+            labels: Vec::new(),
+            fixups: Vec::new(),
+            exceptions: Vec::new(),
+            error: None,
+        }
+    }
+    /// Adds this method to its class.
+    pub fn finish(self) {
+        let pool = &mut self.class.pool;
+        let mut attributes = Vec::new();
+        if let Some(code) = self.code {
+            attributes.push((pool.utf8("Code"), Attribute::Code(code)));
+        }
+        if !self.exceptions.is_empty() {
+            let exceptions = self.exceptions.into();
+            attributes.push((pool.utf8("Exceptions"), Attribute::Exceptions { exceptions }));
+        }
+        self.class.methods.push(Method {
+            access_flags: self.flags,
+            name_index: self.name_index,
+            descriptor_index: self.descriptor_index,
+            attributes: attributes.into(),
+        });
+    }
+}
+/// Position in the bytecode, used as a branch target before it is known. Created by [`CodeBuilder::new_label`].
+///
+/// A label can only be used with the builder which created it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Label {
+    /// Id of the [`CodeBuilder`] which created this label.
+    builder: usize,
+    index: usize,
+}
+/// Id of the next [`CodeBuilder`], used to tell apart the labels of different builders.
+static NEXT_CODE_BUILDER_ID: AtomicUsize = AtomicUsize::new(0);
+#[derive(Debug)]
+enum Fixup {
+    Branch(fn(i16) -> OpCode, Label),
+    Jump(fn(i32) -> OpCode, Label),
+    /// Default target, followed by the target of each case.
+    Switch(Box<[Label]>),
+}
+/// Bytecode being built, created by [`MethodBuilder::code`].
+///
+/// Instructions are encoded in their shortest form, and `goto` and `jsr` become `goto_w` and `jsr_w` if their target
+/// is too far. Branches refer to [`Label`]s, which are resolved by [`CodeBuilder::finish`]. `max_stack`, `max_locals` and the stack map frames are computed from the instructions.
+#[derive(Debug)]
+#[must_use = "the code is only added to the method by `finish`"]
+pub struct CodeBuilder<'a> {
+    pool: &'a mut ConstantPoolBuilder,
+    target: &'a mut Option<Code>,
+    this_class: &'a str,
+    super_name: &'a str,
+    interfaces: &'a [IString],
+    hierarchy: Option<&'a Hierarchy>,
+    major_version: u16,
+    name: &'a str,
+    descriptor: &'a str,
+    is_static: bool,
+    ops: Vec<(OpCode, u16)>,
+    opcodes: Vec<u8>,
+    length: u32,
+    /// Id stored in the labels of this builder.
+    id: usize,
+    /// Index in `ops` of the instruction each label is bound to, or `ops.len()` for the end of the code.
+    labels: Vec<Option<usize>>,
+    /// Instructions whose targets are set by `finish`, by their index in `ops`.
+    fixups: Vec<(usize, Fixup)>,
+    /// Start, end, handler and catch type of each exception handler.
+    exceptions: Vec<(Label, Label, Label, u16)>,
+    // First error, reported by `finish` so instructions can be chained.
+    error: Option<BuildError>,
+}
+impl<'a> CodeBuilder<'a> {
+    pub fn pool(&mut self) -> &mut ConstantPoolBuilder {
+        self.pool
+    }
+    pub fn new_label(&mut self) -> Label {
+        self.labels.push(None);
+        Label {
+            builder: self.id,
+            index: self.labels.len() - 1,
+        }
+    }
+    /// Binds `label` to the offset of the next instruction.
+    pub fn bind(&mut self, label: Label) -> &mut Self {
+        let index = self.ops.len();
+        match self.labels.get_mut(label.index).filter(|_| label.builder == self.id) {
+            None => self.fail(BuildError::ForeignLabel),
+            Some(Some(_)) => self.fail(BuildError::DuplicateLabel),
+            Some(bound) => *bound = Some(index),
+        }
+        self
+    }
+    /// Offset the next instruction will have, unless `finish` has to widen a `goto` or `jsr` before it.
+    pub fn offset(&self) -> u32 {
+        self.length
+    }
+    fn fail(&mut self, error: BuildError) {
+        self.error.get_or_insert(error);
+    }
+    /// Appends an instruction. Branch offsets of `op` are kept as they are, see [`CodeBuilder::branch`] for
+    /// branches to labels.
+    pub fn op(&mut self, op: OpCode) -> &mut Self {
+        let Ok(offset) = u16::try_from(self.length) else {
+            self.fail(BuildError::InvalidCodeLength);
+            return self;
+        };
+        let wide_constant = matches!(op, OpCode::LoadConst(index) if self.pool.is_wide(index));
+        let Some(opcode) = op.default_opcode(wide_constant) else {
+            self.fail(BuildError::Unencodable(offset));
+            return self;
+        };
+        self.length = self.length.saturating_add(op.encoded_size(opcode, offset));
+        self.ops.push((op, offset));
+        self.opcodes.push(opcode);
+        self
+    }
+    /// Appends a conditional branch to `target`, e.g. `branch(OpCode::IfNull, end)`.
+    ///
+    /// # Panics
+    /// If `op` does not create a branch instruction.
+    pub fn branch(&mut self, op: fn(i16) -> OpCode, target: Label) -> &mut Self {
+        assert!(op(0).is_branch(), "{:?} is not a branch", op(0));
+        self.fixups.push((self.ops.len(), Fixup::Branch(op, target)));
+        self.op(op(0))
+    }
+    pub fn goto(&mut self, target: Label) -> &mut Self {
+        self.fixups.push((self.ops.len(), Fixup::Jump(OpCode::GoTo, target)));
+        self.op(OpCode::GoTo(0))
+    }
+    pub fn jsr(&mut self, target: Label) -> &mut Self {
+        self.fixups.push((self.ops.len(), Fixup::Jump(OpCode::Jsr, target)));
+        self.op(OpCode::Jsr(0))
+    }
+    /// Appends a `lookupswitch`. Cases don't need to be sorted by key, but each key must be unique.
+    pub fn lookup_switch(&mut self, default: Label, cases: &[(i32, Label)]) -> &mut Self {
+        let mut cases = cases.to_vec();
+        cases.sort_by_key(|(key, _)| *key);
+        if cases.windows(2).any(|pair| pair[0].0 == pair[1].0) {
+            self.fail(BuildError::InvalidSwitch);
+            return self;
+        }
+        let labels = std::iter::once(default).chain(cases.iter().map(|(_, label)| *label));
+        self.fixups.push((self.ops.len(), Fixup::Switch(labels.collect())));
+        self.op(OpCode::LookupSwitch(Box::new(LookupSwitch {
+            default_offset: 0,
+            pairs: cases.iter().map(|(key, _)| (*key, 0)).collect(),
+        })))
+    }
+    /// Appends a `tableswitch` jumping to `cases[key - low]`. `cases` must not be empty.
+    pub fn table_switch(&mut self, default: Label, low: i32, cases: &[Label]) -> &mut Self {
+        let last = cases.len().checked_sub(1).and_then(|last| i32::try_from(last).ok());
+        let high = last.and_then(|last| low.checked_add(last));
+        let Some(high) = high else {
+            self.fail(BuildError::InvalidSwitch);
+            return self;
+        };
+        let labels = std::iter::once(default).chain(cases.iter().copied());
+        self.fixups.push((self.ops.len(), Fixup::Switch(labels.collect())));
+        self.op(OpCode::TableSwitch(Box::new(TableSwitch {
+            default_offset: 0,
+            low,
+            high,
+            offsets: vec![0; cases.len()].into(),
+        })))
+    }
+    /// Adds an exception handler at `handler`, for exceptions of class `catch_type` thrown between `start`
+    /// (inclusive) and `end` (exclusive). `None` catches everything, as used by `finally`.
+    pub fn try_catch(
+        &mut self,
+        start: Label,
+        end: Label,
+        handler: Label,
+        catch_type: Option<&str>,
+    ) -> &mut Self {
+        let catch_type = catch_type.map_or(0, |class| self.pool.class(class));
+        self.exceptions.push((start, end, handler, catch_type));
+        self
+    }
+    /// Pushes `value` using the shortest of `iconst_<n>`, `bipush`, `sipush` and `ldc`.
+    pub fn load_int(&mut self, value: i32) -> &mut Self {
+        match value {
+            -1..=5 => self.op(OpCode::IConst(value)),
+            _ if i8::try_from(value).is_ok() => self.op(OpCode::BIPush(value as i8)),
+            _ if i16::try_from(value).is_ok() => self.op(OpCode::SIPush(value as i16)),
+            _ => {
+                let index = self.pool.integer(value);
+                self.op(OpCode::LoadConst(index))
+            }
+        }
+    }
+    pub fn load_long(&mut self, value: i64) -> &mut Self {
+        match value {
+            0 | 1 => self.op(OpCode::LConst(value)),
+            _ => {
+                let index = self.pool.long(value);
+                self.op(OpCode::LoadConst(index))
+            }
+        }
+    }
+    pub fn load_float(&mut self, value: f32) -> &mut Self {
+        if OpCode::FConst(value).default_opcode(false).is_some() {
+            return self.op(OpCode::FConst(value));
+        }
+        let index = self.pool.float(value);
+        self.op(OpCode::LoadConst(index))
+    }
+    pub fn load_double(&mut self, value: f64) -> &mut Self {
+        if OpCode::DConst(value).default_opcode(false).is_some() {
+            return self.op(OpCode::DConst(value));
+        }
+        let index = self.pool.double(value);
+        self.op(OpCode::LoadConst(index))
+    }
+    pub fn load_string(&mut self, value: &str) -> &mut Self {
+        let index = self.pool.string(value);
+        self.op(OpCode::LoadConst(index))
+    }
+    pub fn get_static(&mut self, class: &str, name: &str, descriptor: &str) -> &mut Self {
+        let index = self.pool.field_ref(class, name, descriptor);
+        self.op(OpCode::GetStatic(index))
+    }
+    pub fn put_static(&mut self, class: &str, name: &str, descriptor: &str) -> &mut Self {
+        let index = self.pool.field_ref(class, name, descriptor);
+        self.op(OpCode::PutStatic(index))
+    }
+    pub fn get_field(&mut self, class: &str, name: &str, descriptor: &str) -> &mut Self {
+        let index = self.pool.field_ref(class, name, descriptor);
+        self.op(OpCode::GetField(index))
+    }
+    pub fn put_field(&mut self, class: &str, name: &str, descriptor: &str) -> &mut Self {
+        let index = self.pool.field_ref(class, name, descriptor);
+        self.op(OpCode::PutField(index))
+    }
+    pub fn invoke_virtual(&mut self, class: &str, name: &str, descriptor: &str) -> &mut Self {
+        let index = self.pool.method_ref(class, name, descriptor);
+        self.op(OpCode::InvokeVirtual(index))
+    }
+    pub fn invoke_special(&mut self, class: &str, name: &str, descriptor: &str) -> &mut Self {
+        let index = self.pool.method_ref(class, name, descriptor);
+        self.op(OpCode::InvokeSpecial(index))
+    }
+    pub fn invoke_static(&mut self, class: &str, name: &str, descriptor: &str) -> &mut Self {
+        let index = self.pool.method_ref(class, name, descriptor);
+        self.op(OpCode::InvokeStatic(index))
+    }
+    /// Appends an `invokeinterface`, with the argument count taken from `descriptor`.
+    pub fn invoke_interface(&mut self, class: &str, name: &str, descriptor: &str) -> &mut Self {
+        let Ok(parsed) = MethodDescriptor::parse(descriptor) else {
+            self.fail(BuildError::InvalidDescriptor(descriptor.into()));
+            return self;
+        };
+        let count = (parsed.argument_slots() + 1).min(255) as u8;
+        let index = self.pool.interface_method_ref(class, name, descriptor);
+        self.op(OpCode::InvokeInterface(index, count))
+    }
+    /// Appends a `new`, creating an uninitialized object of `class`.
+    pub fn new_object(&mut self, class: &str) -> &mut Self {
+        let index = self.pool.class(class);
+        self.op(OpCode::New(index))
+    }
+    pub fn check_cast(&mut self, class: &str) -> &mut Self {
+        let index = self.pool.class(class);
+        self.op(OpCode::CheckCast(index))
+    }
+    pub fn instance_of(&mut self, class: &str) -> &mut Self {
+        let index = self.pool.class(class);
+        self.op(OpCode::InstanceOf(index))
+    }
+    fn label_offset(&self, label: Label) -> Result<u16, BuildError> {
+        match self.labels.get(label.index).filter(|_| label.builder == self.id) {
+            None => Err(BuildError::ForeignLabel),
+            Some(None) => Err(BuildError::UnboundLabel),
+            Some(Some(index)) => Ok(self.ops.get(*index).map_or(self.length as u16, |(_, offset)| *offset)),
+        }
+    }
+    /// Recomputes the offset of each instruction and the length of the code, after some instruction changed size.
+    fn layout(&mut self) -> Result<(), BuildError> {
+        let mut length: u32 = 0;
+        for ((op, offset), opcode) in self.ops.iter_mut().zip(&self.opcodes) {
+            *offset = u16::try_from(length).map_err(|_| BuildError::InvalidCodeLength)?;
+            length = length.saturating_add(op.encoded_size(*opcode, *offset));
+        }
+        if length > u16::MAX as u32 {
+            return Err(BuildError::InvalidCodeLength);
+        }
+        self.length = length;
+        Ok(())
+    }
+    /// Widens each `goto` and `jsr` whose target is too far to `goto_w` or `jsr_w`. Widening moves the code after
+    /// the jump, which can push other jumps out of range, so this repeats until all of them fit.
+    fn widen_jumps(&mut self) -> Result<(), BuildError> {
+        loop {
+            let mut widened = false;
+            for (index, fixup) in self.fixups.iter() {
+                let Fixup::Jump(op, label) = fixup else {
+                    continue;
+                };
+                let relative = self.label_offset(*label)? as i32 - self.ops[*index].1 as i32;
+                if i16::try_from(relative).is_ok() {
+                    continue;
+                }
+                // The opcode of a far jump is its wide form.
+                let wide = op(relative).default_opcode(false);
+                if let Some(wide) = wide.filter(|wide| self.opcodes[*index] != *wide) {
+                    self.opcodes[*index] = wide;
+                    widened = true;
+                }
+            }
+            if !widened {
+                return Ok(());
+            }
+            self.layout()?;
+        }
+    }
+    /// Resolves labels, computes `max_stack`, `max_locals` and stack map frames, and sets the result as the code of the method.
+    pub fn finish(mut self) -> Result<(), BuildError> {
+        if let Some(error) = self.error.take() {
+            return Err(error);
+        }
+        if self.ops.is_empty() || self.length > u16::MAX as u32 {
+            return Err(BuildError::InvalidCodeLength);
+        }
+        self.widen_jumps()?;
+        for (index, fixup) in std::mem::take(&mut self.fixups) {
+            let offset = self.ops[index].1;
+            let relative = |label| -> Result<i32, BuildError> {
+                Ok(self.label_offset(label)? as i32 - offset as i32)
+            };
+            let short = |label| -> Result<i16, BuildError> {
+                i16::try_from(relative(label)?).map_err(|_| BuildError::BranchTooFar(offset))
+            };
+            let op = match fixup {
+                Fixup::Branch(op, label) => op(short(label)?),
+                Fixup::Jump(op, label) => op(relative(label)?),
+                Fixup::Switch(labels) => {
+                    let mut op = self.ops[index].0.clone();
+                    let targets = labels.iter().map(|label| relative(*label));
+                    let targets = targets.collect::<Result<Vec<_>, _>>()?;
+                    match &mut op {
+                        OpCode::LookupSwitch(switch) => {
+                            switch.default_offset = targets[0];
+                            for (pair, target) in switch.pairs.iter_mut().zip(&targets[1..]) {
+                                pair.1 = *target;
+                            }
+                        }
+                        OpCode::TableSwitch(switch) => {
+                            switch.default_offset = targets[0];
+                            switch.offsets.copy_from_slice(&targets[1..]);
+                        }
+                        _ => unreachable!("switch fixup on {op:?}"),
+                    }
+                    op
+                }
+            };
+            self.ops[index].0 = op;
+        }
+        let mut exceptions = Vec::with_capacity(self.exceptions.len());
+        for (start, end, handler, catch_type) in self.exceptions.iter() {
+            exceptions.push(CodeException {
+                start_pc: self.label_offset(*start)?,
+                end_pc: self.label_offset(*end)?,
+                handler_pc: self.label_offset(*handler)?,
+                catch_type: *catch_type,
+            });
+        }
+        let Ok(descriptor) = MethodDescriptor::parse(self.descriptor) else {
+            return Err(BuildError::InvalidDescriptor(self.descriptor.into()));
+        };
+        let arguments = descriptor.argument_slots() + !self.is_static as u16;
+        let max_locals = self.ops.iter().map(|(op, _)| locals_used(op)).fold(arguments as u32, u32::max);
+        let max_stack = max_stack(&self.ops, &exceptions, self.pool.pool())?;
+        let mut code = Code {
+            max_stack,
+            max_locals: max_locals.min(u16::MAX as u32) as u16,
+            ops: self.ops.into(),
+            opcodes: self.opcodes.into(),
+            exceptions: exceptions.into(),
+            attributes: Box::default(),
+        };
+        if self.major_version >= 50 {
+            let no_hierarchy = |_: &str| None;
+            let context = FrameContext {
+                this_class: self.this_class,
+                super_class: self.super_name,
+                interfaces: self.interfaces,
+                method_name: self.name,
+                descriptor: &descriptor,
+                is_static: self.is_static,
+                hierarchy: self.hierarchy.map_or(&no_hierarchy, |hierarchy| &*hierarchy.0),
+            };
+            let frames = compute_frames(&mut code, self.pool, &context)?;
+            if !frames.is_empty() {
+                code.attributes = Box::new([(self.pool.utf8("StackMapTable"), Attribute::StackMapTable { frames })]);
+            }
+        }
+        *self.target = Some(code);
+        Ok(())
+    }
+}
+/// Number of local variable slots needed by `op`.
+fn locals_used(op: &OpCode) -> u32 {
+    match op {
+        OpCode::ILoad(index)
+        | OpCode::FLoad(index)
+        | OpCode::ALoad(index)
+        | OpCode::IStore(index)
+        | OpCode::FStore(index)
+        | OpCode::AStore(index)
+        | OpCode::Ret(index)
+        | OpCode::IInc(index, _) => *index as u32 + 1,
+        OpCode::LLoad(index) | OpCode::DLoad(index) | OpCode::LStore(index) | OpCode::DStore(index) => {
+            *index as u32 + 2
+        }
+        _ => 0,
+    }
+}
+/// Largest stack depth reached by `ops`, found by following every path through the code.
+fn max_stack(
+    ops: &[(OpCode, u16)],
+    exceptions: &[CodeException],
+    pool: ConstantPool,
+) -> Result<u16, BuildError> {
+    let index_of = |offset: u16| {
+        ops.binary_search_by_key(&offset, |(_, op_offset)| *op_offset)
+            .map_err(|_| BuildError::InvalidBranchTarget(offset))
+    };
+    let mut depths: Vec<Option<u16>> = vec![None; ops.len()];
+    let mut pending = vec![(0, 0)];
+    for exception in exceptions {
+        // Handlers start with the exception as the only value on the stack.
+        pending.push((index_of(exception.handler_pc)?, 1));
+    }
+    let mut max = 0;
+    while let Some((index, depth)) = pending.pop() {
+        let (op, offset) = &ops[index];
+        match depths[index] {
+            Some(known) if known == depth => continue,
+            Some(_) => return Err(BuildError::StackMismatch(*offset)),
+            None => depths[index] = Some(depth),
+        }
+        let effect = pool
+            .stack_effect(op)
+            .ok_or(BuildError::UnknownStackEffect(*offset))?;
+        let Some(after) = depth.checked_sub(effect.pops) else {
+            return Err(BuildError::StackUnderflow(*offset));
+        };
+        let after = after + effect.pushes;
+        max = max.max(depth).max(after);
+        for target in op.branch_targets(*offset) {
+            pending.push((index_of(target)?, after));
+        }
+        if !op.is_terminator() {
+            if index + 1 == ops.len() {
+                return Err(BuildError::FallsOffEnd);
+            }
+            // The return address pushed by `jsr` is consumed by the subroutine.
+            let next = if matches!(op, OpCode::Jsr(_)) { depth } else { after };
+            pending.push((index + 1, next));
+        }
+    }
+    Ok(max)
+}
+#[test]
+fn build_class() {
+    use crate::stack_map::{StackMapFrame, VerificationType};
+    let mut class = ClassBuilder::new("Built");
+    class.source_file("Built.java");
+    let mut init = class.method(AccessFlags::PUBLIC, "<init>", "()V");
+    let mut code = init.code();
+    code.op(OpCode::ALoad(0))
+        .invoke_special("java/lang/Object", "<init>", "()V")
+        .op(OpCode::Return);
+    code.finish().unwrap();
+    init.finish();
+    // static int sum(int[] values), with a loop.
+    let mut sum = class.method(AccessFlags::STATIC, "sum", "([I)I");
+    let mut code = sum.code();
+    let (check, end) = (code.new_label(), code.new_label());
+    code.load_int(0).op(OpCode::IStore(1)).load_int(0).op(OpCode::IStore(2));
+    code.bind(check)
+        .op(OpCode::ILoad(2))
+        .op(OpCode::ALoad(0))
+        .op(OpCode::ArrayLength)
+        .branch(OpCode::IfIGreterEqual, end);
+    code.op(OpCode::ILoad(1))
+        .op(OpCode::ALoad(0))
+        .op(OpCode::ILoad(2))
+        .op(OpCode::IALoad)
+        .op(OpCode::IAdd)
+        .op(OpCode::IStore(1))
+        .op(OpCode::IInc(2, 1))
+        .goto(check);
+    code.bind(end).op(OpCode::ILoad(1)).op(OpCode::IReturn);
+    code.finish().unwrap();
+    sum.finish();
+    // static void main(String[] args), printing the sum in a try block.
+    let mut main = class.method(AccessFlags::PUBLIC | AccessFlags::STATIC, "main", "([Ljava/lang/String;)V");
+    main.throws("java/lang/Exception");
+    let mut code = main.code();
+    let (start, end, handler) = (code.new_label(), code.new_label(), code.new_label());
+    code.bind(start)
+        .get_static("java/lang/System", "out", "Ljava/io/PrintStream;")
+        .load_int(2)
+        .op(OpCode::NewArray(10))
+        .op(OpCode::Dup)
+        .load_int(1)
+        .load_int(1000)
+        .op(OpCode::IAStore)
+        .invoke_static("Built", "sum", "([I)I")
+        .invoke_virtual("java/io/PrintStream", "println", "(I)V")
+        .bind(end)
+        .op(OpCode::Return);
+    code.bind(handler).op(OpCode::AStore(1)).op(OpCode::Return);
+    code.try_catch(start, end, handler, Some("java/lang/RuntimeException"));
+    code.finish().unwrap();
+    main.finish();
+    let built = class.build().unwrap();
+    let mut bytes = Vec::new();
+    built.write(&mut bytes).unwrap();
+    let class = crate::import_class_file(&mut &bytes[..]).unwrap();
+    let pool = class.constant_pool();
+    // Each constant is only stored once.
+    let utf8: Vec<_> = pool.iter().filter_map(|(index, _)| pool.utf8(index)).collect();
+    let mut unique = utf8.clone();
+    unique.sort();
+    unique.dedup();
+    assert_eq!(utf8.len(), unique.len());
+    assert_eq!(pool.class_name(class.this_class().0), Some("Built"));
+    let sum = class.methods()[1].code().unwrap();
+    assert_eq!((sum.max_stack(), sum.max_locals()), (3, 3));
+    // The `goto` jumps back to the loop condition at offset 4, and `if_icmpge` forward to the return.
+    assert!(matches!(sum.instruction_at(19), Some(OpCode::GoTo(-15))));
+    assert!(matches!(sum.instruction_at(7), Some(OpCode::IfIGreterEqual(15))));
+    assert!(matches!(
+        sum.stack_map().unwrap(),
+        [StackMapFrame::Append { offset: 4, locals }, StackMapFrame::Same { offset: 22, .. }]
+            if locals[..] == [VerificationType::Integer, VerificationType::Integer]
+    ));
+    let main = class.methods()[2].code().unwrap();
+    assert_eq!((main.max_stack(), main.max_locals()), (5, 2));
+    let exception = &main.exceptions()[0];
+    assert_eq!(
+        (exception.start_pc(), exception.end_pc(), exception.handler_pc()),
+        (0, 18, 19)
+    );
+    assert_eq!(pool.class_name(exception.catch_type().0), Some("java/lang/RuntimeException"));
+    assert!(matches!(
+        main.stack_map().unwrap(),
+        [StackMapFrame::SameLocals1StackItem { offset: 19, stack: VerificationType::Object(class), .. }]
+            if pool.class_name(class.0) == Some("java/lang/RuntimeException")
+    ));
+}
+#[test]
+fn far_jumps() {
+    let mut class = ClassBuilder::new("Far");
+    let mut method = class.method(AccessFlags::STATIC, "far", "(I)V");
+    let mut code = method.code();
+    let (back, skip, end) = (code.new_label(), code.new_label(), code.new_label());
+    code.op(OpCode::ILoad(0)).branch(OpCode::IfZero, skip).goto(end);
+    code.bind(back).op(OpCode::Return);
+    code.bind(skip);
+    for _ in 0..40000 {
+        code.op(OpCode::Nop);
+    }
+    code.goto(back).bind(end).op(OpCode::Return);
+    code.finish().unwrap();
+    method.finish();
+    let class = class.build().unwrap();
+    let code = class.methods()[0].code().unwrap();
+    let jumps: Vec<_> = code
+        .raw_instructions()
+        .filter(|instruction| matches!(instruction.op(), OpCode::GoTo(_)))
+        .map(|instruction| {
+            let target = instruction.op().branch_targets(instruction.offset())[0];
+            (instruction.offset(), instruction.opcode(), target)
+        })
+        .collect();
+    // Widening the forward `goto` moves `back`, and the code after it.
+    assert_eq!(jumps, [(4, 0xc8, 40015), (40010, 0xc8, 9)]);
+    let mut written = Vec::new();
+    class.write(&mut written).unwrap();
+    let loaded = crate::import_class_file(&written[..]).unwrap();
+    let far = loaded.methods()[0].code().unwrap().instruction_at(40010);
+    assert!(matches!(far, Some(OpCode::GoTo(-40001))));
+}
+#[test]
+fn unreachable_code() {
+    use crate::stack_map::{StackMapFrame, VerificationType};
+    let mut class = ClassBuilder::new("Dead");
+    let mut method = class.method(AccessFlags::STATIC, "dead", "(I)I");
+    let mut code = method.code();
+    let (start, end, handler) = (code.new_label(), code.new_label(), code.new_label());
+    code.try_catch(start, end, handler, None);
+    code.bind(start).op(OpCode::ILoad(0)).op(OpCode::IReturn);
+    // Never reached, and would underflow the stack if it was.
+    code.op(OpCode::Nop).op(OpCode::IAdd).op(OpCode::IReturn);
+    code.bind(end).bind(handler).op(OpCode::Pop).load_int(1).op(OpCode::IReturn);
+    code.finish().unwrap();
+    method.finish();
+    let class = class.build().unwrap();
+    let code = class.methods()[0].code().unwrap();
+    let ops: Vec<_> = code.raw_instructions().map(|instruction| (instruction.offset(), instruction.opcode())).collect();
+    assert_eq!(ops, [(0, 0x1a), (1, 0xac), (2, 0x00), (3, 0x00), (4, 0xbf), (5, 0x57), (6, 0x04), (7, 0xac)]);
+    // The dead code is no longer covered by the handler.
+    let exception = &code.exceptions()[0];
+    assert_eq!((code.exceptions().len(), exception.start_pc(), exception.end_pc()), (1, 0, 2));
+    assert!(matches!(
+        code.stack_map().unwrap(),
+        [StackMapFrame::SameLocals1StackItem { offset: 2, stack: VerificationType::Object(throwable), .. }, _]
+            if class.constant_pool().class_name(throwable.0) == Some("java/lang/Throwable")
+    ));
+}
+#[test]
+fn build_errors() {
+    let mut class = ClassBuilder::new("Broken");
+    let mut method = class.method(AccessFlags::STATIC, "broken", "(I)V");
+    let mut code = method.code();
+    let label = code.new_label();
+    code.goto(label);
+    assert_eq!(code.finish(), Err(BuildError::UnboundLabel));
+    let mut code = method.code();
+    code.op(OpCode::IAdd).op(OpCode::Return);
+    assert_eq!(code.finish(), Err(BuildError::StackUnderflow(0)));
+    let mut code = method.code();
+    code.op(OpCode::IConst(7)).op(OpCode::Return);
+    assert_eq!(code.finish(), Err(BuildError::Unencodable(0)));
+    let mut code = method.code();
+    let (other, join) = (code.new_label(), code.new_label());
+    code.op(OpCode::ILoad(0)).branch(OpCode::IfZero, other);
+    code.load_int(1).goto(join);
+    code.bind(other);
+    code.bind(join).op(OpCode::Return);
+    assert_eq!(code.finish(), Err(BuildError::StackMismatch(8)));
+    let mut code = method.code();
+    code.op(OpCode::Nop);
+    assert_eq!(code.finish(), Err(BuildError::FallsOffEnd));
+    let mut code = method.code();
+    let foreign = code.new_label();
+    let mut code = method.code();
+    code.bind(foreign);
+    assert_eq!(code.finish(), Err(BuildError::ForeignLabel));
+    let mut code = method.code();
+    code.goto(foreign);
+    assert_eq!(code.finish(), Err(BuildError::ForeignLabel));
+    let mut code = method.code();
+    let end = code.new_label();
+    code.op(OpCode::ILoad(0)).lookup_switch(end, &[(1, end), (1, end)]);
+    assert_eq!(code.finish(), Err(BuildError::InvalidSwitch));
+    let mut code = method.code();
+    code.op(OpCode::ILoad(0)).table_switch(end, 0, &[]);
+    assert_eq!(code.finish(), Err(BuildError::InvalidSwitch));
+    let mut code = method.code();
+    let end = code.new_label();
+    let cases: Vec<_> = (0..10000).map(|key| (key, end)).collect();
+    code.op(OpCode::ILoad(0)).lookup_switch(end, &cases);
+    code.bind(end).op(OpCode::Return);
+    assert_eq!(code.finish(), Err(BuildError::InvalidCodeLength));
+    for array in ["[L", "[L\u{e9}", "[L;"] {
+        let mut code = method.code();
+        code.op(OpCode::AConstNull).check_cast(array).load_int(0).op(OpCode::AALoad);
+        code.op(OpCode::Pop).op(OpCode::Return);
+        assert_eq!(code.finish(), Err(BuildError::UnknownStackEffect(5)));
+    }
+    method.finish();
+    for _ in 0..65536 {
+        class.field(AccessFlags::STATIC, "field", "I");
+    }
+    assert_eq!(class.build().unwrap_err(), BuildError::TooManyMembers);
+}
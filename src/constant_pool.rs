@@ -0,0 +1,397 @@
+use crate::descriptor::{FieldType, MethodDescriptor};
+use crate::import::ConstantItem;
+use crate::opcodes::{OpCode, StackEffect};
+/// Read-only view into the constant pool of a class, created by [`crate::JavaClassFile::constant_pool`].
+///
+/// Indices are the same 1-based indices used by the class file itself. The slot after a `Long` or `Double`
+/// constant is unusable and resolves to `None`, just like index 0.
+#[derive(Debug, Clone, Copy)]
+pub struct ConstantPool<'a> {
+    items: &'a [ConstantItem],
+}
+/// A fully resolved constant pool entry.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Constant<'a> {
+    Utf8(&'a str),
+    /// Modified UTF-8 bytes of a `Utf8` constant which is not valid Unicode. Only present if the class was imported
+    /// with [`crate::ImportOptions::keep_invalid_utf8`].
+    RawUtf8(&'a [u8]),
+    Integer(i32),
+    Float(f32),
+    Long(i64),
+    Double(f64),
+    /// Internal name of a class, e.g. `java/lang/Object`, or a descriptor of an array class.
+    Class(&'a str),
+    String(&'a str),
+    FieldRef(FieldRef<'a>),
+    MethodRef(MethodRef<'a>),
+    InterfaceMethodRef(MethodRef<'a>),
+    NameAndType(NameAndType<'a>),
+    MethodHandle(MethodHandle<'a>),
+    /// Method descriptor of a `MethodType` constant.
+    MethodType(&'a str),
+    InvokeDynamic(InvokeDynamic<'a>),
+    /// Dynamically-computed constant. Shares its layout with `InvokeDynamic`, but `descriptor` is a field descriptor.
+    Dynamic(InvokeDynamic<'a>),
+    Module(&'a str),
+    Package(&'a str),
+}
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FieldRef<'a> {
+    pub class: &'a str,
+    pub name: &'a str,
+    pub descriptor: &'a str,
+}
+/// Reference to a method of a class or interface.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MethodRef<'a> {
+    pub class: &'a str,
+    pub name: &'a str,
+    pub descriptor: &'a str,
+}
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NameAndType<'a> {
+    pub name: &'a str,
+    pub descriptor: &'a str,
+}
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MethodHandle<'a> {
+    pub kind: ReferenceKind,
+    pub reference: MemberRef<'a>,
+}
+/// Member a [`MethodHandle`] points to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MemberRef<'a> {
+    Field(FieldRef<'a>),
+    Method(MethodRef<'a>),
+    InterfaceMethod(MethodRef<'a>),
+}
+/// `reference_kind` of a `MethodHandle` constant, describing the bytecode behaviour of the handle.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReferenceKind {
+    GetField,
+    GetStatic,
+    PutField,
+    PutStatic,
+    InvokeVirtual,
+    InvokeStatic,
+    InvokeSpecial,
+    NewInvokeSpecial,
+    InvokeInterface,
+}
+impl ReferenceKind {
+    pub fn from_u8(kind: u8) -> Option<Self> {
+        Some(match kind {
+            1 => Self::GetField,
+            2 => Self::GetStatic,
+            3 => Self::PutField,
+            4 => Self::PutStatic,
+            5 => Self::InvokeVirtual,
+            6 => Self::InvokeStatic,
+            7 => Self::InvokeSpecial,
+            8 => Self::NewInvokeSpecial,
+            9 => Self::InvokeInterface,
+            _ => return None,
+        })
+    }
+    pub fn as_u8(&self) -> u8 {
+        match self {
+            Self::GetField => 1,
+            Self::GetStatic => 2,
+            Self::PutField => 3,
+            Self::PutStatic => 4,
+            Self::InvokeVirtual => 5,
+            Self::InvokeStatic => 6,
+            Self::InvokeSpecial => 7,
+            Self::NewInvokeSpecial => 8,
+            Self::InvokeInterface => 9,
+        }
+    }
+}
+/// Call site of an `invokedynamic` instruction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InvokeDynamic<'a> {
+    /// Index into the `BootstrapMethods` attribute of the class.
+    pub bootstrap_method_attr_index: u16,
+    pub name: &'a str,
+    pub descriptor: &'a str,
+}
+impl<'a> ConstantPool<'a> {
+    pub(crate) fn new(items: &'a [ConstantItem]) -> Self {
+        Self { items }
+    }
+    pub(crate) fn item(&self, index: u16) -> Option<&'a ConstantItem> {
+        if index == 0 {
+            return None;
+        }
+        self.items.get(index as usize - 1)
+    }
+    /// Value of `constant_pool_count`: all valid indices are smaller than this.
+    pub fn count(&self) -> u16 {
+        (self.items.len() as u16).saturating_add(1)
+    }
+    /// Resolves the constant at `index`. Returns `None` for unusable slots, and for constants referencing
+    /// entries of the wrong kind.
+    pub fn get(&self, index: u16) -> Option<Constant<'a>> {
+        Some(match self.item(index)? {
+            ConstantItem::Utf8(string) => Constant::Utf8(string),
+            ConstantItem::RawUtf8(bytes) => Constant::RawUtf8(bytes),
+            ConstantItem::Intiger(int) => Constant::Integer(*int),
+            ConstantItem::Float(float) => Constant::Float(*float),
+            ConstantItem::Long(long) => Constant::Long(*long),
+            ConstantItem::Double(double) => Constant::Double(*double),
+            ConstantItem::Class { name_index } => Constant::Class(self.utf8(*name_index)?),
+            ConstantItem::ConstString { string_index } => {
+                Constant::String(self.utf8(*string_index)?)
+            }
+            ConstantItem::FieldRef { .. } => Constant::FieldRef(self.field_ref(index)?),
+            ConstantItem::MethodRef { .. } => Constant::MethodRef(self.method_ref(index)?),
+            ConstantItem::InterfaceMethodRef { .. } => {
+                Constant::InterfaceMethodRef(self.interface_method_ref(index)?)
+            }
+            ConstantItem::NameAndType { .. } => Constant::NameAndType(self.name_and_type(index)?),
+            ConstantItem::MethodHandle { .. } => Constant::MethodHandle(self.method_handle(index)?),
+            ConstantItem::MethodType { descriptor_index } => {
+                Constant::MethodType(self.utf8(*descriptor_index)?)
+            }
+            ConstantItem::InvokeDynamic { .. } => {
+                Constant::InvokeDynamic(self.invoke_dynamic(index)?)
+            }
+            ConstantItem::Dynamic {
+                bootstrap_method_attr_index,
+                name_and_type_index,
+            } => {
+                let NameAndType { name, descriptor } = self.name_and_type(*name_and_type_index)?;
+                Constant::Dynamic(InvokeDynamic {
+                    bootstrap_method_attr_index: *bootstrap_method_attr_index,
+                    name,
+                    descriptor,
+                })
+            }
+            ConstantItem::Module { name_index } => Constant::Module(self.utf8(*name_index)?),
+            ConstantItem::Package { name_index } => Constant::Package(self.utf8(*name_index)?),
+            ConstantItem::Padding | ConstantItem::Unknown => return None,
+        })
+    }
+    /// Iterates over all `(index, constant)` pairs that can be resolved, skipping unusable slots.
+    pub fn iter(&self) -> impl Iterator<Item = (u16, Constant<'a>)> + '_ {
+        (1..self.count()).filter_map(|index| Some((index, self.get(index)?)))
+    }
+    pub fn utf8(&self, index: u16) -> Option<&'a str> {
+        match self.item(index)? {
+            ConstantItem::Utf8(string) => Some(string),
+            _ => None,
+        }
+    }
+    /// Name of the class referenced by the `Class` constant at `index`.
+    pub fn class_name(&self, index: u16) -> Option<&'a str> {
+        match self.item(index)? {
+            ConstantItem::Class { name_index } => self.utf8(*name_index),
+            _ => None,
+        }
+    }
+    pub fn name_and_type(&self, index: u16) -> Option<NameAndType<'a>> {
+        match self.item(index)? {
+            ConstantItem::NameAndType {
+                name_index,
+                descriptor_index,
+            } => Some(NameAndType {
+                name: self.utf8(*name_index)?,
+                descriptor: self.utf8(*descriptor_index)?,
+            }),
+            _ => None,
+        }
+    }
+    fn member(&self, class_index: u16, name_and_type_index: u16) -> Option<MethodRef<'a>> {
+        let class = self.class_name(class_index)?;
+        let NameAndType { name, descriptor } = self.name_and_type(name_and_type_index)?;
+        Some(MethodRef {
+            class,
+            name,
+            descriptor,
+        })
+    }
+    pub fn field_ref(&self, index: u16) -> Option<FieldRef<'a>> {
+        match self.item(index)? {
+            ConstantItem::FieldRef {
+                class_index,
+                name_and_type_index,
+            } => {
+                let MethodRef {
+                    class,
+                    name,
+                    descriptor,
+                } = self.member(*class_index, *name_and_type_index)?;
+                Some(FieldRef {
+                    class,
+                    name,
+                    descriptor,
+                })
+            }
+            _ => None,
+        }
+    }
+    /// Resolves a `MethodRef` constant. See [`Self::any_method_ref`] for operands of `invokestatic` and `invokespecial`,
+    /// which may also point to an `InterfaceMethodRef`.
+    pub fn method_ref(&self, index: u16) -> Option<MethodRef<'a>> {
+        match self.item(index)? {
+            ConstantItem::MethodRef {
+                class_index,
+                name_and_type_index,
+            } => self.member(*class_index, *name_and_type_index),
+            _ => None,
+        }
+    }
+    pub fn interface_method_ref(&self, index: u16) -> Option<MethodRef<'a>> {
+        match self.item(index)? {
+            ConstantItem::InterfaceMethodRef {
+                class_index,
+                name_and_type_index,
+            } => self.member(*class_index, *name_and_type_index),
+            _ => None,
+        }
+    }
+    /// Resolves either a `MethodRef` or an `InterfaceMethodRef` constant.
+    pub fn any_method_ref(&self, index: u16) -> Option<MethodRef<'a>> {
+        self.method_ref(index)
+            .or_else(|| self.interface_method_ref(index))
+    }
+    pub fn method_handle(&self, index: u16) -> Option<MethodHandle<'a>> {
+        match self.item(index)? {
+            ConstantItem::MethodHandle {
+                reference_kind,
+                reference_index,
+            } => {
+                let kind = ReferenceKind::from_u8(*reference_kind)?;
+                let reference = match self.item(*reference_index)? {
+                    ConstantItem::FieldRef { .. } => {
+                        MemberRef::Field(self.field_ref(*reference_index)?)
+                    }
+                    ConstantItem::MethodRef { .. } => {
+                        MemberRef::Method(self.method_ref(*reference_index)?)
+                    }
+                    ConstantItem::InterfaceMethodRef { .. } => {
+                        MemberRef::InterfaceMethod(self.interface_method_ref(*reference_index)?)
+                    }
+                    _ => return None,
+                };
+                Some(MethodHandle { kind, reference })
+            }
+            _ => None,
+        }
+    }
+    pub fn invoke_dynamic(&self, index: u16) -> Option<InvokeDynamic<'a>> {
+        match self.item(index)? {
+            ConstantItem::InvokeDynamic {
+                bootstrap_method_attr_index,
+                name_and_type_index,
+            } => {
+                let NameAndType { name, descriptor } = self.name_and_type(*name_and_type_index)?;
+                Some(InvokeDynamic {
+                    bootstrap_method_attr_index: *bootstrap_method_attr_index,
+                    name,
+                    descriptor,
+                })
+            }
+            _ => None,
+        }
+    }
+}
+impl ConstantPool<'_> {
+    /// Stack slots popped and pushed by `op`. Unlike [`OpCode::stack_effect`], this also handles instructions
+    /// which need the constant pool: field accesses, invokes and `ldc`. Returns `None` if their operand can't
+    /// be resolved.
+    pub fn stack_effect(&self, op: &OpCode) -> Option<StackEffect> {
+        if let Some(effect) = op.stack_effect() {
+            return Some(effect);
+        }
+        let field_slots = |index| -> Option<u16> {
+            let field = self.field_ref(index)?;
+            Some(FieldType::parse(field.descriptor).ok()?.slot_size() as u16)
+        };
+        let invoke = |descriptor: &str, this: u16| -> Option<StackEffect> {
+            let descriptor = MethodDescriptor::parse(descriptor).ok()?;
+            Some(StackEffect::new(
+                descriptor.argument_slots() + this,
+                descriptor.return_slots() as u16,
+            ))
+        };
+        match op {
+            OpCode::LoadConst(index) => match self.item(*index)? {
+                ConstantItem::Long(_) | ConstantItem::Double(_) => Some(StackEffect::new(0, 2)),
+                ConstantItem::Dynamic { .. } => {
+                    let Some(Constant::Dynamic(dynamic)) = self.get(*index) else {
+                        return None;
+                    };
+                    let slots = FieldType::parse(dynamic.descriptor).ok()?.slot_size();
+                    Some(StackEffect::new(0, slots as u16))
+                }
+                ConstantItem::Padding | ConstantItem::Unknown => None,
+                _ => Some(StackEffect::new(0, 1)),
+            },
+            OpCode::GetStatic(index) => Some(StackEffect::new(0, field_slots(*index)?)),
+            OpCode::PutStatic(index) => Some(StackEffect::new(field_slots(*index)?, 0)),
+            OpCode::GetField(index) => Some(StackEffect::new(1, field_slots(*index)?)),
+            OpCode::PutField(index) => Some(StackEffect::new(1 + field_slots(*index)?, 0)),
+            OpCode::InvokeVirtual(index) | OpCode::InvokeSpecial(index) => {
+                invoke(self.any_method_ref(*index)?.descriptor, 1)
+            }
+            OpCode::InvokeInterface(index, _) => {
+                invoke(self.interface_method_ref(*index)?.descriptor, 1)
+            }
+            OpCode::InvokeStatic(index) => invoke(self.any_method_ref(*index)?.descriptor, 0),
+            OpCode::InvokeDynamic(index) => invoke(self.invoke_dynamic(*index)?.descriptor, 0),
+            _ => None,
+        }
+    }
+}
+#[test]
+fn resolve_constant_pool() {
+    let mut file = std::fs::File::open("test/ConstantPool.class").unwrap();
+    let class = crate::import_class_file(&mut file).unwrap();
+    let pool = class.constant_pool();
+    assert_eq!(
+        pool.get(1),
+        Some(Constant::MethodRef(MethodRef {
+            class: "java/lang/Object",
+            name: "<init>",
+            descriptor: "()V"
+        }))
+    );
+    assert_eq!(
+        pool.field_ref(9),
+        Some(FieldRef {
+            class: "ConstantPool",
+            name: "name",
+            descriptor: "Ljava/lang/String;"
+        })
+    );
+    assert_eq!(pool.interface_method_ref(14).map(|size| size.name), Some("size"));
+    assert_eq!(pool.any_method_ref(14), pool.interface_method_ref(14));
+    assert_eq!(pool.invoke_dynamic(20).map(|indy| indy.name), Some("get"));
+    assert_eq!(pool.get(7), Some(Constant::String("ConstantPool")));
+    // `Long` and `Double` take 2 slots, and the second one can't be used.
+    assert_eq!(pool.get(70), Some(Constant::Long(0x123456789abc)));
+    assert_eq!(pool.get(71), None);
+    assert_eq!(pool.get(72), Some(Constant::Double(0.5)));
+    assert_eq!(pool.get(73), None);
+    assert_eq!(pool.utf8(74), Some("Code"));
+    let handle = pool.method_handle(90).unwrap();
+    assert_eq!(handle.kind, ReferenceKind::InvokeStatic);
+    assert!(
+        matches!(handle.reference, MemberRef::Method(method) if method.name == "metafactory")
+    );
+    assert_eq!(pool.get(97), Some(Constant::MethodType("()Ljava/lang/Object;")));
+    assert!(pool.iter().all(|(index, _)| index != 71 && index != 73));
+    assert_eq!(pool.get(0), None);
+    assert_eq!(pool.get(pool.count()), None);
+    let main = &class.methods()[3];
+    assert_eq!(class.get_utf8(main.name()), Some("main"));
+    let code = main.code().unwrap();
+    let mut depth = 0;
+    for (_, op) in code.instructions() {
+        depth += pool.stack_effect(op).unwrap().net();
+        assert!((0..=code.max_stack() as i32).contains(&depth));
+    }
+    assert_eq!(depth, 0);
+}
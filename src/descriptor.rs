@@ -0,0 +1,301 @@
+use crate::IString;
+/// Primitive type of the JVM, as used in field and method descriptors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BaseType {
+    Byte,
+    Char,
+    Double,
+    Float,
+    Int,
+    Long,
+    Short,
+    Boolean,
+}
+impl BaseType {
+    pub fn from_char(c: char) -> Option<Self> {
+        Some(match c {
+            'B' => Self::Byte,
+            'C' => Self::Char,
+            'D' => Self::Double,
+            'F' => Self::Float,
+            'I' => Self::Int,
+            'J' => Self::Long,
+            'S' => Self::Short,
+            'Z' => Self::Boolean,
+            _ => return None,
+        })
+    }
+    pub fn as_char(&self) -> char {
+        match self {
+            Self::Byte => 'B',
+            Self::Char => 'C',
+            Self::Double => 'D',
+            Self::Float => 'F',
+            Self::Int => 'I',
+            Self::Long => 'J',
+            Self::Short => 'S',
+            Self::Boolean => 'Z',
+        }
+    }
+    /// Number of local variable or operand stack slots a value of this type takes.
+    pub fn slot_size(&self) -> u8 {
+        match self {
+            Self::Long | Self::Double => 2,
+            _ => 1,
+        }
+    }
+}
+/// Type of a field, parameter or local variable, e.g. `I`, `Ljava/lang/String;` or `[[D`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum FieldType {
+    Base(BaseType),
+    /// Object type, holding the internal name of the class, e.g. `java/lang/String`.
+    Object(IString),
+    /// Array of the inner type. Multi-dimensional arrays are arrays of arrays.
+    Array(Box<FieldType>),
+}
+/// Parameter and return types of a method, e.g. `(IJLjava/lang/Object;)V`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct MethodDescriptor {
+    parameters: Box<[FieldType]>,
+    return_type: Option<FieldType>,
+}
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DescriptorError {
+    /// Descriptor ended in the middle of a type.
+    UnexpectedEnd,
+    /// Character at this byte offset can't start a type, or is not allowed in a class name.
+    UnexpectedChar(char, usize),
+    /// Descriptor contains something after a complete type, starting at this byte offset.
+    TrailingCharacters(usize),
+    /// Array type has more than 255 dimensions.
+    TooManyDimensions,
+    /// Generic signature has type arguments nested deeper than 256 levels.
+    TooDeeplyNested,
+}
+impl std::fmt::Display for DescriptorError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::UnexpectedEnd => write!(f, "descriptor ended unexpectedly"),
+            Self::UnexpectedChar(c, pos) => write!(f, "unexpected character {c:?} at {pos}"),
+            Self::TrailingCharacters(pos) => write!(f, "trailing characters at {pos}"),
+            Self::TooManyDimensions => write!(f, "array type has more than 255 dimensions"),
+            Self::TooDeeplyNested => write!(f, "type arguments are nested too deeply"),
+        }
+    }
+}
+impl std::error::Error for DescriptorError {}
+/// Parses a single field type starting at `pos`, returning it and the position just after it.
+fn parse_field_type(src: &str, mut pos: usize) -> Result<(FieldType, usize), DescriptorError> {
+    let mut dimensions = 0;
+    while src[pos..].starts_with('[') {
+        dimensions += 1;
+        pos += 1;
+    }
+    if dimensions > 255 {
+        return Err(DescriptorError::TooManyDimensions);
+    }
+    let c = src[pos..]
+        .chars()
+        .next()
+        .ok_or(DescriptorError::UnexpectedEnd)?;
+    let (mut field_type, end) = if let Some(base) = BaseType::from_char(c) {
+        (FieldType::Base(base), pos + 1)
+    } else if c == 'L' {
+        let name_start = pos + 1;
+        let name_len = src[name_start..]
+            .find(';')
+            .ok_or(DescriptorError::UnexpectedEnd)?;
+        let name = &src[name_start..name_start + name_len];
+        if name.is_empty() {
+            return Err(DescriptorError::UnexpectedChar(';', name_start));
+        }
+        if let Some(bad) = name.find(['.', '[']) {
+            return Err(DescriptorError::UnexpectedChar(
+                name[bad..].chars().next().unwrap_or_default(),
+                name_start + bad,
+            ));
+        }
+        (FieldType::Object(name.into()), name_start + name_len + 1)
+    } else {
+        return Err(DescriptorError::UnexpectedChar(c, pos));
+    };
+    for _ in 0..dimensions {
+        field_type = FieldType::Array(Box::new(field_type));
+    }
+    Ok((field_type, end))
+}
+impl FieldType {
+    /// Parses a field descriptor, e.g. `[Ljava/lang/String;`.
+    pub fn parse(src: &str) -> Result<Self, DescriptorError> {
+        let (field_type, end) = parse_field_type(src, 0)?;
+        if end != src.len() {
+            return Err(DescriptorError::TrailingCharacters(end));
+        }
+        Ok(field_type)
+    }
+    /// Number of local variable or operand stack slots a value of this type takes.
+    pub fn slot_size(&self) -> u8 {
+        match self {
+            Self::Base(base) => base.slot_size(),
+            _ => 1,
+        }
+    }
+    pub fn is_reference(&self) -> bool {
+        !matches!(self, Self::Base(_))
+    }
+    /// Number of array dimensions, 0 for non-array types. Parsed descriptors have at most 255, but a `FieldType`
+    /// built directly may have more.
+    pub fn dimensions(&self) -> usize {
+        let mut dimensions = 0;
+        let mut curr = self;
+        while let Self::Array(inner) = curr {
+            dimensions += 1;
+            curr = inner;
+        }
+        dimensions
+    }
+    /// Innermost element type of an array, or the type itself for non-array types.
+    pub fn element_type(&self) -> &FieldType {
+        let mut curr = self;
+        while let Self::Array(inner) = curr {
+            curr = inner;
+        }
+        curr
+    }
+}
+impl std::str::FromStr for FieldType {
+    type Err = DescriptorError;
+    fn from_str(src: &str) -> Result<Self, Self::Err> {
+        Self::parse(src)
+    }
+}
+impl std::fmt::Display for FieldType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Base(base) => write!(f, "{}", base.as_char()),
+            Self::Object(name) => write!(f, "L{name};"),
+            Self::Array(inner) => write!(f, "[{inner}"),
+        }
+    }
+}
+impl MethodDescriptor {
+    pub fn new(parameters: impl Into<Box<[FieldType]>>, return_type: Option<FieldType>) -> Self {
+        Self {
+            parameters: parameters.into(),
+            return_type,
+        }
+    }
+    /// Parses a method descriptor, e.g. `([Ljava/lang/String;)V`.
+    pub fn parse(src: &str) -> Result<Self, DescriptorError> {
+        let mut pos = match src.chars().next() {
+            Some('(') => 1,
+            Some(c) => return Err(DescriptorError::UnexpectedChar(c, 0)),
+            None => return Err(DescriptorError::UnexpectedEnd),
+        };
+        let mut parameters = Vec::new();
+        loop {
+            if pos >= src.len() {
+                return Err(DescriptorError::UnexpectedEnd);
+            }
+            if src[pos..].starts_with(')') {
+                pos += 1;
+                break;
+            }
+            let (parameter, end) = parse_field_type(src, pos)?;
+            parameters.push(parameter);
+            pos = end;
+        }
+        let return_type = if src[pos..].starts_with('V') {
+            pos += 1;
+            None
+        } else {
+            let (return_type, end) = parse_field_type(src, pos)?;
+            pos = end;
+            Some(return_type)
+        };
+        if pos != src.len() {
+            return Err(DescriptorError::TrailingCharacters(pos));
+        }
+        Ok(Self {
+            parameters: parameters.into(),
+            return_type,
+        })
+    }
+    pub fn parameters(&self) -> &[FieldType] {
+        &self.parameters
+    }
+    /// Return type of the method, `None` if it returns `void`.
+    pub fn return_type(&self) -> Option<&FieldType> {
+        self.return_type.as_ref()
+    }
+    pub fn argument_count(&self) -> usize {
+        self.parameters.len()
+    }
+    /// Number of slots the arguments take, not counting `this`.
+    pub fn argument_slots(&self) -> u16 {
+        self.parameters
+            .iter()
+            .map(|parameter| parameter.slot_size() as u16)
+            .sum()
+    }
+    /// Number of slots the return value takes, 0 for `void`.
+    pub fn return_slots(&self) -> u8 {
+        self.return_type
+            .as_ref()
+            .map_or(0, |return_type| return_type.slot_size())
+    }
+}
+impl std::str::FromStr for MethodDescriptor {
+    type Err = DescriptorError;
+    fn from_str(src: &str) -> Result<Self, Self::Err> {
+        Self::parse(src)
+    }
+}
+impl std::fmt::Display for MethodDescriptor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "(")?;
+        for parameter in self.parameters.iter() {
+            write!(f, "{parameter}")?;
+        }
+        write!(f, ")")?;
+        match &self.return_type {
+            Some(return_type) => write!(f, "{return_type}"),
+            None => write!(f, "V"),
+        }
+    }
+}
+#[test]
+fn parse_descriptors() {
+    for src in ["I", "J", "Ljava/lang/String;", "[[[D", "[Ljava/util/Map$Entry;"] {
+        assert_eq!(FieldType::parse(src).unwrap().to_string(), src);
+    }
+    let matrix = FieldType::parse("[[J").unwrap();
+    assert_eq!(matrix.dimensions(), 2);
+    assert_eq!(matrix.element_type(), &FieldType::Base(BaseType::Long));
+    assert_eq!(matrix.slot_size(), 1);
+    assert_eq!(FieldType::parse("D").unwrap().slot_size(), 2);
+    let method = MethodDescriptor::parse("(IJ[Ljava/lang/Object;D)Ljava/lang/String;").unwrap();
+    assert_eq!(method.argument_count(), 4);
+    assert_eq!(method.argument_slots(), 6);
+    assert_eq!(
+        method.return_type(),
+        Some(&FieldType::Object("java/lang/String".into()))
+    );
+    assert_eq!(
+        method.to_string(),
+        "(IJ[Ljava/lang/Object;D)Ljava/lang/String;"
+    );
+    let main = MethodDescriptor::parse("([Ljava/lang/String;)V").unwrap();
+    assert_eq!((main.return_type(), main.return_slots()), (None, 0));
+    assert_eq!(FieldType::parse("V"), Err(DescriptorError::UnexpectedChar('V', 0)));
+    assert_eq!(FieldType::parse("Ljava/lang/Object"), Err(DescriptorError::UnexpectedEnd));
+    assert_eq!(FieldType::parse("II"), Err(DescriptorError::TrailingCharacters(1)));
+    assert_eq!(FieldType::parse("L;"), Err(DescriptorError::UnexpectedChar(';', 1)));
+    assert_eq!(MethodDescriptor::parse("(I"), Err(DescriptorError::UnexpectedEnd));
+    assert_eq!(MethodDescriptor::parse("()VI"), Err(DescriptorError::TrailingCharacters(3)));
+    assert_eq!(FieldType::parse(&"[".repeat(256)), Err(DescriptorError::TooManyDimensions));
+    assert_eq!(FieldType::parse(&format!("{}I", "[".repeat(255))).unwrap().dimensions(), 255);
+    let deep = (0..300).fold(FieldType::Base(BaseType::Int), |inner, _| FieldType::Array(Box::new(inner)));
+    assert_eq!(deep.dimensions(), 300);
+}
@@ -0,0 +1,199 @@
+use crate::IString;
+/// Error returned when a class file or jar can't be imported.
+///
+/// Besides the [`ImportErrorKind`], it records where the problem was found: the byte offset within the class file,
+/// the name of the class, and the field or method and attribute which were being parsed, if they are known.
+#[derive(Debug)]
+pub struct JavaImportError {
+    kind: ImportErrorKind,
+    offset: Option<u64>,
+    class: Option<IString>,
+    member: Option<IString>,
+    attribute: Option<IString>,
+}
+/// Reason why importing failed.
+#[derive(Debug)]
+pub enum ImportErrorKind {
+    /// Reading from the source failed.
+    IoError(std::io::Error),
+    /// The class file ended unexpectedly.
+    UnexpectedEof,
+    /// The file does not start with `0xCAFEBABE`.
+    NotJavaBytecode(u32),
+    /// Major and minor version of the class file.
+    UnsuportedVersion(u16, u16),
+    /// Constant pool entry has an unknown tag.
+    InvalidConstantTag(u8),
+    /// Constant pool index is 0, out of range, or points to an entry of the wrong kind.
+    BadConstantIndex(u16),
+    /// A `Utf8` constant is not valid modified UTF-8, or not valid Unicode.
+    InvalidUtf8,
+    InvalidThisClass,
+    InvalidSuperClass,
+    /// Opcode is reserved, or not supported.
+    UnknownOpcode(u8),
+    /// Instruction which can't be modified by `wide`.
+    InvalidWide(u8),
+    /// Length of the bytecode is 0 or larger than 65535.
+    InvalidCodeLength(u32),
+    /// A `lookupswitch` or `tableswitch` has a negative or too big number of entries.
+    InvalidSwitch,
+    /// Bytes which must be zero(e.g. in `invokeinterface` or `invokedynamic`) are not.
+    NonZeroPadding,
+    /// The last instruction does not end where the bytecode does.
+    InstructionPastEnd,
+    /// The contents of an attribute need more bytes than its declared length.
+    TruncatedAttribute { length: u32 },
+    /// The contents of an attribute don't use all of its declared length.
+    WrongAttributeLength { length: u32, used: u32 },
+    /// A `Code` attribute is inside of another `Code` attribute, or of a record component.
+    NestedCode,
+    /// A `StackMapTable` frame has a reserved frame type or verification type tag, or its offset overflows.
+    InvalidStackMapFrame,
+    /// An annotation element value has an unknown tag, or annotations are nested too deeply.
+    InvalidAnnotation,
+    /// A jar is nested inside of too many other jars.
+    JarTooDeeplyNested,
+    /// A jar entry is larger than this many bytes when decompressed.
+    EntryTooLarge(u64),
+    ZipError(zip::result::ZipError),
+}
+impl JavaImportError {
+    pub(crate) fn new(kind: ImportErrorKind) -> Self {
+        Self {
+            kind,
+            offset: None,
+            class: None,
+            member: None,
+            attribute: None,
+        }
+    }
+    pub fn kind(&self) -> &ImportErrorKind {
+        &self.kind
+    }
+    pub fn into_kind(self) -> ImportErrorKind {
+        self.kind
+    }
+    /// Byte offset within the class file at which the error was detected.
+    pub fn offset(&self) -> Option<u64> {
+        self.offset
+    }
+    /// Internal name of the class being imported, if it was already read.
+    pub fn class_name(&self) -> Option<&str> {
+        self.class.as_deref()
+    }
+    /// Name of the field or method being parsed.
+    pub fn member(&self) -> Option<&str> {
+        self.member.as_deref()
+    }
+    /// Name of the innermost attribute being parsed.
+    pub fn attribute(&self) -> Option<&str> {
+        self.attribute.as_deref()
+    }
+    /// Replaces the kind of this error, keeping its context.
+    pub(crate) fn with_kind(mut self, kind: ImportErrorKind) -> Self {
+        self.kind = kind;
+        self
+    }
+    // Context is added while the error travels up, so the innermost(most precise) information is kept.
+    pub(crate) fn at(mut self, offset: u64) -> Self {
+        self.offset.get_or_insert(offset);
+        self
+    }
+    pub(crate) fn in_class(mut self, class: Option<&str>) -> Self {
+        if self.class.is_none() {
+            self.class = class.map(Into::into);
+        }
+        self
+    }
+    pub(crate) fn in_member(mut self, member: Option<&str>) -> Self {
+        if self.member.is_none() {
+            self.member = member.map(Into::into);
+        }
+        self
+    }
+    pub(crate) fn in_attribute(mut self, attribute: &str) -> Self {
+        self.attribute.get_or_insert_with(|| attribute.into());
+        self
+    }
+}
+impl std::fmt::Display for ImportErrorKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::IoError(err) => write!(f, "io error: {err}"),
+            Self::UnexpectedEof => write!(f, "unexpected end of file"),
+            Self::NotJavaBytecode(magic) => write!(f, "not a class file(magic 0x{magic:08x})"),
+            Self::UnsuportedVersion(major, minor) => {
+                write!(f, "unsupported class file version {major}.{minor}")
+            }
+            Self::InvalidConstantTag(tag) => write!(f, "invalid constant tag {tag}"),
+            Self::BadConstantIndex(index) => write!(f, "bad constant index {index}"),
+            Self::InvalidUtf8 => write!(f, "invalid Utf8 constant"),
+            Self::InvalidThisClass => write!(f, "invalid this_class"),
+            Self::InvalidSuperClass => write!(f, "invalid super_class"),
+            Self::UnknownOpcode(op) => write!(f, "unknown opcode 0x{op:02x}"),
+            Self::InvalidWide(op) => write!(f, "opcode 0x{op:02x} can't be used with wide"),
+            Self::InvalidCodeLength(length) => write!(f, "invalid code length {length}"),
+            Self::InvalidSwitch => write!(f, "invalid switch"),
+            Self::NonZeroPadding => write!(f, "non-zero padding"),
+            Self::InstructionPastEnd => write!(f, "instruction extends past the end of code"),
+            Self::TruncatedAttribute { length } => {
+                write!(f, "attribute contents extend past its length({length})")
+            }
+            Self::WrongAttributeLength { length, used } => {
+                write!(f, "attribute length is {length}, but only {used} bytes are used")
+            }
+            Self::NestedCode => write!(f, "nested Code attribute"),
+            Self::InvalidStackMapFrame => write!(f, "invalid stack map frame"),
+            Self::InvalidAnnotation => write!(f, "invalid annotation"),
+            Self::JarTooDeeplyNested => write!(f, "jar is nested too deeply"),
+            Self::EntryTooLarge(limit) => write!(f, "jar entry is larger than {limit} bytes"),
+            Self::ZipError(err) => write!(f, "zip error: {err}"),
+        }
+    }
+}
+impl std::fmt::Display for JavaImportError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.kind)?;
+        if let Some(offset) = self.offset {
+            write!(f, " at byte {offset}")?;
+        }
+        if let Some(class) = &self.class {
+            write!(f, " in class {class}")?;
+        }
+        if let Some(member) = &self.member {
+            write!(f, ", member {member}")?;
+        }
+        if let Some(attribute) = &self.attribute {
+            write!(f, ", attribute {attribute}")?;
+        }
+        Ok(())
+    }
+}
+impl std::error::Error for JavaImportError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match &self.kind {
+            ImportErrorKind::IoError(err) => Some(err),
+            ImportErrorKind::ZipError(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+impl From<ImportErrorKind> for JavaImportError {
+    fn from(kind: ImportErrorKind) -> Self {
+        Self::new(kind)
+    }
+}
+impl From<std::io::Error> for JavaImportError {
+    fn from(err: std::io::Error) -> Self {
+        match err.kind() {
+            std::io::ErrorKind::UnexpectedEof => Self::new(ImportErrorKind::UnexpectedEof),
+            _ => Self::new(ImportErrorKind::IoError(err)),
+        }
+    }
+}
+impl From<zip::result::ZipError> for JavaImportError {
+    fn from(err: zip::result::ZipError) -> Self {
+        Self::new(ImportErrorKind::ZipError(err))
+    }
+}
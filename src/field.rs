@@ -1,26 +1,30 @@
-use crate::import::{load_u16};
+use crate::import::{load_u16, store_u16};
+use crate::annotation::{annotations, type_annotations, Annotation, TypeAnnotation};
 use crate::attribute::Attribute;
-use crate::import::AccessFlags;
-use crate::import::ConstantItem;
+use crate::import::{AccessFlags, Utf8};
+use crate::import::{ConstantItem, OffsetReader};
+use crate::constant_pool::ConstantPool;
+use crate::error::JavaImportError;
 #[derive(Debug)]
 pub struct Field {
     pub(crate) flags: AccessFlags,
     pub(crate) name_index: u16,
     pub(crate) descriptor_index: u16,
-    attributes: Box<[Attribute]>,
+    pub(crate) attributes: Box<[(u16, Attribute)]>,
 }
 impl Field {
     pub(crate) fn read<R: std::io::Read>(
-        src: &mut R,
+        src: &mut OffsetReader<R>,
         const_items: &[ConstantItem],
-    ) -> Result<Self, std::io::Error> {
+    ) -> Result<Self, JavaImportError> {
         let flags = AccessFlags::read(src)?;
         let name_index = load_u16(src)?;
         let descriptor_index = load_u16(src)?;
+        let name = ConstantPool::new(const_items).utf8(name_index);
         let attributes_count = load_u16(src)?;
         let mut attributes = Vec::with_capacity(attributes_count as usize);
         for _ in 0..attributes_count {
-            attributes.push(Attribute::read(src, const_items)?);
+            attributes.push(Attribute::read(src, const_items).map_err(|err| err.in_member(name))?);
         }
         Ok(Self {
             flags,
@@ -29,4 +33,42 @@ impl Field {
             attributes: attributes.into(),
         })
     }
+    pub(crate) fn write<W: std::io::Write>(&self, dst: &mut W) -> std::io::Result<()> {
+        self.flags.write(dst)?;
+        store_u16(dst, self.name_index)?;
+        store_u16(dst, self.descriptor_index)?;
+        Attribute::write_all(&self.attributes, dst)
+    }
+    pub fn access_flags(&self) -> AccessFlags {
+        self.flags
+    }
+    pub fn name(&self) -> Utf8 {
+        Utf8(self.name_index)
+    }
+    pub fn descriptor(&self) -> Utf8 {
+        Utf8(self.descriptor_index)
+    }
+    /// Generic signature of this field, if it has one.
+    pub fn signature(&self) -> Option<Utf8> {
+        self.attributes.iter().find_map(|(_, attribute)| match attribute {
+            Attribute::Signature { signature } => Some(Utf8(*signature)),
+            _ => None,
+        })
+    }
+    /// Annotations with `RUNTIME` retention.
+    pub fn annotations(&self) -> &[Annotation] {
+        annotations(&self.attributes, true)
+    }
+    /// Annotations with `CLASS` retention, which are stored in the class file but not visible through reflection.
+    pub fn invisible_annotations(&self) -> &[Annotation] {
+        annotations(&self.attributes, false)
+    }
+    /// Type annotations with `RUNTIME` retention on types in the declaration of this field.
+    pub fn type_annotations(&self) -> &[TypeAnnotation] {
+        type_annotations(&self.attributes, true)
+    }
+    /// Type annotations with `CLASS` retention on types in the declaration of this field.
+    pub fn invisible_type_annotations(&self) -> &[TypeAnnotation] {
+        type_annotations(&self.attributes, false)
+    }
 }
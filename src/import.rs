@@ -1,9 +1,18 @@
 #![allow(dead_code)]
-use crate::attribute::BootstrapMethod;
 use crate::IString;
-use crate::attribute::Attribute;
+use crate::annotation::{annotations, type_annotations, Annotation, TypeAnnotation};
+use crate::attribute::{Attribute, InnerClass};
 use crate::field::Field;
-use crate::opcodes::OpCode;
+use crate::method::Method;
+use crate::module::Module;
+use crate::nesting::Nesting;
+use crate::record::RecordComponent;
+use crate::constant_pool::ConstantPool;
+use crate::error::{ImportErrorKind, JavaImportError};
+use crate::mutf8::Mutf8Error;
+use crate::builder::{BuildError, ConstantPoolBuilder};
+use crate::descriptor::MethodDescriptor;
+use crate::stack_map::{compute_frames, ClassHierarchy, FrameContext};
 macro_rules! load_fn_impl {
     ($name:ident,$tpe:ty) => {
         pub(crate) fn $name<R: std::io::Read>(src: &mut R) -> std::io::Result<$tpe> {
@@ -23,53 +32,107 @@ load_fn_impl!(load_u16, u16);
 load_fn_impl!(load_i16, i16);
 load_fn_impl!(load_u8, u8);
 load_fn_impl!(load_i8, i8);
-pub struct Utf8(u16);
-pub struct ClassInfo(u16);
-pub(crate) struct Method {
-    access_flags: AccessFlags,
-    name_index: u16,
-    descriptor_index: u16,
-    attributes: Box<[Attribute]>,
+macro_rules! store_fn_impl {
+    ($name:ident,$tpe:ty) => {
+        pub(crate) fn $name<W: std::io::Write>(dst: &mut W, value: $tpe) -> std::io::Result<()> {
+            dst.write_all(&value.to_be_bytes())
+        }
+    };
 }
-impl Method {
-    fn read<R: std::io::Read>(
-        src: &mut R,
-        const_items: &[ConstantItem],
-    ) -> Result<Self, std::io::Error> {
-        let access_flags = AccessFlags::read(src)?;
-        let name_index = load_u16(src)?;
-        let descriptor_index = load_u16(src)?;
-        let attributes_count = load_u16(src)?;
-        let mut attributes = Vec::with_capacity(attributes_count as usize);
-        for _ in 0..attributes_count {
-            attributes.push(Attribute::read(src, const_items)?);
+store_fn_impl!(store_i64, i64);
+store_fn_impl!(store_f64, f64);
+store_fn_impl!(store_i32, i32);
+store_fn_impl!(store_u32, u32);
+store_fn_impl!(store_f32, f32);
+store_fn_impl!(store_u16, u16);
+store_fn_impl!(store_i16, i16);
+store_fn_impl!(store_u8, u8);
+store_fn_impl!(store_i8, i8);
+macro_rules! store_len_fn_impl {
+    ($name:ident,$store:ident,$tpe:ty) => {
+        /// Stores a length or count, failing if it does not fit in its field instead of truncating it.
+        pub(crate) fn $name<W: std::io::Write>(dst: &mut W, len: usize) -> std::io::Result<()> {
+            let Ok(value) = <$tpe>::try_from(len) else {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidInput,
+                    format!("length {len} does not fit in a {}", stringify!($tpe)),
+                ));
+            };
+            $store(dst, value)
         }
-        Ok(Self {
-            access_flags,
-            name_index,
-            descriptor_index,
-            attributes: attributes.into(),
-        })
+    };
+}
+store_len_fn_impl!(store_len_u32, store_u32, u32);
+store_len_fn_impl!(store_len_u16, store_u16, u16);
+store_len_fn_impl!(store_len_u8, store_u8, u8);
+/// Reader which keeps track of how many bytes were read from the start of the class file, so errors can say where they happened.
+pub(crate) struct OffsetReader<R> {
+    inner: R,
+    offset: u64,
+}
+impl<R: std::io::Read> OffsetReader<R> {
+    pub(crate) fn new(inner: R, offset: u64) -> Self {
+        Self { inner, offset }
+    }
+    pub(crate) fn offset(&self) -> u64 {
+        self.offset
+    }
+    /// Creates an error of `kind`, located at the current offset.
+    pub(crate) fn error(&self, kind: ImportErrorKind) -> JavaImportError {
+        JavaImportError::new(kind).at(self.offset)
     }
 }
-
+impl<'a> OffsetReader<&'a [u8]> {
+    /// Borrows the next `len` bytes, without copying them.
+    pub(crate) fn read_slice(&mut self, len: usize) -> Result<&'a [u8], JavaImportError> {
+        if self.inner.len() < len {
+            return Err(self.error(ImportErrorKind::UnexpectedEof));
+        }
+        let (slice, rest) = self.inner.split_at(len);
+        self.inner = rest;
+        self.offset += len as u64;
+        Ok(slice)
+    }
+}
+impl<R: std::io::Read> std::io::Read for OffsetReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.offset += read as u64;
+        Ok(read)
+    }
+}
+/// Index of a `Utf8` constant, resolved with [`JavaClassFile::get_utf8`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Utf8(pub(crate) u16);
+/// Index of a `Class` constant, resolved with [`JavaClassFile::get_class_info`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ClassInfo(pub(crate) u16);
+/// Index of a `Module` constant, resolved with [`JavaClassFile::get_module_info`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ModuleInfo(pub(crate) u16);
+/// Index of a `Package` constant, resolved with [`JavaClassFile::get_package_info`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PackageInfo(pub(crate) u16);
+#[derive(Debug)]
 pub struct JavaClassFile {
-    const_items: Box<[ConstantItem]>,
+    pub(crate) major_version: u16,
+    pub(crate) minor_version: u16,
+    pub(crate) const_items: Box<[ConstantItem]>,
     //name: IString,
-    this_class: u16,
-    super_class: u16,
-    fields: Box<[Field]>,
-    methods: Box<[Method]>,
-    interfaces: Box<[u16]>,
-    attributes: Box<[Attribute]>, //field_names: Box<[IString]>,
-    flags: AccessFlags,
+    pub(crate) this_class: u16,
+    pub(crate) super_class: u16,
+    pub(crate) fields: Box<[Field]>,
+    pub(crate) methods: Box<[Method]>,
+    pub(crate) interfaces: Box<[u16]>,
+    pub(crate) attributes: Box<[(u16, Attribute)]>, //field_names: Box<[IString]>,
+    pub(crate) flags: AccessFlags,
 }
 impl JavaClassFile {
     pub fn get_utf8(&self, utf8: Utf8) -> Option<&str> {
         if utf8.0 == 0{
             return None;
         }
-        let utf8 = &self.const_items[utf8.0 as usize - 1];
+        let utf8 = self.const_items.get(utf8.0 as usize - 1)?;
         if let ConstantItem::Utf8(string) = utf8 {
             Some(string)
         } else {
@@ -80,13 +143,37 @@ impl JavaClassFile {
         if class_info.0 == 0{
             return None;
         }
-        let class_info = &self.const_items[class_info.0 as usize - 1];
+        let class_info = self.const_items.get(class_info.0 as usize - 1)?;
         if let ConstantItem::Class{name_index} = class_info {
             Some(Utf8(*name_index))
         } else {
             None
         }
     }
+    /// Name of a module, e.g. `java.base`.
+    pub fn get_module_info(&self, module_info:ModuleInfo) -> Option<Utf8> {
+        if module_info.0 == 0{
+            return None;
+        }
+        let module_info = self.const_items.get(module_info.0 as usize - 1)?;
+        if let ConstantItem::Module{name_index} = module_info {
+            Some(Utf8(*name_index))
+        } else {
+            None
+        }
+    }
+    /// Internal name of a package, e.g. `java/lang`.
+    pub fn get_package_info(&self, package_info:PackageInfo) -> Option<Utf8> {
+        if package_info.0 == 0{
+            return None;
+        }
+        let package_info = self.const_items.get(package_info.0 as usize - 1)?;
+        if let ConstantItem::Package{name_index} = package_info {
+            Some(Utf8(*name_index))
+        } else {
+            None
+        }
+    }
     pub fn this_class(&self)->ClassInfo{
         ClassInfo(self.this_class)
     }
@@ -96,6 +183,193 @@ impl JavaClassFile {
     pub fn fields(&self)->&[Field]{
         &self.fields
     }
+    pub fn methods(&self)->&[Method]{
+        &self.methods
+    }
+    /// Interfaces this class directly implements, or an interface directly extends.
+    pub fn interfaces(&self)->impl Iterator<Item = ClassInfo> + '_{
+        self.interfaces.iter().map(|interface| ClassInfo(*interface))
+    }
+    /// Checks if this class is `sealed`, i.e. has a `PermittedSubclasses` attribute.
+    pub fn is_sealed(&self)->bool{
+        self.attributes.iter().any(|(_, attribute)| matches!(attribute, Attribute::PermittedSubclasses { .. }))
+    }
+    /// Classes which are allowed to directly extend or implement this `sealed` class.
+    pub fn permitted_subclasses(&self)->impl Iterator<Item = ClassInfo> + '_{
+        self.attributes
+            .iter()
+            .filter_map(|(_, attribute)| match attribute {
+                Attribute::PermittedSubclasses { classes } => Some(classes.iter()),
+                _ => None,
+            })
+            .flatten()
+            .map(|class| ClassInfo(*class))
+    }
+    /// Generic signature of this class, if it has one.
+    pub fn signature(&self)->Option<Utf8>{
+        self.attributes.iter().find_map(|(_, attribute)| match attribute {
+            Attribute::Signature { signature } => Some(Utf8(*signature)),
+            _ => None,
+        })
+    }
+    /// Nested classes which this class declares or refers to, including the classes it is nested in.
+    pub fn inner_classes(&self)->&[InnerClass]{
+        self.attributes.iter().find_map(|(_, attribute)| match attribute {
+            Attribute::InnerClasses { classes } => Some(&classes[..]),
+            _ => None,
+        }).unwrap_or_default()
+    }
+    /// Components of this class, in declaration order. `None` if this is not a record class.
+    pub fn record_components(&self)->Option<&[RecordComponent]>{
+        self.attributes.iter().find_map(|(_, attribute)| match attribute {
+            Attribute::Record { components } => Some(&components[..]),
+            _ => None,
+        })
+    }
+    /// Declaration of the module, if this is a `module-info.class`.
+    pub fn module(&self)->Option<&Module>{
+        self.attributes.iter().find_map(|(_, attribute)| match attribute {
+            Attribute::Module(module) => Some(module),
+            _ => None,
+        })
+    }
+    /// All packages of the module, including the ones which are not exported or opened. Only recorded by some
+    /// tools, e.g. `jar`.
+    pub fn module_packages(&self)->impl Iterator<Item = PackageInfo> + '_{
+        self.attributes
+            .iter()
+            .filter_map(|(_, attribute)| match attribute {
+                Attribute::ModulePackages { packages } => Some(packages.iter()),
+                _ => None,
+            })
+            .flatten()
+            .map(|package| PackageInfo(*package))
+    }
+    /// Main class of the module, started by `java --module` when no class is given.
+    pub fn module_main_class(&self)->Option<ClassInfo>{
+        self.attributes.iter().find_map(|(_, attribute)| match attribute {
+            Attribute::ModuleMainClass { main_class_index } => Some(ClassInfo(*main_class_index)),
+            _ => None,
+        })
+    }
+    /// Where this class was declared: at the top level, as a member of another class, or within a block of code.
+    pub fn nesting(&self)->Nesting<'_>{
+        Nesting::new(self)
+    }
+    /// Annotations with `RUNTIME` retention.
+    pub fn annotations(&self) -> &[Annotation] {
+        annotations(&self.attributes, true)
+    }
+    /// Annotations with `CLASS` retention, which are stored in the class file but not visible through reflection.
+    pub fn invisible_annotations(&self) -> &[Annotation] {
+        annotations(&self.attributes, false)
+    }
+    /// Type annotations with `RUNTIME` retention on types in the declaration of this class.
+    pub fn type_annotations(&self) -> &[TypeAnnotation] {
+        type_annotations(&self.attributes, true)
+    }
+    /// Type annotations with `CLASS` retention on types in the declaration of this class.
+    pub fn invisible_type_annotations(&self) -> &[TypeAnnotation] {
+        type_annotations(&self.attributes, false)
+    }
+    pub fn constant_pool(&self)->ConstantPool<'_>{
+        ConstantPool::new(&self.const_items)
+    }
+    /// Major and minor version of the class file format, e.g. `(61, 0)` for Java 17.
+    pub fn version(&self)->(u16,u16){
+        (self.major_version, self.minor_version)
+    }
+    /// Replaces the `StackMapTable` of each method with frames computed from its bytecode, e.g. after the code
+    /// was changed. Types are merged using the super types from `hierarchy`.
+    pub fn compute_frames(&mut self, hierarchy: &impl ClassHierarchy) -> Result<(), BuildError> {
+        let mut pool = ConstantPoolBuilder::from_items(std::mem::take(&mut self.const_items).into());
+        let result = self.compute_frames_with(&mut pool, hierarchy);
+        let overflowed = pool.overflowed();
+        self.const_items = pool.into_items().into();
+        match result {
+            Ok(()) if overflowed => Err(BuildError::ConstantPoolOverflow),
+            result => result,
+        }
+    }
+    fn compute_frames_with(
+        &mut self,
+        pool: &mut ConstantPoolBuilder,
+        hierarchy: &dyn ClassHierarchy,
+    ) -> Result<(), BuildError> {
+        let class_name = |index| -> IString { pool.pool().class_name(index).unwrap_or_default().into() };
+        let this_class = class_name(self.this_class);
+        let super_class = class_name(self.super_class);
+        let interfaces: Vec<_> = self.interfaces.iter().map(|interface| class_name(*interface)).collect();
+        let mut frames_name = None;
+        for method in self.methods.iter_mut() {
+            let name: IString = pool.pool().utf8(method.name_index).unwrap_or_default().into();
+            let descriptor = pool.pool().utf8(method.descriptor_index).unwrap_or_default();
+            let Ok(descriptor) = MethodDescriptor::parse(descriptor) else {
+                return Err(BuildError::InvalidDescriptor(descriptor.into()));
+            };
+            let is_static = method.access_flags.is_static();
+            let Some(code) = method.attributes.iter_mut().find_map(|(_, attribute)| match attribute {
+                Attribute::Code(code) => Some(code),
+                _ => None,
+            }) else {
+                continue;
+            };
+            let context = FrameContext {
+                this_class: &this_class,
+                super_class: &super_class,
+                interfaces: &interfaces,
+                method_name: &name,
+                descriptor: &descriptor,
+                is_static,
+                hierarchy,
+            };
+            let frames = compute_frames(code, pool, &context)?;
+            // The new table takes the place of the old one.
+            let mut attributes: Vec<_> = std::mem::take(&mut code.attributes).into();
+            let old = attributes
+                .iter()
+                .position(|(_, attribute)| matches!(attribute, Attribute::StackMapTable { .. }));
+            match old {
+                Some(old) if frames.is_empty() => drop(attributes.remove(old)),
+                Some(old) => attributes[old].1 = Attribute::StackMapTable { frames },
+                None if frames.is_empty() => (),
+                None => {
+                    let name_index = *frames_name.get_or_insert_with(|| pool.utf8("StackMapTable"));
+                    attributes.push((name_index, Attribute::StackMapTable { frames }));
+                }
+            }
+            code.attributes = attributes.into();
+        }
+        Ok(())
+    }
+    /// Encodes this class as a class file. Reading a class file and writing it back produces the same bytes,
+    /// besides the padding of switch instructions, which is always written as zeroes.
+    pub fn write<W: std::io::Write>(&self, mut dst: W) -> std::io::Result<()> {
+        let dst = &mut dst;
+        store_u32(dst, 0xCAFEBABE)?;
+        store_u16(dst, self.minor_version)?;
+        store_u16(dst, self.major_version)?;
+        store_u16(dst, self.constant_pool().count())?;
+        for item in self.const_items.iter() {
+            item.write(dst)?;
+        }
+        self.flags.write(dst)?;
+        store_u16(dst, self.this_class)?;
+        store_u16(dst, self.super_class)?;
+        store_len_u16(dst, self.interfaces.len())?;
+        for interface in self.interfaces.iter() {
+            store_u16(dst, *interface)?;
+        }
+        store_len_u16(dst, self.fields.len())?;
+        for field in self.fields.iter() {
+            field.write(dst)?;
+        }
+        store_len_u16(dst, self.methods.len())?;
+        for method in self.methods.iter() {
+            method.write(dst)?;
+        }
+        Attribute::write_all(&self.attributes, dst)
+    }
 }
 #[derive(Debug)]
 pub(crate) enum ConstantItem {
@@ -129,6 +403,10 @@ pub(crate) enum ConstantItem {
         bootstrap_method_attr_index: u16,
         name_and_type_index: u16,
     },
+    Dynamic {
+        bootstrap_method_attr_index: u16,
+        name_and_type_index: u16,
+    },
     MethodHandle {
         reference_kind: u8,
         reference_index: u16,
@@ -143,6 +421,8 @@ pub(crate) enum ConstantItem {
         name_index: u16
     },
     Utf8(IString),
+    /// `Utf8` constant which is not valid Unicode, kept because of [`ImportOptions::keep_invalid_utf8`].
+    RawUtf8(Box<[u8]>),
     Long(i64),
     Padding,
 }
@@ -154,12 +434,6 @@ impl ConstantItem {
         }
     }
 }
-#[derive(Debug)]
-pub enum ConstantImportError {
-    ZeroTypeConstError,
-    IoError(std::io::Error),
-    Utf8Error(std::str::Utf8Error),
-}
 #[derive(Clone, Copy, Debug)]
 pub struct AccessFlags {
     mask: u16,
@@ -169,53 +443,103 @@ impl AccessFlags {
         let mask = load_u16(src)?;
         Ok(Self { mask })
     }
-    pub(crate) fn is_public(&self) -> bool {
+    pub(crate) fn write<W: std::io::Write>(&self, dst: &mut W) -> std::io::Result<()> {
+        store_u16(dst, self.mask)
+    }
+    pub const PUBLIC: Self = Self::from_bits(0x0001);
+    pub const PRIVATE: Self = Self::from_bits(0x0002);
+    pub const PROTECTED: Self = Self::from_bits(0x0004);
+    pub const STATIC: Self = Self::from_bits(0x0008);
+    pub const FINAL: Self = Self::from_bits(0x0010);
+    pub const SUPER: Self = Self::from_bits(0x0020);
+    pub const SYNCHRONIZED: Self = Self::from_bits(0x0020);
+    pub const BRIDGE: Self = Self::from_bits(0x0040);
+    pub const VARARGS: Self = Self::from_bits(0x0080);
+    pub const NATIVE: Self = Self::from_bits(0x0100);
+    pub const INTERFACE: Self = Self::from_bits(0x0200);
+    pub const ABSTRACT: Self = Self::from_bits(0x0400);
+    pub const SYNTHETIC: Self = Self::from_bits(0x1000);
+    pub const ANNOTATION: Self = Self::from_bits(0x2000);
+    pub const ENUM: Self = Self::from_bits(0x4000);
+    /// Flags with the raw bit mask `mask`.
+    pub const fn from_bits(mask: u16) -> Self {
+        Self { mask }
+    }
+    /// Raw bit mask, as stored in the class file.
+    pub fn bits(&self) -> u16 {
+        self.mask
+    }
+    pub fn is_public(&self) -> bool {
         self.mask & 0x0001 != 0
     }
-    pub(crate) fn is_private(&self) -> bool {
+    pub fn is_private(&self) -> bool {
         self.mask & 0x0002 != 0
     }
-    pub(crate) fn is_protected(&self) -> bool {
+    pub fn is_protected(&self) -> bool {
         self.mask & 0x0004 != 0
     }
     pub fn is_static(&self) -> bool {
         self.mask & 0x0008 != 0
     }
-    fn is_final(&self) -> bool {
+    pub fn is_final(&self) -> bool {
         self.mask & 0x0010 != 0
     }
     pub fn is_super(&self) -> bool {
         self.mask & 0x0020 != 0
     }
-    pub(crate) fn is_interface(&self) -> bool {
+    /// Method only, shares its bit with `ACC_SUPER`.
+    pub fn is_synchronized(&self) -> bool {
+        self.mask & 0x0020 != 0
+    }
+    /// Method only, shares its bit with `ACC_VOLATILE`.
+    pub fn is_bridge(&self) -> bool {
+        self.mask & 0x0040 != 0
+    }
+    /// Method only, shares its bit with `ACC_TRANSIENT`.
+    pub fn is_varargs(&self) -> bool {
+        self.mask & 0x0080 != 0
+    }
+    pub fn is_native(&self) -> bool {
+        self.mask & 0x0100 != 0
+    }
+    pub fn is_interface(&self) -> bool {
         self.mask & 0x0200 != 0
     }
-    pub(crate) fn is_abstract(&self) -> bool {
+    pub fn is_abstract(&self) -> bool {
         self.mask & 0x0400 != 0
     }
-    pub(crate) fn is_synthetic(&self) -> bool {
+    pub fn is_synthetic(&self) -> bool {
         self.mask & 0x1000 != 0
     }
-    pub(crate) fn is_annotantion(&self) -> bool {
+    pub fn is_annotantion(&self) -> bool {
         self.mask & 0x2000 != 0
     }
-    pub(crate) fn is_enum(&self) -> bool {
+    pub fn is_enum(&self) -> bool {
         self.mask & 0x4000 != 0
     }
 }
+impl std::ops::BitOr for AccessFlags {
+    type Output = Self;
+    fn bitor(self, rhs: Self) -> Self {
+        Self::from_bits(self.mask | rhs.mask)
+    }
+}
 impl ConstantItem {
-    fn read<R: std::io::Read>(src: &mut R) -> Result<Self, ConstantImportError> {
+    fn read<R: std::io::Read>(src: &mut R, options: &ImportOptions) -> Result<Self, JavaImportError> {
         let tag = load_u8(src)?;
         //println!("tag:{tag}");
         match tag {
-            0 => Err(ConstantImportError::ZeroTypeConstError),
             1 => {
                 let length = load_u16(src)?;
                 let mut bytes = vec![0; length as usize];
                 src.read_exact(&mut bytes)?;
-                let istring: IString = std::str::from_utf8(&bytes)?.to_owned().into_boxed_str();
-                //println!("bytes:{bytes:?} string:{istring}");
-                Ok(Self::Utf8(istring))
+                match crate::mutf8::decode(&bytes) {
+                    Ok(string) => Ok(Self::Utf8(string.into())),
+                    Err(Mutf8Error::LoneSurrogate) if options.keep_invalid_utf8 => {
+                        Ok(Self::RawUtf8(bytes.into()))
+                    }
+                    Err(_) => Err(ImportErrorKind::InvalidUtf8.into()),
+                }
             }
             3 => {
                 let int = load_i32(src)?;
@@ -286,6 +610,14 @@ impl ConstantItem {
                 let descriptor_index = load_u16(src)?;
                 Ok(Self::MethodType { descriptor_index })
             }
+            17 => {
+                let bootstrap_method_attr_index = load_u16(src)?;
+                let name_and_type_index = load_u16(src)?;
+                Ok(Self::Dynamic {
+                    bootstrap_method_attr_index,
+                    name_and_type_index,
+                })
+            }
             18 => {
                 let bootstrap_method_attr_index = load_u16(src)?;
                 let name_and_type_index = load_u16(src)?;
@@ -302,40 +634,182 @@ impl ConstantItem {
                 let name_index = load_u16(src)?;
                 Ok(Self::Package { name_index })
             }
-            2 | 21.. => Err(std::io::Error::new(
-                std::io::ErrorKind::Other,
-                "Invalid ConstItem type!",
-            )
-            .into()),
-            _ => todo!("Unhandled const info kind: {tag}"),
+            0 | 2 | 13 | 14 | 21.. => Err(ImportErrorKind::InvalidConstantTag(tag).into()),
+        }
+    }
+}
+impl ConstantItem {
+    pub(crate) fn write<W: std::io::Write>(&self, dst: &mut W) -> std::io::Result<()> {
+        match self {
+            Self::Utf8(string) => {
+                let bytes = crate::mutf8::encode(string);
+                let Ok(length) = u16::try_from(bytes.len()) else {
+                    return Err(std::io::Error::new(
+                        std::io::ErrorKind::InvalidInput,
+                        "Utf8 constant longer than 65535 bytes",
+                    ));
+                };
+                store_u8(dst, 1)?;
+                store_u16(dst, length)?;
+                dst.write_all(&bytes)
+            }
+            Self::RawUtf8(bytes) => {
+                store_u8(dst, 1)?;
+                store_len_u16(dst, bytes.len())?;
+                dst.write_all(bytes)
+            }
+            Self::Intiger(int) => {
+                store_u8(dst, 3)?;
+                store_i32(dst, *int)
+            }
+            Self::Float(float) => {
+                store_u8(dst, 4)?;
+                store_f32(dst, *float)
+            }
+            Self::Long(long) => {
+                store_u8(dst, 5)?;
+                store_i64(dst, *long)
+            }
+            Self::Double(double) => {
+                store_u8(dst, 6)?;
+                store_f64(dst, *double)
+            }
+            Self::Class { name_index } => {
+                store_u8(dst, 7)?;
+                store_u16(dst, *name_index)
+            }
+            Self::ConstString { string_index } => {
+                store_u8(dst, 8)?;
+                store_u16(dst, *string_index)
+            }
+            Self::FieldRef {
+                class_index,
+                name_and_type_index,
+            } => {
+                store_u8(dst, 9)?;
+                store_u16(dst, *class_index)?;
+                store_u16(dst, *name_and_type_index)
+            }
+            Self::MethodRef {
+                class_index,
+                name_and_type_index,
+            } => {
+                store_u8(dst, 10)?;
+                store_u16(dst, *class_index)?;
+                store_u16(dst, *name_and_type_index)
+            }
+            Self::InterfaceMethodRef {
+                class_index,
+                name_and_type_index,
+            } => {
+                store_u8(dst, 11)?;
+                store_u16(dst, *class_index)?;
+                store_u16(dst, *name_and_type_index)
+            }
+            Self::NameAndType {
+                name_index,
+                descriptor_index,
+            } => {
+                store_u8(dst, 12)?;
+                store_u16(dst, *name_index)?;
+                store_u16(dst, *descriptor_index)
+            }
+            Self::MethodHandle {
+                reference_kind,
+                reference_index,
+            } => {
+                store_u8(dst, 15)?;
+                store_u8(dst, *reference_kind)?;
+                store_u16(dst, *reference_index)
+            }
+            Self::MethodType { descriptor_index } => {
+                store_u8(dst, 16)?;
+                store_u16(dst, *descriptor_index)
+            }
+            Self::Dynamic {
+                bootstrap_method_attr_index,
+                name_and_type_index,
+            } => {
+                store_u8(dst, 17)?;
+                store_u16(dst, *bootstrap_method_attr_index)?;
+                store_u16(dst, *name_and_type_index)
+            }
+            Self::InvokeDynamic {
+                bootstrap_method_attr_index,
+                name_and_type_index,
+            } => {
+                store_u8(dst, 18)?;
+                store_u16(dst, *bootstrap_method_attr_index)?;
+                store_u16(dst, *name_and_type_index)
+            }
+            Self::Module { name_index } => {
+                store_u8(dst, 19)?;
+                store_u16(dst, *name_index)
+            }
+            Self::Package { name_index } => {
+                store_u8(dst, 20)?;
+                store_u16(dst, *name_index)
+            }
+            // The slot after a `Long` or `Double` is not stored.
+            Self::Padding => Ok(()),
+            Self::Unknown => Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "unknown constant can't be written",
+            )),
         }
     }
 }
+/// Settings changing how class files are imported.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ImportOptions {
+    /// Keep `Utf8` constants which are valid modified UTF-8 but not valid Unicode(e.g. contain unpaired surrogates)
+    /// as raw bytes, instead of failing. They can be accessed as [`crate::Constant::RawUtf8`].
+    pub keep_invalid_utf8: bool,
+}
 pub(crate) fn load_class<R: std::io::Read>(
     src: &mut R,
 ) -> Result<JavaClassFile, JavaImportError> {
+    load_class_with(src, &ImportOptions::default())
+}
+pub(crate) fn load_class_with<R: std::io::Read>(
+    src: &mut R,
+    options: &ImportOptions,
+) -> Result<JavaClassFile, JavaImportError> {
+    let mut src = OffsetReader::new(src, 0);
+    read_class(&mut src, options).map_err(|err| err.at(src.offset()))
+}
+/// Checks the magic number and version of a class file, returning the major and minor version.
+pub(crate) fn read_header<R: std::io::Read>(src: &mut OffsetReader<R>) -> Result<(u16, u16), JavaImportError> {
     const CLASS_MAGIC: u32 = 0xCAFEBABE;
     let magic = load_u32(src)?;
     if magic != CLASS_MAGIC {
         //println!(
-        return Err(JavaImportError::NotJavaBytecode(magic));
+        return Err(src.error(ImportErrorKind::NotJavaBytecode(magic)));
     }
     let minor = load_u16(src)?;
     let major = load_u16(src)?;
     if !(40..=64).contains(&major) || minor != 0 {
-        return Err(JavaImportError::UnsuportedVersion(major, minor));
+        return Err(src.error(ImportErrorKind::UnsuportedVersion(major, minor)));
     }
+    Ok((major, minor))
+}
+fn read_class<R: std::io::Read>(
+    src: &mut OffsetReader<R>,
+    options: &ImportOptions,
+) -> Result<JavaClassFile, JavaImportError> {
+    let version = read_header(src)?;
     let constant_pool_count = load_u16(src)?;
     //println!("constant_pool_count:{constant_pool_count:?}");
     let mut const_items = Vec::with_capacity(constant_pool_count as usize);
-    let mut curr_item = 1;
-    while curr_item < constant_pool_count {
-        let ci = ConstantItem::read(src)?;
+    let mut curr_item: u32 = 1;
+    while curr_item < constant_pool_count as u32 {
+        let ci = ConstantItem::read(src, options)?;
         //println!("curr_item:{curr_item}\tci:{ci:?}");
         let ci_size = ci.size();
-        curr_item += ci_size;
+        curr_item += ci_size as u32;
         const_items.push(ci);
-        if ci_size == 2 {
+        // A malformed pool may end with a `Long` or `Double`, whose second slot is past the end.
+        if ci_size == 2 && curr_item <= constant_pool_count as u32 {
             const_items.push(ConstantItem::Padding);
         }
     }
@@ -344,11 +818,25 @@ pub(crate) fn load_class<R: std::io::Read>(
     let this_class = load_u16(src)?;
     //println!("this_class:{this_class}");
     if this_class < 1 || this_class > constant_pool_count {
-        return Err(JavaImportError::InvalidThisClass);
+        return Err(src.error(ImportErrorKind::InvalidThisClass));
     }
+    let class_name: Option<IString> = ConstantPool::new(&const_items)
+        .class_name(this_class)
+        .map(Into::into);
+    read_class_body(src, version, flags, this_class, const_items)
+        .map_err(|err| err.in_class(class_name.as_deref()))
+}
+/// Reads everything after `this_class`, so that errors can be tagged with the name of the class.
+fn read_class_body<R: std::io::Read>(
+    src: &mut OffsetReader<R>,
+    (major_version, minor_version): (u16, u16),
+    flags: AccessFlags,
+    this_class: u16,
+    const_items: Vec<ConstantItem>,
+) -> Result<JavaClassFile, JavaImportError> {
     let super_class = load_u16(src)?;
-    if super_class > constant_pool_count {
-        return Err(JavaImportError::InvalidSuperClass);
+    if super_class as usize > const_items.len() {
+        return Err(src.error(ImportErrorKind::InvalidSuperClass));
     }
     let interfaces_count = load_u16(src)?;
     let mut interfaces = Vec::with_capacity(interfaces_count as usize);
@@ -372,6 +860,8 @@ pub(crate) fn load_class<R: std::io::Read>(
     }
     //println!("const_items:{const_items:?}");
     Ok(JavaClassFile {
+        major_version,
+        minor_version,
         flags,
         attributes: attributes.into(),
         fields: fields.into(),
@@ -382,90 +872,99 @@ pub(crate) fn load_class<R: std::io::Read>(
         super_class,
     })
 }
-#[derive(Debug)]
-pub enum JavaImportError {
-    NotJavaBytecode(u32),
-    IoError(std::io::Error),
-    UnsuportedVersion(u16, u16),
-    ConstantImportError(ConstantImportError),
-    InvalidThisClass,
-    InvalidSuperClass,
-    ZipError(zip::result::ZipError),
-}
-impl From<std::io::Error> for JavaImportError {
-    fn from(err: std::io::Error) -> Self {
-        Self::IoError(err)
-    }
-}
-impl From<std::io::Error> for ConstantImportError {
-    fn from(err: std::io::Error) -> Self {
-        Self::IoError(err)
-    }
-}
-impl From<std::str::Utf8Error> for ConstantImportError {
-    fn from(err: std::str::Utf8Error) -> Self {
-        Self::Utf8Error(err)
-    }
+#[test]
+fn load_ident_class() {
+    let mut file = std::fs::File::open("test/Identity.class").unwrap();
+    let _class = load_class(&mut file).unwrap();
 }
-impl From<ConstantImportError> for JavaImportError {
-    fn from(err: ConstantImportError) -> Self {
-        match err {
-            ConstantImportError::IoError(err) => Self::IoError(err),
-            _ => Self::ConstantImportError(err),
+#[test]
+fn malformed_classes_dont_panic() {
+    for path in ["test/Identity.class", "test/Sieve.class", "test/ConstantPool.class"] {
+        let bytes = std::fs::read(path).unwrap();
+        for len in 0..bytes.len() {
+            assert!(load_class(&mut &bytes[..len]).is_err());
         }
-    }
-}
-impl From<zip::result::ZipError> for JavaImportError {
-    fn from(err: zip::result::ZipError) -> Self {
-        Self::ZipError(err)
-    }
-}
-pub(crate) fn load_jar(
-    src: &mut (impl std::io::Read + std::io::Seek),
-) -> Result<Vec<JavaClassFile>, JavaImportError> {
-    let mut zip = zip::ZipArchive::new(src)?;
-    let mut classes = Vec::new();
-    for i in 0..zip.len() {
-        use std::io::Read;
-        let mut file = zip.by_index(i)?;
-        let file_name = file.name().to_owned();
-        let mut tmp = Vec::new();
-        file.read_to_end(&mut tmp)?;
-        let mut file = std::io::Cursor::new(tmp);
-        let ext = file_name.split('.').last();
-        let ext = if let Some(ext) = ext { ext } else { continue }.to_owned();
-        if ext == "class" {
-            //println!("Filename: {}", file.name());
-            let loaded = load_class(&mut file);
-            match loaded {
-                Ok(class) => classes.push(class),
-                Err(err) => {
-                    use std::io::Seek;
-                    let dump_path = format!("target/testres/{}", file_name);
-
-                    std::fs::create_dir_all(dump_path.split('.').next().unwrap())?;
-                    println!("{dump_path}");
-                    let mut out = std::fs::File::create(dump_path).unwrap();
-                    file.rewind().unwrap();
-                    //let mut file = zip.by_index(i)?;
-                    std::io::copy(&mut file, &mut out).unwrap();
-                    println!("Error:\"{err:?}\" while loading {}.", file_name)
-                }
+        // Small xorshift generator, so that failures are reproducible.
+        let mut state: u64 = 0x9E37_79B9_7F4A_7C15;
+        let mut next = || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state
+        };
+        for _ in 0..2000 {
+            let mut mutated = bytes.clone();
+            for _ in 0..(next() % 4 + 1) {
+                let index = (next() % mutated.len() as u64) as usize;
+                mutated[index] = next() as u8;
             }
-        }
-        if ext == "jar" {
-            //println!("Filename: {}", file.name());
-            // TODO fix this stupidness, may need to write an issue to request ZipFile to implement Seek.
-            let mut tmp = Vec::new();
-            file.read_to_end(&mut tmp)?;
-            let mut reader = std::io::Cursor::new(tmp);
-            classes.extend(load_jar(&mut reader)?);
+            let _ = load_class(&mut &mutated[..]);
+            let _ = crate::ClassView::parse(&mutated);
         }
     }
-    Ok(classes)
 }
 #[test]
-fn load_ident_class() {
-    let mut file = std::fs::File::open("test/Identity.class").unwrap();
-    let _class = load_class(&mut file).unwrap();
+fn errors_have_context() {
+    let bytes = std::fs::read("test/Identity.class").unwrap();
+    let err = load_class(&mut &bytes[..100]).unwrap_err();
+    assert!(matches!(err.kind(), ImportErrorKind::UnexpectedEof));
+    assert_eq!((err.offset(), err.class_name()), (Some(100), None));
+    let len = bytes.len() - 100;
+    let err = load_class(&mut &bytes[..len]).unwrap_err();
+    assert_eq!(err.offset(), Some(len as u64));
+    assert_eq!(err.class_name(), Some("Identity"));
+    assert_eq!(err.member(), Some("main"));
+    assert_eq!(err.attribute(), Some("Code"));
+    assert_eq!(
+        err.to_string(),
+        format!("unexpected end of file at byte {len} in class Identity, member main, attribute Code")
+    );
+    let mut bad_magic = bytes.clone();
+    bad_magic[0] = 0;
+    let err = load_class(&mut &bad_magic[..]).unwrap_err();
+    assert!(matches!(err.kind(), ImportErrorKind::NotJavaBytecode(0x00FEBABE)));
+    assert_eq!(err.offset(), Some(4));
+}
+#[test]
+fn modified_utf8_constants() {
+    use crate::Constant;
+    let bytes = std::fs::read("test/Mutf8.class").unwrap();
+    let err = load_class(&mut &bytes[..]).unwrap_err();
+    assert!(matches!(err.kind(), ImportErrorKind::InvalidUtf8));
+    let options = ImportOptions {
+        keep_invalid_utf8: true,
+    };
+    let class = load_class_with(&mut &bytes[..], &options).unwrap();
+    let pool = class.constant_pool();
+    assert_eq!(pool.get(12), Some(Constant::String("a\0b")));
+    assert_eq!(pool.get(15), Some(Constant::String("\u{1F600}")));
+    assert_eq!(pool.get(19), Some(Constant::RawUtf8(b"\xED\xA0\x80")));
+    assert_eq!(pool.get(18), None);
+}
+#[test]
+fn write_round_trip() {
+    let options = ImportOptions {
+        keep_invalid_utf8: true,
+    };
+    for path in ["test/Identity.class", "test/Sieve.class", "test/ConstantPool.class", "test/Mutf8.class"] {
+        let bytes = std::fs::read(path).unwrap();
+        let class = load_class_with(&mut &bytes[..], &options).unwrap();
+        let mut written = Vec::new();
+        class.write(&mut written).unwrap();
+        assert!(written == bytes, "{path} changed after a round trip");
+    }
+    // The last constant is a `Long`, so `constant_pool_count` does not cover its second slot.
+    let mut bytes = vec![0xCA, 0xFE, 0xBA, 0xBE, 0, 0, 0, 52, 0, 4];
+    bytes.extend([7, 0, 2, 1, 0, 1, b'A', 5, 0, 0, 0, 0, 0, 0, 0, 0]);
+    bytes.extend([0, 0x21, 0, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+    let class = load_class(&mut &bytes[..]).unwrap();
+    assert_eq!(class.constant_pool().count(), 4);
+    let mut written = Vec::new();
+    class.write(&mut written).unwrap();
+    assert_eq!(written, bytes);
+    // Counts which don't fit in the class file are not truncated.
+    let mut class = class;
+    class.interfaces = vec![1; 65536].into();
+    let err = class.write(&mut Vec::new()).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
 }
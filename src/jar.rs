@@ -0,0 +1,314 @@
+use crate::error::{ImportErrorKind, JavaImportError};
+use crate::import::{load_class_with, ImportOptions, JavaClassFile};
+use crate::IString;
+/// Classes loaded from a jar, in the order in which they are stored in the archive.
+///
+/// Each entry keeps its own result, so one malformed class does not prevent the rest of the jar from loading.
+#[derive(Debug)]
+pub struct JarFile {
+    entries: Box<[JarEntry]>,
+}
+/// A `.class` file stored in a jar.
+#[derive(Debug)]
+pub struct JarEntry {
+    path: IString,
+    class: Result<JavaClassFile, JavaImportError>,
+}
+impl JarEntry {
+    /// Path of the entry within the archive, e.g. `java/lang/String.class`. Entries of nested jars are prefixed
+    /// with the path of the jar containing them, e.g. `lib/inner.jar!/Foo.class`.
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+    pub fn class(&self) -> Result<&JavaClassFile, &JavaImportError> {
+        self.class.as_ref()
+    }
+    pub fn into_class(self) -> Result<JavaClassFile, JavaImportError> {
+        self.class
+    }
+}
+impl JarFile {
+    pub fn entries(&self) -> &[JarEntry] {
+        &self.entries
+    }
+    pub fn into_entries(self) -> Box<[JarEntry]> {
+        self.entries
+    }
+    /// Classes which were loaded successfully.
+    pub fn classes(&self) -> impl Iterator<Item = &JavaClassFile> + '_ {
+        self.entries.iter().filter_map(|entry| entry.class.as_ref().ok())
+    }
+    /// Entries which failed to load, together with their errors.
+    pub fn errors(&self) -> impl Iterator<Item = (&str, &JavaImportError)> + '_ {
+        self.entries
+            .iter()
+            .filter_map(|entry| Some((entry.path(), entry.class.as_ref().err()?)))
+    }
+}
+/// Jars nested deeper than this are not opened, so a malicious archive can't recurse without end.
+const MAX_JAR_DEPTH: u8 = 8;
+/// Entries larger than this when decompressed are not loaded, so a highly compressed entry can't exhaust memory.
+const MAX_ENTRY_SIZE: u64 = 128 << 20;
+/// Loads all classes of a jar, including the ones in jars nested inside of it. A jar nested inside of more than 8
+/// others, or an entry larger than 128 MiB when decompressed, is not loaded, and is returned as an entry with an
+/// error instead.
+///
+/// Only fails if `src` is not a valid zip archive. Never touches the file system. With the `rayon` feature enabled,
+/// entries are decompressed and parsed in parallel, but they are still returned in archive order.
+pub fn load_jar<R: std::io::Read>(src: R) -> Result<JarFile, JavaImportError> {
+    load_jar_with(src, &ImportOptions::default())
+}
+/// Like [`load_jar`], but imports the classes with `options`.
+pub fn load_jar_with<R: std::io::Read>(mut src: R, options: &ImportOptions) -> Result<JarFile, JavaImportError> {
+    let mut bytes = Vec::new();
+    src.read_to_end(&mut bytes)?;
+    let entries = load_entries(&bytes, "", 0, options)?;
+    Ok(JarFile {
+        entries: entries.into(),
+    })
+}
+type ZipSlice<'a> = zip::ZipArchive<std::io::Cursor<&'a [u8]>>;
+fn load_entries(bytes: &[u8], prefix: &str, depth: u8, options: &ImportOptions) -> Result<Vec<JarEntry>, JavaImportError> {
+    let zip = zip::ZipArchive::new(std::io::Cursor::new(bytes))?;
+    let len = zip.len();
+    // Clones of an archive share its central directory, so each thread can cheaply get its own reader.
+    #[cfg(feature = "rayon")]
+    let entries: Vec<_> = {
+        use rayon::prelude::*;
+        (0..len)
+            .into_par_iter()
+            .map_init(|| zip.clone(), |zip, i| load_entry(zip, i, prefix, depth, options))
+            .collect()
+    };
+    #[cfg(not(feature = "rayon"))]
+    let entries: Vec<_> = {
+        let mut zip = zip;
+        (0..len)
+            .map(|i| load_entry(&mut zip, i, prefix, depth, options))
+            .collect()
+    };
+    Ok(entries.into_iter().flatten().collect())
+}
+/// Entry of a jar which could not be loaded.
+fn error_entry(path: String, err: impl Into<JavaImportError>) -> Vec<JarEntry> {
+    vec![JarEntry {
+        path: path.into(),
+        class: Err(err.into()),
+    }]
+}
+/// Loads the `i`th entry of `zip`, which is nested inside of `depth` other jars. Returns nothing if it is neither a
+/// class nor a jar, and many entries for nested jars.
+fn load_entry(zip: &mut ZipSlice, i: usize, prefix: &str, depth: u8, options: &ImportOptions) -> Vec<JarEntry> {
+    use std::io::Read;
+    // The name is taken from the central directory, so it is known even if the entry can't be decompressed.
+    let path = match zip.by_index_raw(i) {
+        Ok(file) => format!("{prefix}{}", file.name()),
+        Err(err) => return error_entry(format!("{prefix}#{i}"), err),
+    };
+    let is_class = path.ends_with(".class");
+    let is_jar = path.ends_with(".jar");
+    if !is_class && !is_jar {
+        return Vec::new();
+    }
+    if is_jar && depth >= MAX_JAR_DEPTH {
+        return error_entry(path, ImportErrorKind::JarTooDeeplyNested);
+    }
+    let file = match zip.by_index(i) {
+        Ok(file) => file,
+        Err(err) => return error_entry(path, err),
+    };
+    if file.size() > MAX_ENTRY_SIZE {
+        return error_entry(path, ImportErrorKind::EntryTooLarge(MAX_ENTRY_SIZE));
+    }
+    // The size in the archive may be wrong, so the limit is also checked while decompressing.
+    let mut bytes = Vec::new();
+    if let Err(err) = file.take(MAX_ENTRY_SIZE + 1).read_to_end(&mut bytes) {
+        return error_entry(path, err);
+    }
+    if bytes.len() as u64 > MAX_ENTRY_SIZE {
+        return error_entry(path, ImportErrorKind::EntryTooLarge(MAX_ENTRY_SIZE));
+    }
+    if is_class {
+        let class = load_class_with(&mut &bytes[..], options);
+        return vec![JarEntry {
+            path: path.into(),
+            class,
+        }];
+    }
+    load_entries(&bytes, &format!("{path}!/"), depth + 1, options).unwrap_or_else(|err| error_entry(path, err))
+}
+/// Jar which is only indexed when opened. Classes are decompressed and parsed on request.
+///
+/// Unlike [`load_jar`], jars nested inside of the archive are not looked into.
+pub struct JarArchive<R> {
+    zip: zip::ZipArchive<R>,
+    /// Internal names of the classes in the zip.
+    classes: std::collections::BTreeSet<IString>,
+    options: ImportOptions,
+}
+impl<R: std::io::Read + std::io::Seek> JarArchive<R> {
+    /// Reads the central directory of the archive and lists its classes.
+    pub fn new(src: R) -> Result<Self, JavaImportError> {
+        Self::with_options(src, &ImportOptions::default())
+    }
+    /// Like [`JarArchive::new`], but classes are imported with `options`.
+    pub fn with_options(src: R, options: &ImportOptions) -> Result<Self, JavaImportError> {
+        let zip = zip::ZipArchive::new(src)?;
+        let classes = zip
+            .file_names()
+            .filter_map(|path| {
+                let name = path.strip_suffix(".class")?;
+                // Classes for other java versions in multi-release jars.
+                (!name.starts_with("META-INF/")).then(|| name.into())
+            })
+            .collect();
+        Ok(Self {
+            zip,
+            classes,
+            options: *options,
+        })
+    }
+    /// Internal names of all classes in this jar, e.g. `java/lang/String`, in sorted order.
+    pub fn class_names(&self) -> impl Iterator<Item = &str> + '_ {
+        self.classes.iter().map(|name| &name[..])
+    }
+    pub fn contains(&self, name: &str) -> bool {
+        self.classes.contains(name)
+    }
+    pub fn len(&self) -> usize {
+        self.classes.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.classes.is_empty()
+    }
+    /// Decompresses and parses the class with the internal name `name`. Returns `None` if there is no such class.
+    pub fn class(&mut self, name: &str) -> Option<Result<JavaClassFile, JavaImportError>> {
+        if !self.classes.contains(name) {
+            return None;
+        }
+        Some(self.load(name))
+    }
+    fn load(&mut self, name: &str) -> Result<JavaClassFile, JavaImportError> {
+        use std::io::Read;
+        let file = self.zip.by_name(&format!("{name}.class"))?;
+        let mut file = std::io::BufReader::new(file.take(MAX_ENTRY_SIZE));
+        load_class_with(&mut file, &self.options)
+    }
+}
+#[cfg(test)]
+fn write_jar(files: &[(&str, &[u8])]) -> Vec<u8> {
+    use std::io::Write;
+    let mut zip = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+    for (name, bytes) in files {
+        zip.start_file(*name, Default::default()).unwrap();
+        zip.write_all(bytes).unwrap();
+    }
+    zip.finish().unwrap().into_inner()
+}
+#[test]
+fn load_jar_entries() {
+    let identity = std::fs::read("test/Identity.class").unwrap();
+    let sieve = std::fs::read("test/Sieve.class").unwrap();
+    let inner = write_jar(&[("Sieve.class", &sieve)]);
+    let jar = write_jar(&[
+        ("META-INF/MANIFEST.MF", b"Manifest-Version: 1.0\n"),
+        ("Identity.class", &identity),
+        ("broken/Broken.class", &identity[..100]),
+        ("lib/inner.jar", &inner),
+        ("lib/notazip.jar", b"not a zip"),
+    ]);
+    let jar = load_jar(std::io::Cursor::new(jar)).unwrap();
+    let paths: Vec<_> = jar.entries().iter().map(JarEntry::path).collect();
+    assert_eq!(
+        paths,
+        [
+            "Identity.class",
+            "broken/Broken.class",
+            "lib/inner.jar!/Sieve.class",
+            "lib/notazip.jar"
+        ]
+    );
+    let names: Vec<_> = jar
+        .classes()
+        .map(|class| class.constant_pool().class_name(class.this_class().0).unwrap())
+        .collect();
+    assert_eq!(names, ["Identity", "Sieve"]);
+    let errors: Vec<_> = jar.errors().map(|(path, _)| path).collect();
+    assert_eq!(errors, ["broken/Broken.class", "lib/notazip.jar"]);
+    assert!(load_jar(std::io::Cursor::new(b"not a zip")).is_err());
+}
+#[test]
+fn load_jar_reports_unreadable_entries() {
+    let identity = std::fs::read("test/Identity.class").unwrap();
+    let options = zip::write::FileOptions::default().compression_method(zip::CompressionMethod::Stored);
+    let mut zip = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+    for name in ["Odd.class", "Identity.class"] {
+        zip.start_file(name, options).unwrap();
+        std::io::Write::write_all(&mut zip, &identity).unwrap();
+    }
+    let mut jar = zip.finish().unwrap().into_inner();
+    // Sets an unknown compression method for `Odd.class`, in its local and central directory headers.
+    let central = jar.windows(4).position(|window| window == b"PK\x01\x02").unwrap();
+    jar[8] = 42;
+    jar[central + 10] = 42;
+    let jar = load_jar(&jar[..]).unwrap();
+    let paths: Vec<_> = jar.entries().iter().map(JarEntry::path).collect();
+    assert_eq!(paths, ["Odd.class", "Identity.class"]);
+    assert!(matches!(jar.entries()[0].class().unwrap_err().kind(), ImportErrorKind::ZipError(_)));
+    assert_eq!(jar.classes().count(), 1);
+}
+#[test]
+fn load_jar_limits_nesting() {
+    let sieve = std::fs::read("test/Sieve.class").unwrap();
+    // 10 jars inside of each other, with a class in the innermost one.
+    let jar = (0..10).fold(write_jar(&[("Sieve.class", &sieve)]), |inner, _| write_jar(&[("in.jar", &inner)]));
+    let jar = load_jar(&jar[..]).unwrap();
+    let [entry] = jar.entries() else {
+        panic!("expected a single entry");
+    };
+    assert_eq!(entry.path(), format!("{}in.jar", "in.jar!/".repeat(8)));
+    assert!(matches!(entry.class().unwrap_err().kind(), ImportErrorKind::JarTooDeeplyNested));
+}
+#[test]
+fn lazy_jar_archive() {
+    let identity = std::fs::read("test/Identity.class").unwrap();
+    let sieve = std::fs::read("test/Sieve.class").unwrap();
+    let jar = write_jar(&[
+        ("META-INF/MANIFEST.MF", b"Manifest-Version: 1.0\n"),
+        ("META-INF/versions/17/Sieve.class", &sieve),
+        ("Sieve.class", &sieve),
+        ("pkg/Identity.class", &identity),
+        ("pkg/Broken.class", &identity[..100]),
+    ]);
+    let mut archive = JarArchive::new(std::io::Cursor::new(jar)).unwrap();
+    let names: Vec<_> = archive.class_names().collect();
+    assert_eq!(names, ["Sieve", "pkg/Broken", "pkg/Identity"]);
+    assert!(archive.contains("pkg/Identity") && !archive.contains("Identity"));
+    let class = archive.class("pkg/Identity").unwrap().unwrap();
+    assert_eq!(class.methods().len(), 7);
+    assert!(archive.class("pkg/Broken").unwrap().is_err());
+    assert!(archive.class("java/lang/String").is_none());
+}
+#[test]
+fn jar_import_options() {
+    let mutf8 = std::fs::read("test/Mutf8.class").unwrap();
+    let jar = write_jar(&[("Mutf8.class", &mutf8)]);
+    let options = ImportOptions {
+        keep_invalid_utf8: true,
+    };
+    assert_eq!(load_jar(&jar[..]).unwrap().errors().count(), 1);
+    assert_eq!(load_jar_with(&jar[..], &options).unwrap().classes().count(), 1);
+    assert!(JarArchive::new(std::io::Cursor::new(&jar)).unwrap().class("Mutf8").unwrap().is_err());
+    let mut archive = JarArchive::with_options(std::io::Cursor::new(&jar), &options).unwrap();
+    assert!(archive.class("Mutf8").unwrap().is_ok());
+}
+#[test]
+fn load_jar_keeps_order() {
+    let identity = std::fs::read("test/Identity.class").unwrap();
+    let paths: Vec<_> = (0..64).map(|i| format!("pkg/C{i}.class")).collect();
+    let files: Vec<_> = paths.iter().map(|path| (&path[..], &identity[..])).collect();
+    let jar = load_jar(&write_jar(&files)[..]).unwrap();
+    let loaded: Vec<_> = jar.entries().iter().map(JarEntry::path).collect();
+    assert_eq!(loaded, paths);
+    assert_eq!(jar.classes().count(), 64);
+}
@@ -3,9 +3,44 @@ mod opcodes;
 mod attribute;
 mod import;
 mod field;
+mod method;
+mod constant_pool;
+mod descriptor;
+mod signature;
+mod error;
+mod jar;
+mod view;
+mod mutf8;
+mod builder;
+mod stack_map;
+mod annotation;
+mod nesting;
+mod record;
+mod sealed;
+mod module;
 type IString = Box<str>;
-pub use crate::import::{JavaClassFile,JavaImportError};
-pub use crate::import::{Utf8,ClassInfo};
-fn import_class_file<R:std::io::Read>(mut r:R)->Result<JavaClassFile,JavaImportError>{
+pub use crate::import::{ImportOptions,JavaClassFile};
+pub use crate::error::{ImportErrorKind,JavaImportError};
+pub use crate::import::{Utf8,ClassInfo,ModuleInfo,PackageInfo,AccessFlags};
+pub use crate::jar::{load_jar,load_jar_with,JarArchive,JarEntry,JarFile};
+pub use crate::view::{AttributeView,ClassView,MemberView};
+pub use crate::builder::{BuildError,ClassBuilder,CodeBuilder,ConstantPoolBuilder,Label,MethodBuilder};
+pub use crate::field::Field;
+pub use crate::method::Method;
+pub use crate::record::RecordComponent;
+pub use crate::module::{Module,ModulePackage,ModuleProvides,ModuleRequires};
+pub use crate::attribute::{Code,CodeException,InnerClass,Instructions,LocalVariable,LocalVariableInfo,MethodParameter,RawInstruction};
+pub use crate::nesting::{Nesting,NestingKind};
+pub use crate::sealed::{check_sealed_hierarchy,SealedHierarchyError};
+pub use crate::opcodes::{LookupSwitch,OpCode,StackEffect,TableSwitch};
+pub use crate::stack_map::{ClassHierarchy,StackMapFrame,VerificationType};
+pub use crate::annotation::{Annotation,AnnotationElement,ElementValue,LocalVariableTarget,TypeAnnotation,TypeAnnotationTarget,TypePathEntry};
+pub use crate::constant_pool::{Constant,ConstantPool,FieldRef,InvokeDynamic,MemberRef,MethodHandle,MethodRef,NameAndType,ReferenceKind};
+pub use crate::descriptor::{BaseType,DescriptorError,FieldType,MethodDescriptor};
+pub use crate::signature::{ClassSignature,ClassTypeSignature,JavaTypeSignature,MethodSignature,ReferenceTypeSignature,SimpleClassTypeSignature,TypeArgument,TypeParameter};
+pub fn import_class_file<R:std::io::Read>(mut r:R)->Result<JavaClassFile,JavaImportError>{
     crate::import::load_class(&mut r)
 }
+pub fn import_class_file_with<R:std::io::Read>(mut r:R,options:&ImportOptions)->Result<JavaClassFile,JavaImportError>{
+    crate::import::load_class_with(&mut r,options)
+}
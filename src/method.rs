@@ -0,0 +1,170 @@
+use crate::annotation::{annotations, type_annotations, Annotation, ElementValue, TypeAnnotation};
+use crate::attribute::{Attribute, Code, MethodParameter};
+use crate::constant_pool::ConstantPool;
+use crate::error::JavaImportError;
+use crate::import::{load_u16, store_u16, AccessFlags, ClassInfo, ConstantItem, OffsetReader, Utf8};
+/// A method of a class, as stored in the `methods` table of a class file.
+#[derive(Debug)]
+pub struct Method {
+    pub(crate) access_flags: AccessFlags,
+    pub(crate) name_index: u16,
+    pub(crate) descriptor_index: u16,
+    pub(crate) attributes: Box<[(u16, Attribute)]>,
+}
+impl Method {
+    pub(crate) fn read<R: std::io::Read>(
+        src: &mut OffsetReader<R>,
+        const_items: &[ConstantItem],
+    ) -> Result<Self, JavaImportError> {
+        let access_flags = AccessFlags::read(src)?;
+        let name_index = load_u16(src)?;
+        let descriptor_index = load_u16(src)?;
+        let name = ConstantPool::new(const_items).utf8(name_index);
+        let attributes_count = load_u16(src)?;
+        let mut attributes = Vec::with_capacity(attributes_count as usize);
+        for _ in 0..attributes_count {
+            attributes.push(Attribute::read(src, const_items).map_err(|err| err.in_member(name))?);
+        }
+        Ok(Self {
+            access_flags,
+            name_index,
+            descriptor_index,
+            attributes: attributes.into(),
+        })
+    }
+    pub(crate) fn write<W: std::io::Write>(&self, dst: &mut W) -> std::io::Result<()> {
+        self.access_flags.write(dst)?;
+        store_u16(dst, self.name_index)?;
+        store_u16(dst, self.descriptor_index)?;
+        Attribute::write_all(&self.attributes, dst)
+    }
+    pub fn access_flags(&self) -> AccessFlags {
+        self.access_flags
+    }
+    /// Name of this method, e.g. `<init>` or `main`.
+    pub fn name(&self) -> Utf8 {
+        Utf8(self.name_index)
+    }
+    /// Erased descriptor of this method, e.g. `([Ljava/lang/String;)V`.
+    pub fn descriptor(&self) -> Utf8 {
+        Utf8(self.descriptor_index)
+    }
+    /// Bytecode of this method. `None` for `abstract` and `native` methods.
+    pub fn code(&self) -> Option<&Code> {
+        self.attributes.iter().find_map(|(_, attribute)| match attribute {
+            Attribute::Code(code) => Some(code),
+            _ => None,
+        })
+    }
+    /// Checked exceptions this method declares in its `throws` clause.
+    pub fn exceptions(&self) -> impl Iterator<Item = ClassInfo> + '_ {
+        self.attributes
+            .iter()
+            .filter_map(|(_, attribute)| match attribute {
+                Attribute::Exceptions { exceptions } => Some(exceptions.iter()),
+                _ => None,
+            })
+            .flatten()
+            .map(|exception| ClassInfo(*exception))
+    }
+    /// Generic signature of this method, if it has one.
+    pub fn signature(&self) -> Option<Utf8> {
+        self.attributes.iter().find_map(|(_, attribute)| match attribute {
+            Attribute::Signature { signature } => Some(Utf8(*signature)),
+            _ => None,
+        })
+    }
+    /// Formal parameters of this method. Only present if the class was compiled with `-parameters`.
+    pub fn parameters(&self) -> Option<&[MethodParameter]> {
+        self.attributes.iter().find_map(|(_, attribute)| match attribute {
+            Attribute::MethodParameters { parameters } => Some(&parameters[..]),
+            _ => None,
+        })
+    }
+    /// Annotations with `RUNTIME` retention.
+    pub fn annotations(&self) -> &[Annotation] {
+        annotations(&self.attributes, true)
+    }
+    /// Annotations with `CLASS` retention, which are stored in the class file but not visible through reflection.
+    pub fn invisible_annotations(&self) -> &[Annotation] {
+        annotations(&self.attributes, false)
+    }
+    /// Type annotations with `RUNTIME` retention on types in the declaration of this method.
+    pub fn type_annotations(&self) -> &[TypeAnnotation] {
+        type_annotations(&self.attributes, true)
+    }
+    /// Type annotations with `CLASS` retention on types in the declaration of this method.
+    pub fn invisible_type_annotations(&self) -> &[TypeAnnotation] {
+        type_annotations(&self.attributes, false)
+    }
+    /// Annotations of each formal parameter with `RUNTIME` retention. The list may be shorter than the parameters in
+    /// the descriptor, since compilers may skip synthetic parameters(e.g. the outer instance of an inner class).
+    pub fn parameter_annotations(&self) -> &[Box<[Annotation]>] {
+        self.attributes
+            .iter()
+            .find_map(|(_, attribute)| match attribute {
+                Attribute::RuntimeVisibleParameterAnnotations { parameters } => Some(&parameters[..]),
+                _ => None,
+            })
+            .unwrap_or_default()
+    }
+    /// Annotations of each formal parameter with `CLASS` retention.
+    pub fn invisible_parameter_annotations(&self) -> &[Box<[Annotation]>] {
+        self.attributes
+            .iter()
+            .find_map(|(_, attribute)| match attribute {
+                Attribute::RuntimeInvisibleParameterAnnotations { parameters } => Some(&parameters[..]),
+                _ => None,
+            })
+            .unwrap_or_default()
+    }
+    /// Default value of an element of an annotation interface, e.g. `""` for `String name() default "";`.
+    pub fn annotation_default(&self) -> Option<&ElementValue> {
+        self.attributes.iter().find_map(|(_, attribute)| match attribute {
+            Attribute::AnnotationDefault { value } => Some(value),
+            _ => None,
+        })
+    }
+    pub fn is_deprecated(&self) -> bool {
+        self.attributes
+            .iter()
+            .any(|(_, attribute)| matches!(attribute, Attribute::Deprecated))
+    }
+}
+#[test]
+fn list_ident_methods() {
+    let mut file = std::fs::File::open("test/Identity.class").unwrap();
+    let class = crate::import_class_file(&mut file).unwrap();
+    let names: Vec<_> = class
+        .methods()
+        .iter()
+        .map(|method| class.get_utf8(method.name()).unwrap())
+        .collect();
+    assert_eq!(
+        names,
+        [
+            "<init>",
+            "identityInt",
+            "identityLong",
+            "identityFloat",
+            "identityDouble",
+            "identityObject",
+            "main"
+        ]
+    );
+    let main = &class.methods()[6];
+    assert_eq!(
+        class.get_utf8(main.descriptor()),
+        Some("([Ljava/lang/String;)V")
+    );
+    assert!(main.access_flags().is_public() && main.access_flags().is_static());
+    assert!(main.code().is_some_and(|code| code.max_locals() == 1));
+    let exceptions: Vec<_> = main
+        .exceptions()
+        .map(|exception| class.get_utf8(class.get_class_info(exception).unwrap()))
+        .collect();
+    assert_eq!(exceptions, [Some("java/lang/Exception")]);
+    let parameters = main.parameters().unwrap();
+    assert_eq!(class.get_utf8(parameters[0].name()), Some("args"));
+    assert!(main.signature().is_none());
+}
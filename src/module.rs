@@ -0,0 +1,249 @@
+use crate::error::JavaImportError;
+use crate::import::{load_u16, store_len_u16, store_u16, AccessFlags, ClassInfo, ModuleInfo, OffsetReader, PackageInfo, Utf8};
+/// Declaration of a module, as stored in the `Module` attribute of a `module-info.class`.
+#[derive(Debug)]
+pub struct Module {
+    pub(crate) name_index: u16,
+    pub(crate) flags: AccessFlags,
+    pub(crate) version_index: u16,
+    pub(crate) requires: Box<[ModuleRequires]>,
+    pub(crate) exports: Box<[ModulePackage]>,
+    pub(crate) opens: Box<[ModulePackage]>,
+    pub(crate) uses: Box<[u16]>,
+    pub(crate) provides: Box<[ModuleProvides]>,
+}
+/// Dependency of a module on another one, e.g. `requires transitive java.sql;`.
+#[derive(Debug)]
+pub struct ModuleRequires {
+    pub(crate) module_index: u16,
+    pub(crate) flags: AccessFlags,
+    pub(crate) version_index: u16,
+}
+/// Package a module exports or opens, e.g. `exports jtc.internal to java.sql;`.
+#[derive(Debug)]
+pub struct ModulePackage {
+    pub(crate) package_index: u16,
+    pub(crate) flags: AccessFlags,
+    pub(crate) targets: Box<[u16]>,
+}
+/// Implementations of a service a module provides, e.g. `provides ToolProvider with Tool;`.
+#[derive(Debug)]
+pub struct ModuleProvides {
+    pub(crate) service_index: u16,
+    pub(crate) implementations: Box<[u16]>,
+}
+fn load_indices<R: std::io::Read>(src: &mut OffsetReader<R>) -> Result<Box<[u16]>, JavaImportError> {
+    let count = load_u16(src)?;
+    (0..count).map(|_| load_u16(src)).collect::<Result<_, _>>().map_err(Into::into)
+}
+fn store_indices<W: std::io::Write>(dst: &mut W, indices: &[u16]) -> std::io::Result<()> {
+    store_len_u16(dst, indices.len())?;
+    indices.iter().try_for_each(|index| store_u16(dst, *index))
+}
+impl Module {
+    pub(crate) fn read<R: std::io::Read>(src: &mut OffsetReader<R>) -> Result<Self, JavaImportError> {
+        let name_index = load_u16(src)?;
+        let flags = AccessFlags::read(src)?;
+        let version_index = load_u16(src)?;
+        let requires_count = load_u16(src)?;
+        let mut requires = Vec::new();
+        for _ in 0..requires_count {
+            requires.push(ModuleRequires {
+                module_index: load_u16(src)?,
+                flags: AccessFlags::read(src)?,
+                version_index: load_u16(src)?,
+            });
+        }
+        let exports = ModulePackage::read_list(src)?;
+        let opens = ModulePackage::read_list(src)?;
+        let uses = load_indices(src)?;
+        let provides_count = load_u16(src)?;
+        let mut provides = Vec::new();
+        for _ in 0..provides_count {
+            provides.push(ModuleProvides {
+                service_index: load_u16(src)?,
+                implementations: load_indices(src)?,
+            });
+        }
+        Ok(Self {
+            name_index,
+            flags,
+            version_index,
+            requires: requires.into(),
+            exports,
+            opens,
+            uses,
+            provides: provides.into(),
+        })
+    }
+    pub(crate) fn write<W: std::io::Write>(&self, dst: &mut W) -> std::io::Result<()> {
+        store_u16(dst, self.name_index)?;
+        self.flags.write(dst)?;
+        store_u16(dst, self.version_index)?;
+        store_len_u16(dst, self.requires.len())?;
+        for requires in self.requires.iter() {
+            store_u16(dst, requires.module_index)?;
+            requires.flags.write(dst)?;
+            store_u16(dst, requires.version_index)?;
+        }
+        ModulePackage::write_list(&self.exports, dst)?;
+        ModulePackage::write_list(&self.opens, dst)?;
+        store_indices(dst, &self.uses)?;
+        store_len_u16(dst, self.provides.len())?;
+        for provides in self.provides.iter() {
+            store_u16(dst, provides.service_index)?;
+            store_indices(dst, &provides.implementations)?;
+        }
+        Ok(())
+    }
+    pub fn name(&self) -> ModuleInfo {
+        ModuleInfo(self.name_index)
+    }
+    pub fn access_flags(&self) -> AccessFlags {
+        self.flags
+    }
+    /// Checks if this is an `open` module, whose packages are all open to deep reflection.
+    pub fn is_open(&self) -> bool {
+        self.flags.bits() & 0x0020 != 0
+    }
+    /// Version of this module, e.g. `1.2`. Index 0 if it has no version.
+    pub fn version(&self) -> Utf8 {
+        Utf8(self.version_index)
+    }
+    pub fn requires(&self) -> &[ModuleRequires] {
+        &self.requires
+    }
+    pub fn exports(&self) -> &[ModulePackage] {
+        &self.exports
+    }
+    pub fn opens(&self) -> &[ModulePackage] {
+        &self.opens
+    }
+    /// Service interfaces this module looks up with `ServiceLoader`.
+    pub fn uses(&self) -> impl Iterator<Item = ClassInfo> + '_ {
+        self.uses.iter().map(|service| ClassInfo(*service))
+    }
+    pub fn provides(&self) -> &[ModuleProvides] {
+        &self.provides
+    }
+}
+impl ModuleRequires {
+    pub fn module(&self) -> ModuleInfo {
+        ModuleInfo(self.module_index)
+    }
+    pub fn access_flags(&self) -> AccessFlags {
+        self.flags
+    }
+    /// Checks if modules requiring this one also read the required module(`requires transitive`).
+    pub fn is_transitive(&self) -> bool {
+        self.flags.bits() & 0x0020 != 0
+    }
+    /// Checks if the dependency is only needed at compile time(`requires static`).
+    pub fn is_static_phase(&self) -> bool {
+        self.flags.bits() & 0x0040 != 0
+    }
+    /// Checks if the dependency was added implicitly, e.g. on `java.base`.
+    pub fn is_mandated(&self) -> bool {
+        self.flags.bits() & 0x8000 != 0
+    }
+    /// Version of the required module at compile time. Index 0 if it was not recorded.
+    pub fn version(&self) -> Utf8 {
+        Utf8(self.version_index)
+    }
+}
+impl ModulePackage {
+    fn read_list<R: std::io::Read>(src: &mut OffsetReader<R>) -> Result<Box<[Self]>, JavaImportError> {
+        let count = load_u16(src)?;
+        let mut packages = Vec::new();
+        for _ in 0..count {
+            packages.push(Self {
+                package_index: load_u16(src)?,
+                flags: AccessFlags::read(src)?,
+                targets: load_indices(src)?,
+            });
+        }
+        Ok(packages.into())
+    }
+    fn write_list<W: std::io::Write>(list: &[Self], dst: &mut W) -> std::io::Result<()> {
+        store_len_u16(dst, list.len())?;
+        for package in list {
+            store_u16(dst, package.package_index)?;
+            package.flags.write(dst)?;
+            store_indices(dst, &package.targets)?;
+        }
+        Ok(())
+    }
+    pub fn package(&self) -> PackageInfo {
+        PackageInfo(self.package_index)
+    }
+    pub fn access_flags(&self) -> AccessFlags {
+        self.flags
+    }
+    /// Modules the package is exported or opened to. Empty if it is exported or opened to all modules.
+    pub fn targets(&self) -> impl Iterator<Item = ModuleInfo> + '_ {
+        self.targets.iter().map(|target| ModuleInfo(*target))
+    }
+}
+impl ModuleProvides {
+    /// Service interface, or class, which is provided.
+    pub fn service(&self) -> ClassInfo {
+        ClassInfo(self.service_index)
+    }
+    pub fn implementations(&self) -> impl Iterator<Item = ClassInfo> + '_ {
+        self.implementations.iter().map(|implementation| ClassInfo(*implementation))
+    }
+}
+#[test]
+fn module_info() {
+    let mut file = std::fs::File::open("test/module/module-info.class").unwrap();
+    let class = crate::import_class_file(&mut file).unwrap();
+    let module_name = |module| class.get_utf8(class.get_module_info(module).unwrap()).unwrap();
+    let package_name = |package| class.get_utf8(class.get_package_info(package).unwrap()).unwrap();
+    let class_name = |class_info| class.get_utf8(class.get_class_info(class_info).unwrap()).unwrap();
+    let module = class.module().unwrap();
+    assert_eq!(module_name(module.name()), "jtc.test");
+    assert_eq!(class.get_utf8(module.version()), Some("1.2"));
+    assert!(!module.is_open());
+    let requires: Vec<_> = module
+        .requires()
+        .iter()
+        .map(|requires| {
+            let flags = (requires.is_mandated(), requires.is_transitive(), requires.is_static_phase());
+            (module_name(requires.module()), flags)
+        })
+        .collect();
+    assert_eq!(
+        requires,
+        [
+            ("java.base", (true, false, false)),
+            ("java.logging", (false, false, false)),
+            ("java.sql", (false, true, false)),
+            ("java.compiler", (false, false, true))
+        ]
+    );
+    assert!(class.get_utf8(module.requires()[0].version()).is_some());
+    let exports: Vec<_> = module
+        .exports()
+        .iter()
+        .map(|exports| (package_name(exports.package()), exports.targets().map(module_name).collect::<Vec<_>>()))
+        .collect();
+    assert_eq!(
+        exports,
+        [("jtc/api", vec![]), ("jtc/internal", vec!["java.logging", "java.sql"])]
+    );
+    let opens: Vec<_> = module.opens().iter().map(|opens| package_name(opens.package())).collect();
+    assert_eq!(opens, ["jtc/internal"]);
+    let uses: Vec<_> = module.uses().map(class_name).collect();
+    assert_eq!(uses, ["java/util/spi/ToolProvider"]);
+    let [provides] = module.provides() else {
+        panic!("expected a single provides directive");
+    };
+    assert_eq!(class_name(provides.service()), "java/util/spi/ToolProvider");
+    assert_eq!(provides.implementations().map(class_name).collect::<Vec<_>>(), ["jtc/api/Tool"]);
+    let packages: Vec<_> = class.module_packages().map(package_name).collect();
+    assert_eq!(packages, ["jtc/api", "jtc/internal"]);
+    assert_eq!(class.module_main_class().map(class_name), Some("jtc/api/Tool"));
+    let mut written = Vec::new();
+    class.write(&mut written).unwrap();
+    assert_eq!(written, std::fs::read("test/module/module-info.class").unwrap());
+}
@@ -0,0 +1,84 @@
+use std::borrow::Cow;
+/// Why bytes could not be decoded as modified UTF-8.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Mutf8Error {
+    /// Bytes are not valid modified UTF-8.
+    Malformed,
+    /// Bytes are valid modified UTF-8, but encode an unpaired surrogate, which can't be stored in a `str`.
+    LoneSurrogate,
+}
+/// Decodes the modified UTF-8 used by `Utf8` constants. It differs from UTF-8 in two ways: NUL is encoded as
+/// `0xC0 0x80`, and characters outside of the BMP are encoded as two 3 byte surrogates instead of 4 bytes.
+pub(crate) fn decode(bytes: &[u8]) -> Result<Cow<'_, str>, Mutf8Error> {
+    // Modified UTF-8 without NULs or supplementary characters is also valid UTF-8, so it can be borrowed.
+    if !bytes.iter().any(|&byte| byte == 0 || byte >= 0xF0) {
+        if let Ok(string) = std::str::from_utf8(bytes) {
+            return Ok(Cow::Borrowed(string));
+        }
+    }
+    let mut units = Vec::with_capacity(bytes.len());
+    let mut bytes = bytes.iter();
+    let continuation = |bytes: &mut std::slice::Iter<u8>| match bytes.next() {
+        Some(&byte) if byte & 0xC0 == 0x80 => Ok((byte & 0x3F) as u16),
+        _ => Err(Mutf8Error::Malformed),
+    };
+    while let Some(&byte) = bytes.next() {
+        let unit = match byte {
+            0x01..=0x7F => byte as u16,
+            // Overlong forms are rejected(besides NUL), so encoding the result gives back the same bytes.
+            0xC0..=0xDF => match ((byte & 0x1F) as u16) << 6 | continuation(&mut bytes)? {
+                unit @ (0 | 0x80..) => unit,
+                _ => return Err(Mutf8Error::Malformed),
+            },
+            0xE0..=0xEF => {
+                let high = ((byte & 0x0F) as u16) << 12 | continuation(&mut bytes)? << 6;
+                match high | continuation(&mut bytes)? {
+                    unit @ 0x800.. => unit,
+                    _ => return Err(Mutf8Error::Malformed),
+                }
+            }
+            _ => return Err(Mutf8Error::Malformed),
+        };
+        units.push(unit);
+    }
+    char::decode_utf16(units)
+        .collect::<Result<String, _>>()
+        .map(Cow::Owned)
+        .map_err(|_| Mutf8Error::LoneSurrogate)
+}
+/// Encodes `string` as modified UTF-8. Inverse of [`decode`].
+pub(crate) fn encode(string: &str) -> Cow<'_, [u8]> {
+    if !string.bytes().any(|byte| byte == 0 || byte >= 0xF0) {
+        return Cow::Borrowed(string.as_bytes());
+    }
+    let mut bytes = Vec::with_capacity(string.len() + 2);
+    for unit in string.encode_utf16() {
+        match unit {
+            0x01..=0x7F => bytes.push(unit as u8),
+            0 | 0x80..=0x7FF => bytes.extend([0xC0 | (unit >> 6) as u8, 0x80 | (unit & 0x3F) as u8]),
+            _ => bytes.extend([
+                0xE0 | (unit >> 12) as u8,
+                0x80 | (unit >> 6 & 0x3F) as u8,
+                0x80 | (unit & 0x3F) as u8,
+            ]),
+        }
+    }
+    Cow::Owned(bytes)
+}
+#[test]
+fn decode_modified_utf8() {
+    assert!(matches!(decode(b"java/lang/Object"), Ok(Cow::Borrowed("java/lang/Object"))));
+    assert!(matches!(decode("zażółć".as_bytes()), Ok(Cow::Borrowed("zażółć"))));
+    assert_eq!(decode(b"a\xC0\x80b").unwrap(), "a\0b");
+    assert_eq!(decode(b"\xED\xA0\xBD\xED\xB8\x80").unwrap(), "\u{1F600}");
+    assert_eq!(decode(b"\xED\xA0\x80"), Err(Mutf8Error::LoneSurrogate));
+    assert_eq!(decode(b"a\0b"), Err(Mutf8Error::Malformed));
+    assert_eq!(decode("\u{1F600}".as_bytes()), Err(Mutf8Error::Malformed));
+    assert_eq!(decode(b"\xE0\x80"), Err(Mutf8Error::Malformed));
+    assert_eq!(decode(b"\xC1\x81"), Err(Mutf8Error::Malformed));
+    assert_eq!(decode(b"\xE0\x81\x81"), Err(Mutf8Error::Malformed));
+    for string in ["java/lang/Object", "a\0b", "zażółć", "\u{1F600}"] {
+        assert_eq!(decode(&encode(string)).unwrap(), string);
+    }
+    assert_eq!(&encode("a\0\u{1F600}")[..], b"a\xC0\x80\xED\xA0\xBD\xED\xB8\x80");
+}
@@ -0,0 +1,158 @@
+use crate::attribute::{Attribute, InnerClass};
+use crate::constant_pool::{ConstantPool, NameAndType};
+use crate::import::{AccessFlags, JavaClassFile};
+/// Kind of a class, depending on where it was declared in the source.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NestingKind {
+    TopLevel,
+    /// Class declared in the body of another class, e.g. `Outer.Inner`.
+    Member,
+    /// Named class declared in a block of code.
+    Local,
+    Anonymous,
+}
+/// Where a class was declared, created by [`JavaClassFile::nesting`].
+///
+/// Combines the `InnerClasses`, `EnclosingMethod`, `NestHost` and `NestMembers` attributes of the class.
+#[derive(Debug, Clone, Copy)]
+pub struct Nesting<'a> {
+    class: &'a JavaClassFile,
+    /// Entry of the `InnerClasses` attribute describing the class itself.
+    entry: Option<&'a InnerClass>,
+}
+impl<'a> Nesting<'a> {
+    pub(crate) fn new(class: &'a JavaClassFile) -> Self {
+        let entry = Self::entry(class, class.constant_pool().class_name(class.this_class));
+        Self { class, entry }
+    }
+    /// Entry of the `InnerClasses` attribute of `class` describing the class named `name`.
+    fn entry(class: &'a JavaClassFile, name: Option<&str>) -> Option<&'a InnerClass> {
+        let pool = class.constant_pool();
+        name.and_then(|name| {
+            class
+                .inner_classes()
+                .iter()
+                .find(|entry| pool.class_name(entry.inner_class_info_index) == Some(name))
+        })
+    }
+    fn pool(&self) -> ConstantPool<'a> {
+        self.class.constant_pool()
+    }
+    pub fn kind(&self) -> NestingKind {
+        match self.entry {
+            None => NestingKind::TopLevel,
+            Some(entry) if entry.outer_class_info_index != 0 => NestingKind::Member,
+            Some(entry) if entry.inner_name_index == 0 => NestingKind::Anonymous,
+            Some(_) => NestingKind::Local,
+        }
+    }
+    /// Flags of the class as declared in the source. Unlike the flags of the class file, they include `private`,
+    /// `protected` and `static` for member classes.
+    pub fn access_flags(&self) -> AccessFlags {
+        self.entry.map_or(self.class.flags, |entry| entry.inner_class_access_flags)
+    }
+    /// Name of the class as written in the source, e.g. `Inner`, or `None` for anonymous classes.
+    pub fn simple_name(&self) -> Option<&'a str> {
+        match self.entry {
+            Some(entry) => self.pool().utf8(entry.inner_name_index),
+            None => {
+                let name = self.pool().class_name(self.class.this_class)?;
+                Some(name.rsplit('/').next().unwrap_or(name))
+            }
+        }
+    }
+    /// Internal name of the class this one is a member of.
+    pub fn declaring_class(&self) -> Option<&'a str> {
+        self.pool().class_name(self.entry?.outer_class_info_index)
+    }
+    /// Internal name of the innermost class whose body contains this one, for member, local and anonymous classes.
+    pub fn enclosing_class(&self) -> Option<&'a str> {
+        self.declaring_class().or_else(|| {
+            self.class.attributes.iter().find_map(|(_, attribute)| match attribute {
+                Attribute::EnclosingMethod { class_index, .. } => self.pool().class_name(*class_index),
+                _ => None,
+            })
+        })
+    }
+    /// Method whose code contains this local or anonymous class. `None` if the class is declared in an
+    /// initializer, or is not local.
+    pub fn enclosing_method(&self) -> Option<NameAndType<'a>> {
+        self.class.attributes.iter().find_map(|(_, attribute)| match attribute {
+            Attribute::EnclosingMethod { method_index, .. } => self.pool().name_and_type(*method_index),
+            _ => None,
+        })
+    }
+    /// Name used to refer to the class in the source, e.g. `java.util.Map.Entry` for `java/util/Map$Entry`.
+    /// `None` for local and anonymous classes, and for the classes nested in them.
+    pub fn source_name(&self) -> Option<String> {
+        let pool = self.pool();
+        let mut name = pool.class_name(self.class.this_class)?;
+        let mut simple_names: Vec<&str> = Vec::new();
+        // A malformed file may contain a cycle of outer classes, and no chain can be longer than the table.
+        for _ in 0..=self.class.inner_classes().len() {
+            let Some(entry) = Self::entry(self.class, Some(name)) else {
+                let mut source_name = name.replace('/', ".");
+                for simple_name in simple_names.iter().rev() {
+                    source_name.push('.');
+                    source_name.push_str(simple_name);
+                }
+                return Some(source_name);
+            };
+            if entry.outer_class_info_index == 0 {
+                return None;
+            }
+            simple_names.push(pool.utf8(entry.inner_name_index)?);
+            name = pool.class_name(entry.outer_class_info_index)?;
+        }
+        None
+    }
+    /// Internal name of the host of the nest this class belongs to. `None` if the class is the host itself.
+    pub fn nest_host(&self) -> Option<&'a str> {
+        self.class.attributes.iter().find_map(|(_, attribute)| match attribute {
+            Attribute::NestHost { host_class_index } => self.pool().class_name(*host_class_index),
+            _ => None,
+        })
+    }
+    /// Internal names of the other members of the nest, if this class is its host.
+    pub fn nest_members(&self) -> impl Iterator<Item = &'a str> + 'a {
+        let pool = self.pool();
+        self.class
+            .attributes
+            .iter()
+            .filter_map(|(_, attribute)| match attribute {
+                Attribute::NestMembers { classes } => Some(classes.iter()),
+                _ => None,
+            })
+            .flatten()
+            .filter_map(move |class| pool.class_name(*class))
+    }
+}
+#[test]
+fn nesting_kinds() {
+    let load = |path: &str| crate::import_class_file(std::fs::File::open(path).unwrap()).unwrap();
+    let outer = load("test/Nesting.class");
+    let nesting = outer.nesting();
+    assert_eq!(nesting.kind(), NestingKind::TopLevel);
+    assert_eq!((nesting.simple_name(), nesting.source_name().as_deref()), (Some("Nesting"), Some("Nesting")));
+    assert_eq!(nesting.nest_host(), None);
+    let mut members: Vec<_> = nesting.nest_members().collect();
+    members.sort();
+    assert_eq!(members, ["Nesting$1", "Nesting$1Local", "Nesting$Inner", "Nesting$Inner$Deepest"]);
+    let deepest = load("test/Nesting$Inner$Deepest.class");
+    let nesting = deepest.nesting();
+    assert_eq!(nesting.kind(), NestingKind::Member);
+    assert_eq!(nesting.source_name().as_deref(), Some("Nesting.Inner.Deepest"));
+    assert_eq!((nesting.declaring_class(), nesting.nest_host()), (Some("Nesting$Inner"), Some("Nesting")));
+    // `static` is only stored in the `InnerClasses` entry.
+    assert!(nesting.access_flags().is_static() && !deepest.flags.is_static());
+    let local = load("test/Nesting$1Local.class");
+    let nesting = local.nesting();
+    assert_eq!((nesting.kind(), nesting.simple_name()), (NestingKind::Local, Some("Local")));
+    assert_eq!(nesting.source_name(), None);
+    assert_eq!(nesting.enclosing_class(), Some("Nesting"));
+    assert_eq!(nesting.enclosing_method().map(|method| method.name), Some("make"));
+    let anonymous = load("test/Nesting$1.class");
+    let nesting = anonymous.nesting();
+    assert_eq!((nesting.kind(), nesting.simple_name()), (NestingKind::Anonymous, None));
+    assert_eq!(nesting.enclosing_class(), Some("Nesting"));
+}
@@ -1,168 +1,960 @@
-use crate::import::{load_i16, load_i32, load_i8, load_u16, load_u8};
+use crate::error::{ImportErrorKind, JavaImportError};
+use crate::import::{
+    load_i16, load_i32, load_i8, load_u16, load_u8, store_i16, store_i32, store_i8, store_u16, store_u8, OffsetReader,
+};
+use smallvec::SmallVec;
+/// A single, decoded JVM instruction.
+///
+/// Some instructions are normalized while decoding: `iconst_<n>`, `iload_<n>` and similar short forms are
+/// represented by the same variant as their long form, and `ldc`, `ldc_w` and `ldc2_w` all become [`OpCode::LoadConst`].
+/// Instructions prefixed by `wide` and the `_w` forms of `goto` and `jsr` are also decoded into their normal variants.
+/// Branch offsets are relative to the offset of the branching instruction, as stored in the class file.
+/// Indices of type `u16` (besides local variable indices) point into the constant pool.
 #[derive(Debug, Clone)]
-pub(crate) enum OpCode {
+pub enum OpCode {
+    /// `nop`
     Nop,
-    ALoad(u8),
-    FLoad(u8),
-    DLoad(u8),
-    ILoad(u8),
-    LLoad(u8),
+    /// `aload`, `aload_<n>`: push a reference from a local variable.
+    ALoad(u16),
+    /// `fload`, `fload_<n>`: push a float from a local variable.
+    FLoad(u16),
+    /// `dload`, `dload_<n>`: push a double from a local variable.
+    DLoad(u16),
+    /// `iload`, `iload_<n>`: push an int from a local variable.
+    ILoad(u16),
+    /// `lload`, `lload_<n>`: push a long from a local variable.
+    LLoad(u16),
+    /// `dconst_<d>`
     DConst(f64),
+    /// `fconst_<f>`
     FConst(f32),
+    /// `iconst_<i>`, `iconst_m1`
     IConst(i32),
+    /// `lconst_<l>`
     LConst(i64),
+    /// `aconst_null`
     AConstNull,
-    AStore(u8),
-    DStore(u8),
-    FStore(u8),
-    IStore(u8),
-    LStore(u8),
+    /// `astore`, `astore_<n>`: pop a reference into a local variable.
+    AStore(u16),
+    /// `dstore`, `dstore_<n>`: pop a double into a local variable.
+    DStore(u16),
+    /// `fstore`, `fstore_<n>`: pop a float into a local variable.
+    FStore(u16),
+    /// `istore`, `istore_<n>`: pop an int into a local variable.
+    IStore(u16),
+    /// `lstore`, `lstore_<n>`: pop a long into a local variable.
+    LStore(u16),
+    /// `ladd`
     LAdd,
+    /// `iadd`
     IAdd,
+    /// `dadd`
     DAdd,
+    /// `fadd`
     FAdd,
+    /// `lsub`
     LSub,
+    /// `isub`
     ISub,
+    /// `dsub`
     DSub,
+    /// `fsub`
     FSub,
+    /// `lmul`
     LMul,
+    /// `imul`
     IMul,
+    /// `dmul`
     DMul,
+    /// `fmul`
     FMul,
+    /// `ddiv`
     DDiv,
+    /// `fdiv`
     FDiv,
+    /// `idiv`
     IDiv,
+    /// `ldiv`
     LDiv,
+    /// `frem`
     FRem,
+    /// `drem`
     DRem,
+    /// `irem`
     IRem,
+    /// `lrem`
     LRem,
+    /// `ishr`
     IShr,
+    /// `ishl`
     IShl,
+    /// `lshl`
     LShl,
+    /// `iushr`
     IUShr,
+    /// `lushr`
     LUShr,
+    /// `iand`
     IAnd,
+    /// `land`
     LAnd,
+    /// `ior`
     IOr,
+    /// `lor`
     LOr,
+    /// `ixor`
     IXOr,
+    /// `lxor`
     LXOr,
+    /// `dneg`
     DNeg,
+    /// `fneg`
     FNeg,
+    /// `ineg`
     INeg,
+    /// `lneg`
     LNeg,
+    /// `lshr`
     LShr,
-    IInc(u8, i8),
+    /// `iinc`: increment local variable(first) by a constant(second).
+    IInc(u16, i16),
+    /// `invokespecial`, index of a `MethodRef` or `InterfaceMethodRef`.
     InvokeSpecial(u16),
+    /// `invokevirtual`, index of a `MethodRef`.
     InvokeVirtual(u16),
-    InvokeInterface(u16),
+    /// `invokeinterface`, index of an `InterfaceMethodRef` and the `count` operand(number of argument slots + 1).
+    InvokeInterface(u16, u8),
+    /// `invokestatic`, index of a `MethodRef` or `InterfaceMethodRef`.
     InvokeStatic(u16),
+    /// `invokedynamic`, index of an `InvokeDynamic` constant.
     InvokeDynamic(u16),
+    /// `return`
     Return,
+    /// `areturn`
     AReturn,
+    /// `ireturn`
     IReturn,
+    /// `dreturn`
     DReturn,
+    /// `freturn`
     FReturn,
+    /// `lreturn`
     LReturn,
+    /// `getstatic`, index of a `FieldRef`.
     GetStatic(u16),
+    /// `putstatic`, index of a `FieldRef`.
     PutStatic(u16),
+    /// `getfield`, index of a `FieldRef`.
     GetField(u16),
+    /// `putfield`, index of a `FieldRef`.
     PutField(u16),
+    /// `ldc`, `ldc_w` and `ldc2_w`: push a constant from the constant pool.
     LoadConst(u16),
+    /// `if_icmpeq`
     IfICmpEq(i16),
+    /// `if_icmpne`
     IfICmpNe(i16),
+    /// `if_icmple`
     IfICmpLessEqual(i16),
+    /// `if_icmplt`
     IfICmpLessThan(i16),
+    /// `if_icmpgt`
     IfICmpGreater(i16),
-    IfZero(i16),    //aka IfEq on wikipedia
-    IfNotZero(i16), //aka IfEq on wikipedia
+    /// `ifeq`
+    IfZero(i16),
+    /// `ifne`
+    IfNotZero(i16),
+    /// `ifnull`
     IfNull(i16),
+    /// `ifnonnull`
     IfNotNull(i16),
+    /// `if_acmpne`
     IfACmpNe(i16),
+    /// `if_acmpeq`
     IfACmpEq(i16),
+    /// `if_icmpge`
     IfIGreterEqual(i16),
-    IfGreterEqualZero(i16), // aka IfGe
-    IfGreterZero(i16),      // aka IfGt
-    IfLessZero(i16),        // aka IfLt
-    IfLessEqualZero(i16),   // aka IfLt
-    GoTo(i16),
+    /// `ifge`
+    IfGreterEqualZero(i16),
+    /// `ifgt`
+    IfGreterZero(i16),
+    /// `iflt`
+    IfLessZero(i16),
+    /// `ifle`
+    IfLessEqualZero(i16),
+    /// `goto` and `goto_w`
+    GoTo(i32),
+    /// `jsr` and `jsr_w`: jump to a subroutine, pushing the address of the next instruction.
+    Jsr(i32),
+    /// `ret`: return from a subroutine to the address stored in a local variable.
+    Ret(u16),
+    /// `dup`
     Dup,
+    /// `dup_x1`
     DupX1,
+    /// `dup2_x1`
     Dup2X1,
+    /// `dup2_x2`
     Dup2X2,
+    /// `dup_x2`
     DupX2,
+    /// `dup2`
     Dup2,
+    /// `swap`
     Swap,
+    /// `pop`
     Pop,
+    /// `pop2`
     Pop2,
+    /// `new`, index of a `Class` constant.
     New(u16),
+    /// `newarray`, `atype` code of a primitive array(4 = boolean, ..., 11 = long).
     NewArray(u8),
+    /// `anewarray`, index of a `Class` constant.
     ANewArray(u16),
+    /// `multianewarray`, index of a `Class` constant and number of dimensions.
     MultiANewArray(u16, u8),
+    /// `bipush`
     BIPush(i8),
+    /// `sipush`
     SIPush(i16),
+    /// `arraylength`
     ArrayLength,
+    /// `athrow`
     Throw,
+    /// `aaload`
     AALoad,
+    /// `baload`
     BALoad,
+    /// `caload`
     CALoad,
+    /// `faload`
     FALoad,
+    /// `daload`
     DALoad,
+    /// `iaload`
     IALoad,
+    /// `laload`
     LALoad,
+    /// `saload`
     SALoad,
+    /// `aastore`
     AAStore,
+    /// `bastore`
     BAStore,
+    /// `castore`
     CAStore,
+    /// `dastore`
     DAStore,
+    /// `fastore`
     FAStore,
+    /// `iastore`
     IAStore,
+    /// `lastore`
     LAStore,
+    /// `sastore`
     SAStore,
+    /// `checkcast`, index of a `Class` constant.
     CheckCast(u16),
-    InstanceOf(u16), // Check if obj is an instance of class and then???
+    /// `instanceof`, index of a `Class` constant.
+    InstanceOf(u16),
+    /// `d2f`
     D2F,
+    /// `d2i`
     D2I,
+    /// `d2l`
     D2L,
+    /// `f2i`
     F2I,
+    /// `f2l`
     F2L,
+    /// `f2d`
     F2D,
+    /// `i2b`
     I2B,
+    /// `i2c`
     I2C,
+    /// `i2d`
     I2D,
+    /// `i2f`
     I2F,
+    /// `i2l`
     I2L,
+    /// `i2s`
     I2S,
+    /// `l2i`
     L2I,
+    /// `l2f`
     L2F,
+    /// `l2d`
     L2D,
-    LCmp,  //Compare two longs. Push 0 if same, 1 if a > b, -1 if b > a
-    FCmpL, //Compare two floats. Push 0 if same, 1 if a > b, -1 if b > a, and -1 if a or b is NaN.
-    FCmpG, //Compare two floats. Push 0 if same, 1 if a > b, -1 if b > a, and 1 if a or b is NaN.
-    DCmpL, //Compare two doubles. Push 0 if same, 1 if a > b, -1 if b > a, and -1 if a or b is NaN.
-    DCmpG, //Compare two doubles. Push 0 if same, 1 if a > b, -1 if b > a, and 1 if a or b is NaN.
+    /// `lcmp`: compare two longs. Push 0 if same, 1 if a > b, -1 if b > a.
+    LCmp,
+    /// `fcmpl`: compare two floats. Push 0 if same, 1 if a > b, -1 if b > a, and -1 if a or b is NaN.
+    FCmpL,
+    /// `fcmpg`: compare two floats. Push 0 if same, 1 if a > b, -1 if b > a, and 1 if a or b is NaN.
+    FCmpG,
+    /// `dcmpl`: compare two doubles. Push 0 if same, 1 if a > b, -1 if b > a, and -1 if a or b is NaN.
+    DCmpL,
+    /// `dcmpg`: compare two doubles. Push 0 if same, 1 if a > b, -1 if b > a, and 1 if a or b is NaN.
+    DCmpG,
+    /// `monitorenter`
     MonitorEnter,
+    /// `monitorexit`
     MonitorExit,
+    /// `lookupswitch`
     LookupSwitch(Box<LookupSwitch>),
+    /// `tableswitch`
+    TableSwitch(Box<TableSwitch>),
 }
 ///Separate to decrease footprint of individual OP.
 #[derive(Debug, Clone)]
-pub(crate) struct LookupSwitch {
+pub struct LookupSwitch {
     pub(crate) default_offset: i32,
     pub(crate) pairs: Box<[(i32, i32)]>,
 }
+impl LookupSwitch {
+    /// Jump offset used when no key matches.
+    pub fn default_offset(&self) -> i32 {
+        self.default_offset
+    }
+    /// `(key, offset)` pairs of this switch.
+    pub fn pairs(&self) -> &[(i32, i32)] {
+        &self.pairs
+    }
+}
+/// Jump table indexed by keys in range `low..=high`. Separate to decrease footprint of individual OP.
+#[derive(Debug, Clone)]
+pub struct TableSwitch {
+    pub(crate) default_offset: i32,
+    pub(crate) low: i32,
+    pub(crate) high: i32,
+    pub(crate) offsets: Box<[i32]>,
+}
+impl TableSwitch {
+    /// Jump offset used when the key is outside of `low..=high`.
+    pub fn default_offset(&self) -> i32 {
+        self.default_offset
+    }
+    pub fn low(&self) -> i32 {
+        self.low
+    }
+    pub fn high(&self) -> i32 {
+        self.high
+    }
+    /// Jump offsets for keys `low`, `low + 1`, ..., `high`.
+    pub fn offsets(&self) -> &[i32] {
+        &self.offsets
+    }
+    /// `(key, offset)` pairs of this switch.
+    pub fn pairs(&self) -> impl Iterator<Item = (i32, i32)> + '_ {
+        (0..)
+            .zip(self.offsets.iter())
+            .map(|(index, offset)| (self.low.wrapping_add(index), *offset))
+    }
+}
+/// Number of operand stack slots an instruction pops and then pushes. `long` and `double` values take 2 slots.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StackEffect {
+    pub pops: u16,
+    pub pushes: u16,
+}
+impl StackEffect {
+    pub(crate) const fn new(pops: u16, pushes: u16) -> Self {
+        Self { pops, pushes }
+    }
+    /// Change of the stack depth caused by the instruction.
+    pub fn net(&self) -> i32 {
+        self.pushes as i32 - self.pops as i32
+    }
+}
+impl OpCode {
+    /// Returns true for instructions which may transfer control somewhere else than the next instruction:
+    /// conditional jumps, `goto`, `jsr` and switches. Returns, `ret` and `athrow` are not branches.
+    pub fn is_branch(&self) -> bool {
+        self.relative_targets().is_some()
+    }
+    /// Returns true if execution never continues to the next instruction after this one.
+    pub fn is_terminator(&self) -> bool {
+        matches!(
+            self,
+            Self::Return
+                | Self::AReturn
+                | Self::IReturn
+                | Self::DReturn
+                | Self::FReturn
+                | Self::LReturn
+                | Self::Throw
+                | Self::GoTo(_)
+                | Self::Ret(_)
+                | Self::LookupSwitch(_)
+                | Self::TableSwitch(_)
+        )
+    }
+    /// `(key, target)` pairs of a `lookupswitch` or `tableswitch` located at `offset`, with absolute targets.
+    /// The default target is not included. Empty for other instructions.
+    pub fn switch_cases(&self, offset: u16) -> impl Iterator<Item = (i32, u16)> + '_ {
+        let (lookup, table): (&[(i32, i32)], _) = match self {
+            Self::LookupSwitch(switch) => (&switch.pairs, None),
+            Self::TableSwitch(switch) => (&[], Some(switch)),
+            _ => (&[], None),
+        };
+        lookup
+            .iter()
+            .copied()
+            .chain(table.into_iter().flat_map(|switch| switch.pairs()))
+            .filter_map(move |(key, relative)| {
+                Some((key, u16::try_from(offset as i64 + relative as i64).ok()?))
+            })
+    }
+    /// Absolute offsets this instruction may jump to, if it is located at `offset`.
+    /// Does not include the next instruction for conditional jumps. Targets outside of `u16` range are skipped.
+    pub fn branch_targets(&self, offset: u16) -> SmallVec<[u16; 2]> {
+        let Some(relative) = self.relative_targets() else {
+            return SmallVec::new();
+        };
+        relative
+            .into_iter()
+            .filter_map(|relative| u16::try_from(offset as i64 + relative as i64).ok())
+            .collect()
+    }
+    fn relative_targets(&self) -> Option<SmallVec<[i32; 2]>> {
+        match self {
+            Self::IfICmpEq(offset)
+            | Self::IfICmpNe(offset)
+            | Self::IfICmpLessEqual(offset)
+            | Self::IfICmpLessThan(offset)
+            | Self::IfICmpGreater(offset)
+            | Self::IfZero(offset)
+            | Self::IfNotZero(offset)
+            | Self::IfNull(offset)
+            | Self::IfNotNull(offset)
+            | Self::IfACmpNe(offset)
+            | Self::IfACmpEq(offset)
+            | Self::IfIGreterEqual(offset)
+            | Self::IfGreterEqualZero(offset)
+            | Self::IfGreterZero(offset)
+            | Self::IfLessZero(offset)
+            | Self::IfLessEqualZero(offset) => Some(smallvec::smallvec![*offset as i32]),
+            Self::GoTo(offset) | Self::Jsr(offset) => Some(smallvec::smallvec![*offset]),
+            Self::LookupSwitch(switch) => Some(
+                std::iter::once(switch.default_offset)
+                    .chain(switch.pairs.iter().map(|(_, offset)| *offset))
+                    .collect(),
+            ),
+            Self::TableSwitch(switch) => Some(
+                std::iter::once(switch.default_offset)
+                    .chain(switch.offsets.iter().copied())
+                    .collect(),
+            ),
+            _ => None,
+        }
+    }
+    /// Opcode byte of the shortest encoding of this instruction, e.g. `iload_1` for `ILoad(1)`. `wide_constant` selects
+    /// `ldc2_w` for `LoadConst`, and must be set for `long` and `double` constants. Returns `None` for constants which
+    /// have no `*const_<n>` form, e.g. `IConst(7)`.
+    pub fn default_opcode(&self, wide_constant: bool) -> Option<u8> {
+        // Locals 0 to 3 have their own opcodes, locals above 255 need `wide`.
+        let local = |index: u16, short: u8, long: u8| match index {
+            0..=3 => short + index as u8,
+            4..=255 => long,
+            _ => 0xc4,
+        };
+        Some(match self {
+            Self::Nop => 0x00,
+            Self::AConstNull => 0x01,
+            Self::IConst(value @ -1..=5) => (0x03 + value) as u8,
+            Self::LConst(value @ 0..=1) => 0x09 + *value as u8,
+            Self::FConst(value) if *value == 0.0 && value.is_sign_positive() => 0x0b,
+            Self::FConst(value) if *value == 1.0 => 0x0c,
+            Self::FConst(value) if *value == 2.0 => 0x0d,
+            Self::DConst(value) if *value == 0.0 && value.is_sign_positive() => 0x0e,
+            Self::DConst(value) if *value == 1.0 => 0x0f,
+            Self::IConst(_) | Self::LConst(_) | Self::FConst(_) | Self::DConst(_) => return None,
+            Self::BIPush(_) => 0x10,
+            Self::SIPush(_) => 0x11,
+            Self::LoadConst(_) if wide_constant => 0x14,
+            Self::LoadConst(index) => {
+                if *index <= 255 {
+                    0x12
+                } else {
+                    0x13
+                }
+            }
+            Self::ILoad(index) => local(*index, 0x1a, 0x15),
+            Self::LLoad(index) => local(*index, 0x1e, 0x16),
+            Self::FLoad(index) => local(*index, 0x22, 0x17),
+            Self::DLoad(index) => local(*index, 0x26, 0x18),
+            Self::ALoad(index) => local(*index, 0x2a, 0x19),
+            Self::IALoad => 0x2e,
+            Self::LALoad => 0x2f,
+            Self::FALoad => 0x30,
+            Self::DALoad => 0x31,
+            Self::AALoad => 0x32,
+            Self::BALoad => 0x33,
+            Self::CALoad => 0x34,
+            Self::SALoad => 0x35,
+            Self::IStore(index) => local(*index, 0x3b, 0x36),
+            Self::LStore(index) => local(*index, 0x3f, 0x37),
+            Self::FStore(index) => local(*index, 0x43, 0x38),
+            Self::DStore(index) => local(*index, 0x47, 0x39),
+            Self::AStore(index) => local(*index, 0x4b, 0x3a),
+            Self::IAStore => 0x4f,
+            Self::LAStore => 0x50,
+            Self::FAStore => 0x51,
+            Self::DAStore => 0x52,
+            Self::AAStore => 0x53,
+            Self::BAStore => 0x54,
+            Self::CAStore => 0x55,
+            Self::SAStore => 0x56,
+            Self::Pop => 0x57,
+            Self::Pop2 => 0x58,
+            Self::Dup => 0x59,
+            Self::DupX1 => 0x5a,
+            Self::DupX2 => 0x5b,
+            Self::Dup2 => 0x5c,
+            Self::Dup2X1 => 0x5d,
+            Self::Dup2X2 => 0x5e,
+            Self::Swap => 0x5f,
+            Self::IAdd => 0x60,
+            Self::LAdd => 0x61,
+            Self::FAdd => 0x62,
+            Self::DAdd => 0x63,
+            Self::ISub => 0x64,
+            Self::LSub => 0x65,
+            Self::FSub => 0x66,
+            Self::DSub => 0x67,
+            Self::IMul => 0x68,
+            Self::LMul => 0x69,
+            Self::FMul => 0x6a,
+            Self::DMul => 0x6b,
+            Self::IDiv => 0x6c,
+            Self::LDiv => 0x6d,
+            Self::FDiv => 0x6e,
+            Self::DDiv => 0x6f,
+            Self::IRem => 0x70,
+            Self::LRem => 0x71,
+            Self::FRem => 0x72,
+            Self::DRem => 0x73,
+            Self::INeg => 0x74,
+            Self::LNeg => 0x75,
+            Self::FNeg => 0x76,
+            Self::DNeg => 0x77,
+            Self::IShl => 0x78,
+            Self::LShl => 0x79,
+            Self::IShr => 0x7a,
+            Self::LShr => 0x7b,
+            Self::IUShr => 0x7c,
+            Self::LUShr => 0x7d,
+            Self::IAnd => 0x7e,
+            Self::LAnd => 0x7f,
+            Self::IOr => 0x80,
+            Self::LOr => 0x81,
+            Self::IXOr => 0x82,
+            Self::LXOr => 0x83,
+            Self::IInc(index, incr) => {
+                if *index <= 255 && i8::try_from(*incr).is_ok() {
+                    0x84
+                } else {
+                    0xc4
+                }
+            }
+            Self::I2L => 0x85,
+            Self::I2F => 0x86,
+            Self::I2D => 0x87,
+            Self::L2I => 0x88,
+            Self::L2F => 0x89,
+            Self::L2D => 0x8a,
+            Self::F2I => 0x8b,
+            Self::F2L => 0x8c,
+            Self::F2D => 0x8d,
+            Self::D2I => 0x8e,
+            Self::D2L => 0x8f,
+            Self::D2F => 0x90,
+            Self::I2B => 0x91,
+            Self::I2C => 0x92,
+            Self::I2S => 0x93,
+            Self::LCmp => 0x94,
+            Self::FCmpL => 0x95,
+            Self::FCmpG => 0x96,
+            Self::DCmpL => 0x97,
+            Self::DCmpG => 0x98,
+            Self::IfZero(_) => 0x99,
+            Self::IfNotZero(_) => 0x9a,
+            Self::IfLessZero(_) => 0x9b,
+            Self::IfGreterEqualZero(_) => 0x9c,
+            Self::IfGreterZero(_) => 0x9d,
+            Self::IfLessEqualZero(_) => 0x9e,
+            Self::IfICmpEq(_) => 0x9f,
+            Self::IfICmpNe(_) => 0xa0,
+            Self::IfICmpLessThan(_) => 0xa1,
+            Self::IfIGreterEqual(_) => 0xa2,
+            Self::IfICmpGreater(_) => 0xa3,
+            Self::IfICmpLessEqual(_) => 0xa4,
+            Self::IfACmpEq(_) => 0xa5,
+            Self::IfACmpNe(_) => 0xa6,
+            Self::GoTo(offset) => {
+                if i16::try_from(*offset).is_ok() {
+                    0xa7
+                } else {
+                    0xc8
+                }
+            }
+            Self::Jsr(offset) => {
+                if i16::try_from(*offset).is_ok() {
+                    0xa8
+                } else {
+                    0xc9
+                }
+            }
+            Self::Ret(index) => {
+                if *index <= 255 {
+                    0xa9
+                } else {
+                    0xc4
+                }
+            }
+            Self::TableSwitch(_) => 0xaa,
+            Self::LookupSwitch(_) => 0xab,
+            Self::IReturn => 0xac,
+            Self::LReturn => 0xad,
+            Self::FReturn => 0xae,
+            Self::DReturn => 0xaf,
+            Self::AReturn => 0xb0,
+            Self::Return => 0xb1,
+            Self::GetStatic(_) => 0xb2,
+            Self::PutStatic(_) => 0xb3,
+            Self::GetField(_) => 0xb4,
+            Self::PutField(_) => 0xb5,
+            Self::InvokeVirtual(_) => 0xb6,
+            Self::InvokeSpecial(_) => 0xb7,
+            Self::InvokeStatic(_) => 0xb8,
+            Self::InvokeInterface(..) => 0xb9,
+            Self::InvokeDynamic(_) => 0xba,
+            Self::New(_) => 0xbb,
+            Self::NewArray(_) => 0xbc,
+            Self::ANewArray(_) => 0xbd,
+            Self::ArrayLength => 0xbe,
+            Self::Throw => 0xbf,
+            Self::CheckCast(_) => 0xc0,
+            Self::InstanceOf(_) => 0xc1,
+            Self::MonitorEnter => 0xc2,
+            Self::MonitorExit => 0xc3,
+            Self::MultiANewArray(..) => 0xc5,
+            Self::IfNull(_) => 0xc6,
+            Self::IfNotNull(_) => 0xc7,
+        })
+    }
+    /// Size in bytes of this instruction, if it was encoded with the opcode byte `opcode`(`0xc4` for instructions
+    /// prefixed by `wide`) at byte `offset`. Switches depend on `offset`, because of their padding.
+    /// Switches built by hand may not fit in a method, so their size saturates instead of overflowing.
+    pub fn encoded_size(&self, opcode: u8, offset: u16) -> u32 {
+        let padding = 3 - offset as u32 % 4;
+        let entries = |count: usize, size: u32| u32::try_from(count).unwrap_or(u32::MAX).saturating_mul(size);
+        match self {
+            Self::LookupSwitch(switch) => (1 + padding + 8).saturating_add(entries(switch.pairs.len(), 8)),
+            Self::TableSwitch(switch) => (1 + padding + 12).saturating_add(entries(switch.offsets.len(), 4)),
+            Self::IInc(..) if opcode == 0xc4 => 6,
+            _ if opcode == 0xc4 => 4,
+            _ => 1 + operands_size(opcode) as u32,
+        }
+    }
+    /// Stack slots popped and pushed by this instruction. Returns `None` for instructions whose effect
+    /// depends on the constant pool: field accesses, invokes and `ldc`.
+    pub fn stack_effect(&self) -> Option<StackEffect> {
+        let (pops, pushes) = match self {
+            Self::Nop | Self::IInc(..) | Self::GoTo(_) | Self::Ret(_) | Self::Return => (0, 0),
+            Self::AConstNull
+            | Self::Jsr(_)
+            | Self::IConst(_)
+            | Self::FConst(_)
+            | Self::BIPush(_)
+            | Self::SIPush(_)
+            | Self::ILoad(_)
+            | Self::FLoad(_)
+            | Self::ALoad(_)
+            | Self::New(_) => (0, 1),
+            Self::LConst(_) | Self::DConst(_) | Self::LLoad(_) | Self::DLoad(_) => (0, 2),
+            Self::IALoad
+            | Self::FALoad
+            | Self::AALoad
+            | Self::BALoad
+            | Self::CALoad
+            | Self::SALoad => (2, 1),
+            Self::LALoad | Self::DALoad => (2, 2),
+            Self::IStore(_) | Self::FStore(_) | Self::AStore(_) => (1, 0),
+            Self::LStore(_) | Self::DStore(_) => (2, 0),
+            Self::IAStore
+            | Self::FAStore
+            | Self::AAStore
+            | Self::BAStore
+            | Self::CAStore
+            | Self::SAStore => (3, 0),
+            Self::LAStore | Self::DAStore => (4, 0),
+            Self::Pop => (1, 0),
+            Self::Pop2 => (2, 0),
+            Self::Dup => (1, 2),
+            Self::DupX1 => (2, 3),
+            Self::DupX2 => (3, 4),
+            Self::Dup2 => (2, 4),
+            Self::Dup2X1 => (3, 5),
+            Self::Dup2X2 => (4, 6),
+            Self::Swap => (2, 2),
+            Self::IAdd
+            | Self::ISub
+            | Self::IMul
+            | Self::IDiv
+            | Self::IRem
+            | Self::IShl
+            | Self::IShr
+            | Self::IUShr
+            | Self::IAnd
+            | Self::IOr
+            | Self::IXOr
+            | Self::FAdd
+            | Self::FSub
+            | Self::FMul
+            | Self::FDiv
+            | Self::FRem
+            | Self::FCmpL
+            | Self::FCmpG => (2, 1),
+            Self::LAdd
+            | Self::LSub
+            | Self::LMul
+            | Self::LDiv
+            | Self::LRem
+            | Self::LAnd
+            | Self::LOr
+            | Self::LXOr
+            | Self::DAdd
+            | Self::DSub
+            | Self::DMul
+            | Self::DDiv
+            | Self::DRem => (4, 2),
+            Self::LShl | Self::LShr | Self::LUShr => (3, 2),
+            Self::LCmp | Self::DCmpL | Self::DCmpG => (4, 1),
+            Self::INeg
+            | Self::FNeg
+            | Self::I2F
+            | Self::F2I
+            | Self::I2B
+            | Self::I2C
+            | Self::I2S
+            | Self::NewArray(_)
+            | Self::ANewArray(_)
+            | Self::ArrayLength
+            | Self::CheckCast(_)
+            | Self::InstanceOf(_) => (1, 1),
+            Self::LNeg | Self::DNeg | Self::L2D | Self::D2L => (2, 2),
+            Self::I2L | Self::I2D | Self::F2L | Self::F2D => (1, 2),
+            Self::L2I | Self::L2F | Self::D2I | Self::D2F => (2, 1),
+            Self::IfZero(_)
+            | Self::IfNotZero(_)
+            | Self::IfLessZero(_)
+            | Self::IfLessEqualZero(_)
+            | Self::IfGreterZero(_)
+            | Self::IfGreterEqualZero(_)
+            | Self::IfNull(_)
+            | Self::IfNotNull(_)
+            | Self::LookupSwitch(_)
+            | Self::TableSwitch(_)
+            | Self::IReturn
+            | Self::FReturn
+            | Self::AReturn
+            | Self::Throw
+            | Self::MonitorEnter
+            | Self::MonitorExit => (1, 0),
+            Self::IfICmpEq(_)
+            | Self::IfICmpNe(_)
+            | Self::IfICmpLessThan(_)
+            | Self::IfICmpLessEqual(_)
+            | Self::IfICmpGreater(_)
+            | Self::IfIGreterEqual(_)
+            | Self::IfACmpEq(_)
+            | Self::IfACmpNe(_)
+            | Self::LReturn
+            | Self::DReturn => (2, 0),
+            Self::MultiANewArray(_, dimensions) => (*dimensions as u16, 1),
+            Self::LoadConst(_)
+            | Self::GetStatic(_)
+            | Self::PutStatic(_)
+            | Self::GetField(_)
+            | Self::PutField(_)
+            | Self::InvokeVirtual(_)
+            | Self::InvokeSpecial(_)
+            | Self::InvokeStatic(_)
+            | Self::InvokeInterface(..)
+            | Self::InvokeDynamic(_) => return None,
+        };
+        Some(StackEffect::new(pops, pushes))
+    }
+}
+/// Encodes instructions decoded by [`load_ops`] back into bytecode.
+pub(crate) fn store_ops<W: std::io::Write>(ops: &[(OpCode, u16)], opcodes: &[u8], dst: &mut W) -> std::io::Result<()> {
+    for ((op, offset), opcode) in ops.iter().zip(opcodes) {
+        op.write(*opcode, *offset, dst)?;
+    }
+    Ok(())
+}
+impl OpCode {
+    /// Writes this instruction, encoded with the opcode byte `opcode`, located at byte `offset`.
+    pub(crate) fn write<W: std::io::Write>(&self, opcode: u8, offset: u16, dst: &mut W) -> std::io::Result<()> {
+        store_u8(dst, opcode)?;
+        if opcode == 0xc4 {
+            let (wide_op, index) = match self {
+                Self::ILoad(index) => (0x15, index),
+                Self::LLoad(index) => (0x16, index),
+                Self::FLoad(index) => (0x17, index),
+                Self::DLoad(index) => (0x18, index),
+                Self::ALoad(index) => (0x19, index),
+                Self::IStore(index) => (0x36, index),
+                Self::LStore(index) => (0x37, index),
+                Self::FStore(index) => (0x38, index),
+                Self::DStore(index) => (0x39, index),
+                Self::AStore(index) => (0x3a, index),
+                Self::Ret(index) => (0xa9, index),
+                Self::IInc(index, _) => (0x84, index),
+                _ => {
+                    return Err(std::io::Error::new(
+                        std::io::ErrorKind::InvalidInput,
+                        format!("{self:?} can't be used with wide"),
+                    ))
+                }
+            };
+            store_u8(dst, wide_op)?;
+            store_u16(dst, *index)?;
+            if let Self::IInc(_, incr) = self {
+                store_i16(dst, *incr)?;
+            }
+            return Ok(());
+        }
+        let operands_size = operands_size(opcode);
+        match self {
+            Self::BIPush(value) => store_i8(dst, *value),
+            Self::SIPush(value) => store_i16(dst, *value),
+            Self::IInc(index, incr) => {
+                store_u8(dst, *index as u8)?;
+                store_i8(dst, *incr as i8)
+            }
+            Self::ILoad(index)
+            | Self::LLoad(index)
+            | Self::FLoad(index)
+            | Self::DLoad(index)
+            | Self::ALoad(index)
+            | Self::IStore(index)
+            | Self::LStore(index)
+            | Self::FStore(index)
+            | Self::DStore(index)
+            | Self::AStore(index)
+            | Self::Ret(index) => match operands_size {
+                // Short forms, like `iload_0`, have the index encoded in the opcode.
+                0 => Ok(()),
+                _ => store_u8(dst, *index as u8),
+            },
+            Self::NewArray(atype) => store_u8(dst, *atype),
+            Self::LoadConst(index) => match operands_size {
+                1 => store_u8(dst, *index as u8),
+                _ => store_u16(dst, *index),
+            },
+            Self::GoTo(offset) | Self::Jsr(offset) => match operands_size {
+                2 => store_i16(dst, *offset as i16),
+                _ => store_i32(dst, *offset),
+            },
+            Self::IfICmpEq(offset)
+            | Self::IfICmpNe(offset)
+            | Self::IfICmpLessEqual(offset)
+            | Self::IfICmpLessThan(offset)
+            | Self::IfICmpGreater(offset)
+            | Self::IfZero(offset)
+            | Self::IfNotZero(offset)
+            | Self::IfNull(offset)
+            | Self::IfNotNull(offset)
+            | Self::IfACmpNe(offset)
+            | Self::IfACmpEq(offset)
+            | Self::IfIGreterEqual(offset)
+            | Self::IfGreterEqualZero(offset)
+            | Self::IfGreterZero(offset)
+            | Self::IfLessZero(offset)
+            | Self::IfLessEqualZero(offset) => store_i16(dst, *offset),
+            Self::GetStatic(index)
+            | Self::PutStatic(index)
+            | Self::GetField(index)
+            | Self::PutField(index)
+            | Self::InvokeVirtual(index)
+            | Self::InvokeSpecial(index)
+            | Self::InvokeStatic(index)
+            | Self::New(index)
+            | Self::ANewArray(index)
+            | Self::CheckCast(index)
+            | Self::InstanceOf(index) => store_u16(dst, *index),
+            Self::InvokeInterface(index, count) => {
+                store_u16(dst, *index)?;
+                store_u8(dst, *count)?;
+                store_u8(dst, 0)
+            }
+            Self::InvokeDynamic(index) => {
+                store_u16(dst, *index)?;
+                store_u16(dst, 0)
+            }
+            Self::MultiANewArray(index, dimensions) => {
+                store_u16(dst, *index)?;
+                store_u8(dst, *dimensions)
+            }
+            Self::LookupSwitch(switch) => {
+                dst.write_all(&[0; 3][..(3 - offset % 4) as usize])?;
+                store_i32(dst, switch.default_offset)?;
+                store_i32(dst, switch.pairs.len() as i32)?;
+                for (key, offset) in switch.pairs.iter() {
+                    store_i32(dst, *key)?;
+                    store_i32(dst, *offset)?;
+                }
+                Ok(())
+            }
+            Self::TableSwitch(switch) => {
+                dst.write_all(&[0; 3][..(3 - offset % 4) as usize])?;
+                store_i32(dst, switch.default_offset)?;
+                store_i32(dst, switch.low)?;
+                store_i32(dst, switch.high)?;
+                for offset in switch.offsets.iter() {
+                    store_i32(dst, *offset)?;
+                }
+                Ok(())
+            }
+            // Everything else is either a single byte, or has its operand encoded in the opcode(e.g. `iconst_1`).
+            _ => Ok(()),
+        }
+    }
+}
+/// Size in bytes of the operands following `opcode`, for instructions with operands of fixed size.
+fn operands_size(opcode: u8) -> u16 {
+    match opcode {
+        0x10 | 0x12 | 0x15..=0x19 | 0x36..=0x3a | 0xa9 | 0xbc => 1,
+        0x11 | 0x13 | 0x14 | 0x84 | 0x99..=0xa8 | 0xb2..=0xb8 | 0xbb | 0xbd | 0xc0 | 0xc1 | 0xc6 | 0xc7 => 2,
+        0xc5 => 3,
+        0xb9 | 0xba | 0xc8 | 0xc9 => 4,
+        _ => 0,
+    }
+}
+/// Decoded instructions paired with their offsets, and the opcode byte each one was encoded with.
+pub(crate) type LoadedOps = (Vec<(OpCode, u16)>, Vec<u8>);
 pub(crate) fn load_ops<R: std::io::Read>(
-    src: &mut R,
+    src: &mut OffsetReader<R>,
     code_length: u32,
-) -> Result<Vec<(OpCode, u16)>, std::io::Error> {
-    let mut curr_offset: u16 = 0;
+) -> Result<LoadedOps, JavaImportError> {
+    // Offsets of instructions are stored as u16, so longer methods are not valid.
+    if code_length == 0 || code_length > u16::MAX as u32 {
+        return Err(src.error(ImportErrorKind::InvalidCodeLength(code_length)));
+    }
+    let mut curr_offset: u32 = 0;
 
     let mut ops = Vec::with_capacity(code_length as usize);
+    let mut opcodes = Vec::with_capacity(code_length as usize);
     //println!("\nMethod begin\n");
-    while (curr_offset as u32) < code_length {
+    while curr_offset < code_length {
+        let op_start = src.offset();
+        let error = |kind| JavaImportError::new(kind).at(op_start);
         let op = load_u8(src)?;
-        let op_offset = curr_offset;
+        let op_offset = curr_offset as u16;
         //print!("{curr_offset}:\t");
         curr_offset += 1;
         let decoded_op = match op {
@@ -198,33 +990,33 @@ pub(crate) fn load_ops<R: std::io::Read>(
             0x15 => {
                 let index = load_u8(src)?;
                 curr_offset += 1;
-                OpCode::ILoad(index)
+                OpCode::ILoad(index as u16)
             }
             0x16 => {
                 let index = load_u8(src)?;
                 curr_offset += 1;
-                OpCode::LLoad(index)
+                OpCode::LLoad(index as u16)
             }
             0x17 => {
                 let index = load_u8(src)?;
                 curr_offset += 1;
-                OpCode::FLoad(index)
+                OpCode::FLoad(index as u16)
             }
             0x18 => {
                 let index = load_u8(src)?;
                 curr_offset += 1;
-                OpCode::DLoad(index)
+                OpCode::DLoad(index as u16)
             }
             0x19 => {
                 let index = load_u8(src)?;
                 curr_offset += 1;
-                OpCode::ALoad(index)
+                OpCode::ALoad(index as u16)
             }
-            0x1a..=0x1d => OpCode::ILoad(op - 0x1a),
-            0x1e..=0x21 => OpCode::LLoad(op - 0x1e),
-            0x22..=0x25 => OpCode::FLoad(op - 0x22),
-            0x26..=0x29 => OpCode::DLoad(op - 0x26),
-            0x2a..=0x2d => OpCode::ALoad(op - 0x2a),
+            0x1a..=0x1d => OpCode::ILoad((op - 0x1a) as u16),
+            0x1e..=0x21 => OpCode::LLoad((op - 0x1e) as u16),
+            0x22..=0x25 => OpCode::FLoad((op - 0x22) as u16),
+            0x26..=0x29 => OpCode::DLoad((op - 0x26) as u16),
+            0x2a..=0x2d => OpCode::ALoad((op - 0x2a) as u16),
             0x2e => OpCode::IALoad,
             0x2f => OpCode::LALoad,
             0x30 => OpCode::FALoad,
@@ -236,32 +1028,32 @@ pub(crate) fn load_ops<R: std::io::Read>(
             0x3a => {
                 let index = load_u8(src)?;
                 curr_offset += 1;
-                OpCode::AStore(index)
+                OpCode::AStore(index as u16)
             }
             0x37 => {
                 let index = load_u8(src)?;
                 curr_offset += 1;
-                OpCode::LStore(index)
+                OpCode::LStore(index as u16)
             }
             0x38 => {
                 let index = load_u8(src)?;
                 curr_offset += 1;
-                OpCode::FStore(index)
+                OpCode::FStore(index as u16)
             }
             0x39 => {
                 let index = load_u8(src)?;
                 curr_offset += 1;
-                OpCode::DStore(index)
+                OpCode::DStore(index as u16)
             }
-            0x3b..=0x3e => OpCode::IStore(op - 0x3b),
-            0x3f..=0x42 => OpCode::LStore(op - 0x3f),
-            0x43..=0x46 => OpCode::FStore(op - 0x43),
-            0x47..=0x4a => OpCode::DStore(op - 0x47),
-            0x4b..=0x4e => OpCode::AStore(op - 0x4b),
+            0x3b..=0x3e => OpCode::IStore((op - 0x3b) as u16),
+            0x3f..=0x42 => OpCode::LStore((op - 0x3f) as u16),
+            0x43..=0x46 => OpCode::FStore((op - 0x43) as u16),
+            0x47..=0x4a => OpCode::DStore((op - 0x47) as u16),
+            0x4b..=0x4e => OpCode::AStore((op - 0x4b) as u16),
             0x36 => {
                 let index = load_u8(src)?;
                 curr_offset += 1;
-                OpCode::IStore(index)
+                OpCode::IStore(index as u16)
             }
             0x4f => OpCode::IAStore,
             0x50 => OpCode::LAStore,
@@ -308,7 +1100,7 @@ pub(crate) fn load_ops<R: std::io::Read>(
             0x79 => OpCode::LShl,
             0x7a => OpCode::IShr,
             0x7b => OpCode::LShr,
-            0x7c => OpCode::LUShr,
+            0x7c => OpCode::IUShr,
             0x7d => OpCode::LUShr,
             0x7e => OpCode::IAnd,
             0x7f => OpCode::LAnd,
@@ -320,7 +1112,7 @@ pub(crate) fn load_ops<R: std::io::Read>(
                 let var = load_u8(src)?;
                 let incr = load_i8(src)?;
                 curr_offset += 2;
-                OpCode::IInc(var, incr)
+                OpCode::IInc(var as u16, incr as i16)
             }
             0x85 => OpCode::I2L,
             0x86 => OpCode::I2F,
@@ -415,19 +1207,32 @@ pub(crate) fn load_ops<R: std::io::Read>(
             0xa7 => {
                 let offset = load_i16(src)?;
                 curr_offset += 2;
-                OpCode::GoTo(offset)
+                OpCode::GoTo(offset as i32)
+            }
+            0xa8 => {
+                let offset = load_i16(src)?;
+                curr_offset += 2;
+                OpCode::Jsr(offset as i32)
+            }
+            0xa9 => {
+                let index = load_u8(src)?;
+                curr_offset += 1;
+                OpCode::Ret(index as u16)
             }
             0xab => {
-                let to_next = ((4 - curr_offset % 4) % 4) as usize;
+                let to_next = (4 - curr_offset % 4) % 4;
                 // skip to_next
                 let mut out = [0; 4];
-                src.read_exact(&mut out[..to_next])?;
-                curr_offset += to_next as u16;
-                assert_eq!(curr_offset % 4, 0);
+                std::io::Read::read_exact(src, &mut out[..to_next as usize])?;
+                curr_offset += to_next;
                 let default_offset = load_i32(src)?;
                 curr_offset += 4;
                 let npairs = load_i32(src)?;
                 curr_offset += 4;
+                // Each pair takes 8 bytes, so this also bounds the allocation below.
+                if npairs < 0 || npairs as u64 * 8 > code_length.saturating_sub(curr_offset) as u64 {
+                    return Err(error(ImportErrorKind::InvalidSwitch));
+                }
                 let mut pairs = Vec::with_capacity(npairs as usize);
                 for _ in 0..npairs {
                     let value_match = load_i32(src)?;
@@ -442,29 +1247,33 @@ pub(crate) fn load_ops<R: std::io::Read>(
                 }))
             }
             0xaa => {
-                let to_next = ((4 - curr_offset % 4) % 4) as usize;
+                let to_next = (4 - curr_offset % 4) % 4;
                 // skip to_next
                 let mut out = [0; 4];
-                src.read_exact(&mut out[..to_next])?;
-                curr_offset += to_next as u16;
-                assert_eq!(curr_offset % 4, 0);
+                std::io::Read::read_exact(src, &mut out[..to_next as usize])?;
+                curr_offset += to_next;
                 let default_offset = load_i32(src)?;
-                curr_offset += 4 as u16;
+                curr_offset += 4;
                 let low = load_i32(src)?;
-                curr_offset += 4 as u16;
+                curr_offset += 4;
                 let high = load_i32(src)?;
-                curr_offset += 4 as u16;
-                let count = high - low + 1;
-                let mut pairs = Vec::with_capacity(count as usize);
-                for key in 0..count{
-                    let curr_key:i32 = key - low;
+                curr_offset += 4;
+                let count = high as i64 - low as i64 + 1;
+                // Each offset takes 4 bytes, so this also bounds the allocation below.
+                if count < 1 || count * 4 > code_length.saturating_sub(curr_offset) as i64 {
+                    return Err(error(ImportErrorKind::InvalidSwitch));
+                }
+                let mut offsets = Vec::with_capacity(count as usize);
+                for _ in 0..count {
                     let offset = load_i32(src)?;
-                    curr_offset += 4 as u16;
-                    pairs.push((curr_key,offset));
+                    curr_offset += 4;
+                    offsets.push(offset);
                 }
-                OpCode::LookupSwitch(Box::new(LookupSwitch {
+                OpCode::TableSwitch(Box::new(TableSwitch {
                     default_offset,
-                    pairs: pairs.into(),
+                    low,
+                    high,
+                    offsets: offsets.into(),
                 }))
             }
             0xac => OpCode::IReturn,
@@ -510,16 +1319,20 @@ pub(crate) fn load_ops<R: std::io::Read>(
             }
             0xb9 => {
                 let constant_pool_index = load_u16(src)?;
-                let _count = load_u8(src)?;
+                let count = load_u8(src)?;
                 let zero = load_u8(src)?;
-                assert_eq!(zero, 0);
+                if zero != 0 {
+                    return Err(error(ImportErrorKind::NonZeroPadding));
+                }
                 curr_offset += 4;
-                OpCode::InvokeInterface(constant_pool_index)
+                OpCode::InvokeInterface(constant_pool_index, count)
             }
             0xba => {
                 let constant_pool_index = load_u16(src)?;
                 let zeroes = load_u16(src)?;
-                assert_eq!(zeroes, 0);
+                if zeroes != 0 {
+                    return Err(error(ImportErrorKind::NonZeroPadding));
+                }
                 curr_offset += 4;
                 OpCode::InvokeDynamic(constant_pool_index)
             }
@@ -554,10 +1367,28 @@ pub(crate) fn load_ops<R: std::io::Read>(
             0xc2 => OpCode::MonitorEnter,
             0xc3 => OpCode::MonitorExit,
             0xc4 => {
-                return Err(std::io::Error::new(
-                    std::io::ErrorKind::Other,
-                    "Wide ops not supported!",
-                ));
+                let wide_op = load_u8(src)?;
+                let index = load_u16(src)?;
+                curr_offset += 3;
+                match wide_op {
+                    0x15 => OpCode::ILoad(index),
+                    0x16 => OpCode::LLoad(index),
+                    0x17 => OpCode::FLoad(index),
+                    0x18 => OpCode::DLoad(index),
+                    0x19 => OpCode::ALoad(index),
+                    0x36 => OpCode::IStore(index),
+                    0x37 => OpCode::LStore(index),
+                    0x38 => OpCode::FStore(index),
+                    0x39 => OpCode::DStore(index),
+                    0x3a => OpCode::AStore(index),
+                    0xa9 => OpCode::Ret(index),
+                    0x84 => {
+                        let incr = load_i16(src)?;
+                        curr_offset += 2;
+                        OpCode::IInc(index, incr)
+                    }
+                    _ => return Err(error(ImportErrorKind::InvalidWide(wide_op))),
+                }
             }
             0xc5 => {
                 let constant_pool_index = load_u16(src)?;
@@ -575,23 +1406,170 @@ pub(crate) fn load_ops<R: std::io::Read>(
                 curr_offset += 2;
                 OpCode::IfNotNull(offset)
             }
-            0xcb..=0xfd => {
-                return Err(std::io::Error::new(
-                    std::io::ErrorKind::Other,
-                    format!("Invalid(reserved) opcode 0x{op:x}!"),
-                ))
-            } //OpCode::Reserved,
-            _ => todo!("Unhandled opcode:0x{op:x}!"),
+            0xc8 => {
+                let offset = load_i32(src)?;
+                curr_offset += 4;
+                OpCode::GoTo(offset)
+            }
+            0xc9 => {
+                let offset = load_i32(src)?;
+                curr_offset += 4;
+                OpCode::Jsr(offset)
+            }
+            _ => return Err(error(ImportErrorKind::UnknownOpcode(op))),
         };
         ops.push((decoded_op, op_offset));
+        opcodes.push(op);
         //println!("{decoded_op:?}");
     }
+    if curr_offset != code_length {
+        return Err(src.error(ImportErrorKind::InstructionPastEnd));
+    }
     //Check if last op is a return or throw(Useful sanity-check to catch some mistakes early.
     /*
     assert!(
-    ops.iter().last().is_some_and(|lastop|{lastop.0.is_term()}) ||
+    ops.iter().last().is_some_and(|lastop|{lastop.0.is_terminator()}) ||
      ops.iter().last().is_none()
     ,"ops:{ops:?}");*/
     //println!("ops:{ops:?}");
-    Ok(ops)
+    Ok((ops, opcodes))
+}
+#[test]
+fn sieve_init_branches() {
+    let mut file = std::fs::File::open("test/Sieve.class").unwrap();
+    let class = crate::import_class_file(&mut file).unwrap();
+    let init = class
+        .methods()
+        .iter()
+        .find(|method| class.get_utf8(method.name()) == Some("Init"))
+        .unwrap();
+    let code = init.code().unwrap();
+    let offsets: Vec<_> = code.instructions().map(|(offset, _)| offset).collect();
+    assert_eq!(offsets, [0, 1, 3, 6, 7, 8, 9, 10, 13, 16, 17, 18, 19, 22, 25]);
+    let (offset, cmp) = code.instructions().nth(7).unwrap();
+    assert!(cmp.is_branch() && !cmp.is_terminator());
+    assert_eq!(cmp.branch_targets(offset).as_slice(), [25]);
+    let goto = code.instruction_at(22).unwrap();
+    assert!(goto.is_branch() && goto.is_terminator());
+    assert_eq!(goto.branch_targets(22).as_slice(), [8]);
+    assert!(code.instruction_at(2).is_none());
+    let pool = class.constant_pool();
+    let mut depth = 0;
+    for (_, op) in code.instructions() {
+        let effect = pool.stack_effect(op).unwrap();
+        depth += effect.net();
+        assert!(depth <= code.max_stack() as i32);
+    }
+    assert_eq!(depth, 0);
+}
+#[test]
+fn malformed_switches() {
+    let load = |bytes: &[u8], code_length: u32| {
+        let err = load_ops(&mut OffsetReader::new(bytes, 0), code_length).unwrap_err();
+        let offset = err.offset();
+        (err.into_kind(), offset)
+    };
+    // lookupswitch with a negative pair count
+    let negative_pairs = [0xab, 0, 0, 0, 0, 0, 0, 0, 0xff, 0xff, 0xff, 0xff];
+    assert!(matches!(
+        load(&negative_pairs, negative_pairs.len() as u32),
+        (ImportErrorKind::InvalidSwitch, Some(0))
+    ));
+    // tableswitch with high < low
+    let inverted_range = [0xaa, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 0];
+    assert!(matches!(
+        load(&inverted_range, inverted_range.len() as u32),
+        (ImportErrorKind::InvalidSwitch, Some(0))
+    ));
+    // tableswitch claiming more offsets than there is code
+    let huge_range = [0xaa, 0, 0, 0, 0, 0, 0, 0, 0x80, 0, 0, 0, 0x7f, 0xff, 0xff, 0xff];
+    assert!(matches!(
+        load(&huge_range, huge_range.len() as u32),
+        (ImportErrorKind::InvalidSwitch, Some(0))
+    ));
+    assert!(matches!(
+        load(&[0x0, 0xb9, 0, 1, 1, 1], 6),
+        (ImportErrorKind::NonZeroPadding, Some(1))
+    ));
+    assert!(matches!(
+        load(&[0x11, 0, 1], 2),
+        (ImportErrorKind::InstructionPastEnd, Some(3))
+    ));
+    assert!(matches!(
+        load(&[0x0, 0x0, 0xca], 3),
+        (ImportErrorKind::UnknownOpcode(0xca), Some(2))
+    ));
+    assert!(matches!(
+        load(&[0xc4, 0x60, 0, 1], 4),
+        (ImportErrorKind::InvalidWide(0x60), Some(0))
+    ));
+    assert!(matches!(
+        load(&[], 0),
+        (ImportErrorKind::InvalidCodeLength(0), Some(0))
+    ));
+}
+#[test]
+fn wide_and_subroutine_ops() {
+    let code = [
+        0xc4, 0x15, 0x01, 0x00, // wide iload 256
+        0xc4, 0x84, 0x01, 0x2c, 0xfc, 0x18, // wide iinc 300 -1000
+        0xa8, 0x00, 0x0b, // jsr +11
+        0xc8, 0xff, 0xff, 0xff, 0xf3, // goto_w -13
+        0xc9, 0x00, 0x00, 0x00, 0x05, // jsr_w +5
+        0x4c, // astore_1
+        0xc4, 0xa9, 0x01, 0x01, // wide ret 257
+        0xa9, 0x01, // ret 1
+    ];
+    let (ops, opcodes) = load_ops(&mut OffsetReader::new(&code[..], 0), code.len() as u32).unwrap();
+    let offsets: Vec<_> = ops.iter().map(|(_, offset)| *offset).collect();
+    assert_eq!(offsets, [0, 4, 10, 13, 18, 23, 24, 28]);
+    let sizes: Vec<_> = ops
+        .iter()
+        .zip(opcodes)
+        .map(|((op, offset), opcode)| op.encoded_size(opcode, *offset))
+        .collect();
+    assert_eq!(sizes, [4, 6, 3, 5, 5, 1, 4, 2]);
+    assert!(matches!(ops[0].0, OpCode::ILoad(256)));
+    assert!(matches!(ops[1].0, OpCode::IInc(300, -1000)));
+    assert!(matches!(ops[2].0, OpCode::Jsr(11)));
+    assert!(matches!(ops[3].0, OpCode::GoTo(-13)));
+    assert!(matches!(ops[6].0, OpCode::Ret(257)));
+    assert_eq!(ops[2].0.branch_targets(10).as_slice(), [21]);
+    assert_eq!(ops[3].0.branch_targets(13).as_slice(), [0]);
+    assert_eq!(ops[4].0.branch_targets(18).as_slice(), [23]);
+    assert!(ops[2].0.is_branch() && !ops[2].0.is_terminator());
+    assert!(!ops[7].0.is_branch() && ops[7].0.is_terminator());
+    assert_eq!(ops[4].0.stack_effect(), Some(StackEffect::new(0, 1)));
+}
+#[test]
+fn switch_cases() {
+    let table = [
+        0x00, // nop
+        0xaa, 0, 0, // tableswitch, padded to offset 4
+        0, 0, 0, 30, // default
+        0xff, 0xff, 0xff, 0xfe, // low = -2
+        0, 0, 0, 1, // high = 1
+        0, 0, 0, 40, 0, 0, 0, 41, 0, 0, 0, 42, 0, 0, 0, 43,
+    ];
+    let (ops, _) = load_ops(&mut OffsetReader::new(&table[..], 0), table.len() as u32).unwrap();
+    let (switch, offset) = &ops[1];
+    let OpCode::TableSwitch(table_switch) = switch else {
+        panic!("expected a tableswitch, got {switch:?}");
+    };
+    assert_eq!((table_switch.low(), table_switch.high()), (-2, 1));
+    let cases: Vec<_> = switch.switch_cases(*offset).collect();
+    assert_eq!(cases, [(-2, 41), (-1, 42), (0, 43), (1, 44)]);
+    assert_eq!(switch.branch_targets(*offset).as_slice(), [31, 41, 42, 43, 44]);
+    assert!(switch.is_terminator());
+    assert_eq!(switch.encoded_size(0xaa, *offset) as usize, table.len() - 1);
+    let lookup = [
+        0xab, 0, 0, 0, // lookupswitch, padded to offset 4
+        0, 0, 0, 20, // default
+        0, 0, 0, 2, // npairs
+        0, 0, 0, 7, 0, 0, 0, 24, 0, 0, 0x10, 0, 0xff, 0xff, 0xff, 0xff,
+    ];
+    let (ops, _) = load_ops(&mut OffsetReader::new(&lookup[..], 0), lookup.len() as u32).unwrap();
+    let cases: Vec<_> = ops[0].0.switch_cases(0).collect();
+    assert_eq!(cases, [(7, 24)]);
+    assert_eq!(OpCode::Nop.switch_cases(0).count(), 0);
 }
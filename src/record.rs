@@ -0,0 +1,102 @@
+use crate::annotation::{annotations, type_annotations, Annotation, TypeAnnotation};
+use crate::attribute::Attribute;
+use crate::constant_pool::ConstantPool;
+use crate::error::JavaImportError;
+use crate::import::{load_u16, store_u16, ConstantItem, OffsetReader, Utf8};
+/// Component of a record class, as stored in its `Record` attribute, e.g. `int x` in `record Point(int x, int y)`.
+#[derive(Debug)]
+pub struct RecordComponent {
+    pub(crate) name_index: u16,
+    pub(crate) descriptor_index: u16,
+    pub(crate) attributes: Box<[(u16, Attribute)]>,
+}
+impl RecordComponent {
+    pub(crate) fn read<R: std::io::Read>(
+        src: &mut OffsetReader<R>,
+        const_items: &[ConstantItem],
+    ) -> Result<Self, JavaImportError> {
+        let name_index = load_u16(src)?;
+        let descriptor_index = load_u16(src)?;
+        let name = ConstantPool::new(const_items).utf8(name_index);
+        let attributes_count = load_u16(src)?;
+        let mut attributes = Vec::with_capacity(attributes_count as usize);
+        for _ in 0..attributes_count {
+            attributes.push(Attribute::read_nested(src, const_items).map_err(|err| err.in_member(name))?);
+        }
+        Ok(Self {
+            name_index,
+            descriptor_index,
+            attributes: attributes.into(),
+        })
+    }
+    pub(crate) fn write<W: std::io::Write>(&self, dst: &mut W) -> std::io::Result<()> {
+        store_u16(dst, self.name_index)?;
+        store_u16(dst, self.descriptor_index)?;
+        Attribute::write_all(&self.attributes, dst)
+    }
+    pub fn name(&self) -> Utf8 {
+        Utf8(self.name_index)
+    }
+    /// Erased field descriptor of this component, e.g. `Ljava/util/List;`.
+    pub fn descriptor(&self) -> Utf8 {
+        Utf8(self.descriptor_index)
+    }
+    /// Generic signature of this component, e.g. `Ljava/util/List<TT;>;`, if its type is generic.
+    pub fn signature(&self) -> Option<Utf8> {
+        self.attributes.iter().find_map(|(_, attribute)| match attribute {
+            Attribute::Signature { signature } => Some(Utf8(*signature)),
+            _ => None,
+        })
+    }
+    /// Annotations with `RUNTIME` retention. Only annotations which target record components are stored here,
+    /// others are propagated to the field, accessor or constructor parameter.
+    pub fn annotations(&self) -> &[Annotation] {
+        annotations(&self.attributes, true)
+    }
+    /// Annotations with `CLASS` retention.
+    pub fn invisible_annotations(&self) -> &[Annotation] {
+        annotations(&self.attributes, false)
+    }
+    /// Type annotations with `RUNTIME` retention on the type of this component.
+    pub fn type_annotations(&self) -> &[TypeAnnotation] {
+        type_annotations(&self.attributes, true)
+    }
+    /// Type annotations with `CLASS` retention on the type of this component.
+    pub fn invisible_type_annotations(&self) -> &[TypeAnnotation] {
+        type_annotations(&self.attributes, false)
+    }
+}
+#[test]
+fn record_components() {
+    let mut file = std::fs::File::open("test/Records.class").unwrap();
+    let class = crate::import_class_file(&mut file).unwrap();
+    let components = class.record_components().unwrap();
+    let names: Vec<_> = components
+        .iter()
+        .map(|component| {
+            (
+                class.get_utf8(component.name()).unwrap(),
+                class.get_utf8(component.descriptor()).unwrap(),
+                component.signature().and_then(|signature| class.get_utf8(signature)),
+            )
+        })
+        .collect();
+    assert_eq!(
+        names,
+        [
+            ("name", "Ljava/lang/String;", None),
+            ("values", "Ljava/util/List;", Some("Ljava/util/List<TT;>;")),
+            ("count", "I", None)
+        ]
+    );
+    let [key] = components[0].annotations() else {
+        panic!("expected a single annotation on `name`");
+    };
+    assert_eq!(class.get_utf8(key.type_descriptor()), Some("LRecords$Key;"));
+    assert!(components[1].annotations().is_empty());
+    let mut written = Vec::new();
+    class.write(&mut written).unwrap();
+    assert_eq!(written, std::fs::read("test/Records.class").unwrap());
+    let mut file = std::fs::File::open("test/Sieve.class").unwrap();
+    assert!(crate::import_class_file(&mut file).unwrap().record_components().is_none());
+}
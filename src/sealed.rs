@@ -0,0 +1,122 @@
+use crate::import::JavaClassFile;
+use crate::IString;
+use std::collections::HashMap;
+/// Reason why a set of classes does not form a closed sealed hierarchy, returned by [`check_sealed_hierarchy`].
+///
+/// Class names are internal names, e.g. `java/lang/Object`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SealedHierarchyError {
+    /// The sealed class permits a subclass which is not in the set.
+    MissingSubclass { sealed: IString, subclass: IString },
+    /// A permitted subclass does not directly extend or implement the sealed class.
+    NotASubclass { sealed: IString, subclass: IString },
+    /// A class directly extends or implements a sealed class which does not permit it.
+    NotPermitted { sealed: IString, subclass: IString },
+}
+impl std::fmt::Display for SealedHierarchyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::MissingSubclass { sealed, subclass } => {
+                write!(f, "{sealed} permits {subclass}, which is missing")
+            }
+            Self::NotASubclass { sealed, subclass } => {
+                write!(f, "{sealed} permits {subclass}, which does not extend it")
+            }
+            Self::NotPermitted { sealed, subclass } => write!(f, "{subclass} extends {sealed}, which does not permit it"),
+        }
+    }
+}
+impl std::error::Error for SealedHierarchyError {}
+/// Direct super class and interfaces of `class`.
+fn direct_supertypes(class: &JavaClassFile) -> impl Iterator<Item = &str> + '_ {
+    let pool = class.constant_pool();
+    std::iter::once(class.super_class())
+        .chain(class.interfaces())
+        .filter_map(move |super_type| pool.class_name(super_type.0))
+}
+/// Checks that `classes` form closed sealed hierarchies: each `sealed` class has all of its permitted subclasses
+/// in the set, which directly extend or implement it, and no other class in the set does.
+///
+/// A `match` over a sealed class is exhaustive if it covers each of its permitted subclasses, so code generated
+/// from such a set of classes can rely on it. Supertypes which are not in the set are not checked.
+pub fn check_sealed_hierarchy<'a>(
+    classes: impl IntoIterator<Item = &'a JavaClassFile>,
+) -> Result<(), SealedHierarchyError> {
+    let classes: Vec<_> = classes
+        .into_iter()
+        .filter_map(|class| Some((class.constant_pool().class_name(class.this_class)?, class)))
+        .collect();
+    let by_name: HashMap<_, _> = classes.iter().copied().collect();
+    for (name, class) in classes.iter() {
+        let pool = class.constant_pool();
+        for permitted in class.permitted_subclasses() {
+            let subclass = pool.class_name(permitted.0).unwrap_or_default();
+            let error = match by_name.get(subclass) {
+                None => SealedHierarchyError::MissingSubclass {
+                    sealed: (*name).into(),
+                    subclass: subclass.into(),
+                },
+                Some(permitted) if !direct_supertypes(permitted).any(|super_type| super_type == *name) => {
+                    SealedHierarchyError::NotASubclass {
+                        sealed: (*name).into(),
+                        subclass: subclass.into(),
+                    }
+                }
+                Some(_) => continue,
+            };
+            return Err(error);
+        }
+        for super_type in direct_supertypes(class) {
+            let Some(sealed) = by_name.get(super_type).filter(|super_class| super_class.is_sealed()) else {
+                continue;
+            };
+            let sealed_pool = sealed.constant_pool();
+            if !sealed
+                .permitted_subclasses()
+                .any(|permitted| sealed_pool.class_name(permitted.0) == Some(name))
+            {
+                return Err(SealedHierarchyError::NotPermitted {
+                    sealed: super_type.into(),
+                    subclass: (*name).into(),
+                });
+            }
+        }
+    }
+    Ok(())
+}
+#[test]
+fn sealed_hierarchy() {
+    let load = |path: &str| crate::import_class_file(std::fs::File::open(path).unwrap()).unwrap();
+    let sealed = load("test/Sealed.class");
+    assert!(sealed.is_sealed());
+    let permitted: Vec<_> = sealed
+        .permitted_subclasses()
+        .map(|class| sealed.constant_pool().class_name(class.0).unwrap())
+        .collect();
+    assert_eq!(permitted, ["Sealed$Circle", "Sealed$Square", "Sealed$Polygon"]);
+    let circle = load("test/Sealed$Circle.class");
+    let square = load("test/Sealed$Square.class");
+    let polygon = load("test/Sealed$Polygon.class");
+    assert!(!circle.is_sealed() && !polygon.is_sealed());
+    assert_eq!(check_sealed_hierarchy([&sealed, &circle, &square, &polygon]), Ok(()));
+    assert_eq!(
+        check_sealed_hierarchy([&sealed, &circle, &polygon]),
+        Err(SealedHierarchyError::MissingSubclass {
+            sealed: "Sealed".into(),
+            subclass: "Sealed$Square".into()
+        })
+    );
+    // Classes which javac would reject, made with the builder.
+    let fake_square = crate::ClassBuilder::new("Sealed$Square").build().unwrap();
+    assert!(matches!(
+        check_sealed_hierarchy([&sealed, &circle, &fake_square, &polygon]),
+        Err(SealedHierarchyError::NotASubclass { subclass, .. }) if &*subclass == "Sealed$Square"
+    ));
+    let mut builder = crate::ClassBuilder::new("Sealed$Triangle");
+    builder.interface("Sealed");
+    let triangle = builder.build().unwrap();
+    assert!(matches!(
+        check_sealed_hierarchy([&sealed, &circle, &square, &polygon, &triangle]),
+        Err(SealedHierarchyError::NotPermitted { subclass, .. }) if &*subclass == "Sealed$Triangle"
+    ));
+}
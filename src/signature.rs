@@ -0,0 +1,486 @@
+use crate::descriptor::{BaseType, DescriptorError};
+use crate::IString;
+/// Generic signature of a class: its type parameters, superclass and superinterfaces.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ClassSignature {
+    pub type_parameters: Box<[TypeParameter]>,
+    pub superclass: ClassTypeSignature,
+    pub interfaces: Box<[ClassTypeSignature]>,
+}
+/// Generic signature of a method.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct MethodSignature {
+    pub type_parameters: Box<[TypeParameter]>,
+    pub parameters: Box<[JavaTypeSignature]>,
+    /// `None` for `void` methods.
+    pub return_type: Option<JavaTypeSignature>,
+    /// Class types or type variables of the `throws` clause.
+    pub throws: Box<[ReferenceTypeSignature]>,
+}
+/// Declaration of a type parameter, e.g. `T extends Number & Comparable<T>`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct TypeParameter {
+    pub name: IString,
+    /// `None` if the first bound is an interface, e.g. `T::Ljava/lang/Comparable<TT;>;`.
+    pub class_bound: Option<ReferenceTypeSignature>,
+    pub interface_bounds: Box<[ReferenceTypeSignature]>,
+}
+/// Any type which can appear in a signature, including primitive types.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum JavaTypeSignature {
+    Base(BaseType),
+    Reference(ReferenceTypeSignature),
+}
+/// A reference type. Generic signatures of fields are always reference types.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ReferenceTypeSignature {
+    Class(ClassTypeSignature),
+    /// Use of a type variable, e.g. `TT;`.
+    TypeVariable(IString),
+    Array(Box<JavaTypeSignature>),
+}
+/// A possibly parameterized class type, e.g. `Ljava/util/Map<TK;TV;>.Entry<TK;TV;>;`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ClassTypeSignature {
+    /// Package of the class with `/` as separator, e.g. `java/util`. Empty for the default package.
+    pub package: IString,
+    /// The outermost class first, followed by each nested class.
+    pub segments: Box<[SimpleClassTypeSignature]>,
+}
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SimpleClassTypeSignature {
+    pub name: IString,
+    pub type_arguments: Box<[TypeArgument]>,
+}
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum TypeArgument {
+    /// `?`
+    Any,
+    Exact(ReferenceTypeSignature),
+    /// `? extends`
+    Extends(ReferenceTypeSignature),
+    /// `? super`
+    Super(ReferenceTypeSignature),
+}
+impl ClassTypeSignature {
+    /// Internal name of the erased class, e.g. `java/util/Map$Entry`.
+    pub fn internal_name(&self) -> String {
+        let mut name = String::from(&*self.package);
+        if !name.is_empty() {
+            name.push('/');
+        }
+        for (index, segment) in self.segments.iter().enumerate() {
+            if index > 0 {
+                name.push('$');
+            }
+            name.push_str(&segment.name);
+        }
+        name
+    }
+}
+/// Type arguments nested deeper than this are rejected, so a malicious signature can't overflow the stack.
+const MAX_DEPTH: u16 = 256;
+struct Parser<'a> {
+    src: &'a str,
+    pos: usize,
+    /// Number of type argument lists the parser is currently inside of.
+    depth: u16,
+}
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<char> {
+        self.src[self.pos..].chars().next()
+    }
+    fn next(&mut self) -> Result<char, DescriptorError> {
+        let c = self.peek().ok_or(DescriptorError::UnexpectedEnd)?;
+        self.pos += c.len_utf8();
+        Ok(c)
+    }
+    fn expect(&mut self, expected: char) -> Result<(), DescriptorError> {
+        let pos = self.pos;
+        match self.next()? {
+            c if c == expected => Ok(()),
+            c => Err(DescriptorError::UnexpectedChar(c, pos)),
+        }
+    }
+    fn eat(&mut self, expected: char) -> bool {
+        if self.peek() == Some(expected) {
+            self.pos += expected.len_utf8();
+            true
+        } else {
+            false
+        }
+    }
+    fn finish(&self) -> Result<(), DescriptorError> {
+        if self.pos != self.src.len() {
+            return Err(DescriptorError::TrailingCharacters(self.pos));
+        }
+        Ok(())
+    }
+    fn identifier(&mut self) -> Result<&'a str, DescriptorError> {
+        let start = self.pos;
+        let len = self.src[start..]
+            .find(['.', ';', '[', '/', '<', '>', ':'])
+            .unwrap_or(self.src.len() - start);
+        if len == 0 {
+            return match self.peek() {
+                Some(c) => Err(DescriptorError::UnexpectedChar(c, start)),
+                None => Err(DescriptorError::UnexpectedEnd),
+            };
+        }
+        self.pos += len;
+        Ok(&self.src[start..start + len])
+    }
+    fn type_parameters(&mut self) -> Result<Box<[TypeParameter]>, DescriptorError> {
+        let mut type_parameters = Vec::new();
+        if !self.eat('<') {
+            return Ok(type_parameters.into());
+        }
+        loop {
+            let name = self.identifier()?.into();
+            self.expect(':')?;
+            let class_bound = match self.peek() {
+                Some(':') | Some('>') => None,
+                _ => Some(self.reference_type()?),
+            };
+            let mut interface_bounds = Vec::new();
+            while self.eat(':') {
+                interface_bounds.push(self.reference_type()?);
+            }
+            type_parameters.push(TypeParameter {
+                name,
+                class_bound,
+                interface_bounds: interface_bounds.into(),
+            });
+            if self.eat('>') {
+                return Ok(type_parameters.into());
+            }
+        }
+    }
+    fn java_type(&mut self) -> Result<JavaTypeSignature, DescriptorError> {
+        match self.peek().and_then(BaseType::from_char) {
+            Some(base) => {
+                self.pos += 1;
+                Ok(JavaTypeSignature::Base(base))
+            }
+            None => Ok(JavaTypeSignature::Reference(self.reference_type()?)),
+        }
+    }
+    fn reference_type(&mut self) -> Result<ReferenceTypeSignature, DescriptorError> {
+        let pos = self.pos;
+        match self.peek() {
+            Some('L') => Ok(ReferenceTypeSignature::Class(self.class_type()?)),
+            Some('T') => {
+                self.pos += 1;
+                let name = self.identifier()?.into();
+                self.expect(';')?;
+                Ok(ReferenceTypeSignature::TypeVariable(name))
+            }
+            Some('[') => {
+                let mut dimensions = 0;
+                while self.eat('[') {
+                    dimensions += 1;
+                }
+                if dimensions > 255 {
+                    return Err(DescriptorError::TooManyDimensions);
+                }
+                let mut element = self.java_type()?;
+                for _ in 1..dimensions {
+                    element = JavaTypeSignature::Reference(ReferenceTypeSignature::Array(
+                        Box::new(element),
+                    ));
+                }
+                Ok(ReferenceTypeSignature::Array(Box::new(element)))
+            }
+            Some(c) => Err(DescriptorError::UnexpectedChar(c, pos)),
+            None => Err(DescriptorError::UnexpectedEnd),
+        }
+    }
+    fn class_type(&mut self) -> Result<ClassTypeSignature, DescriptorError> {
+        self.expect('L')?;
+        let package_start = self.pos;
+        let mut package_end = self.pos;
+        let mut name = self.identifier()?;
+        while self.eat('/') {
+            package_end = self.pos - 1;
+            name = self.identifier()?;
+        }
+        let package = self.src[package_start..package_end].into();
+        let mut segments = vec![self.simple_class_type(name)?];
+        while self.eat('.') {
+            let name = self.identifier()?;
+            segments.push(self.simple_class_type(name)?);
+        }
+        self.expect(';')?;
+        Ok(ClassTypeSignature {
+            package,
+            segments: segments.into(),
+        })
+    }
+    fn simple_class_type(
+        &mut self,
+        name: &str,
+    ) -> Result<SimpleClassTypeSignature, DescriptorError> {
+        let mut type_arguments = Vec::new();
+        if self.eat('<') {
+            if self.depth >= MAX_DEPTH {
+                return Err(DescriptorError::TooDeeplyNested);
+            }
+            self.depth += 1;
+            loop {
+                let type_argument = match self.peek() {
+                    Some('*') => {
+                        self.pos += 1;
+                        TypeArgument::Any
+                    }
+                    Some('+') => {
+                        self.pos += 1;
+                        TypeArgument::Extends(self.reference_type()?)
+                    }
+                    Some('-') => {
+                        self.pos += 1;
+                        TypeArgument::Super(self.reference_type()?)
+                    }
+                    _ => TypeArgument::Exact(self.reference_type()?),
+                };
+                type_arguments.push(type_argument);
+                if self.eat('>') {
+                    break;
+                }
+            }
+            self.depth -= 1;
+        }
+        Ok(SimpleClassTypeSignature {
+            name: name.into(),
+            type_arguments: type_arguments.into(),
+        })
+    }
+}
+impl ClassSignature {
+    /// Parses the `Signature` attribute of a class.
+    pub fn parse(src: &str) -> Result<Self, DescriptorError> {
+        let mut parser = Parser {
+            src,
+            pos: 0,
+            depth: 0,
+        };
+        let type_parameters = parser.type_parameters()?;
+        let superclass = parser.class_type()?;
+        let mut interfaces = Vec::new();
+        while parser.peek().is_some() {
+            interfaces.push(parser.class_type()?);
+        }
+        Ok(Self {
+            type_parameters,
+            superclass,
+            interfaces: interfaces.into(),
+        })
+    }
+}
+impl MethodSignature {
+    /// Parses the `Signature` attribute of a method.
+    pub fn parse(src: &str) -> Result<Self, DescriptorError> {
+        let mut parser = Parser {
+            src,
+            pos: 0,
+            depth: 0,
+        };
+        let type_parameters = parser.type_parameters()?;
+        parser.expect('(')?;
+        let mut parameters = Vec::new();
+        while !parser.eat(')') {
+            parameters.push(parser.java_type()?);
+        }
+        let return_type = if parser.eat('V') {
+            None
+        } else {
+            Some(parser.java_type()?)
+        };
+        let mut throws = Vec::new();
+        while parser.eat('^') {
+            let pos = parser.pos;
+            match parser.reference_type()? {
+                ReferenceTypeSignature::Array(_) => {
+                    return Err(DescriptorError::UnexpectedChar('[', pos))
+                }
+                thrown => throws.push(thrown),
+            }
+        }
+        parser.finish()?;
+        Ok(Self {
+            type_parameters,
+            parameters: parameters.into(),
+            return_type,
+            throws: throws.into(),
+        })
+    }
+}
+impl ReferenceTypeSignature {
+    /// Parses the `Signature` attribute of a field or record component.
+    pub fn parse(src: &str) -> Result<Self, DescriptorError> {
+        let mut parser = Parser {
+            src,
+            pos: 0,
+            depth: 0,
+        };
+        let reference_type = parser.reference_type()?;
+        parser.finish()?;
+        Ok(reference_type)
+    }
+}
+fn write_type_parameters(
+    f: &mut std::fmt::Formatter<'_>,
+    type_parameters: &[TypeParameter],
+) -> std::fmt::Result {
+    if type_parameters.is_empty() {
+        return Ok(());
+    }
+    write!(f, "<")?;
+    for type_parameter in type_parameters {
+        write!(f, "{}:", type_parameter.name)?;
+        if let Some(class_bound) = &type_parameter.class_bound {
+            write!(f, "{class_bound}")?;
+        }
+        for interface_bound in type_parameter.interface_bounds.iter() {
+            write!(f, ":{interface_bound}")?;
+        }
+    }
+    write!(f, ">")
+}
+impl std::fmt::Display for ClassSignature {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write_type_parameters(f, &self.type_parameters)?;
+        write!(f, "{}", self.superclass)?;
+        for interface in self.interfaces.iter() {
+            write!(f, "{interface}")?;
+        }
+        Ok(())
+    }
+}
+impl std::fmt::Display for MethodSignature {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write_type_parameters(f, &self.type_parameters)?;
+        write!(f, "(")?;
+        for parameter in self.parameters.iter() {
+            write!(f, "{parameter}")?;
+        }
+        write!(f, ")")?;
+        match &self.return_type {
+            Some(return_type) => write!(f, "{return_type}")?,
+            None => write!(f, "V")?,
+        }
+        for thrown in self.throws.iter() {
+            write!(f, "^{thrown}")?;
+        }
+        Ok(())
+    }
+}
+impl std::fmt::Display for JavaTypeSignature {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Base(base) => write!(f, "{}", base.as_char()),
+            Self::Reference(reference) => write!(f, "{reference}"),
+        }
+    }
+}
+impl std::fmt::Display for ReferenceTypeSignature {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Class(class) => write!(f, "{class}"),
+            Self::TypeVariable(name) => write!(f, "T{name};"),
+            Self::Array(element) => write!(f, "[{element}"),
+        }
+    }
+}
+impl std::fmt::Display for ClassTypeSignature {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "L")?;
+        if !self.package.is_empty() {
+            write!(f, "{}/", self.package)?;
+        }
+        for (index, segment) in self.segments.iter().enumerate() {
+            if index > 0 {
+                write!(f, ".")?;
+            }
+            write!(f, "{}", segment.name)?;
+            if !segment.type_arguments.is_empty() {
+                write!(f, "<")?;
+                for type_argument in segment.type_arguments.iter() {
+                    match type_argument {
+                        TypeArgument::Any => write!(f, "*")?,
+                        TypeArgument::Exact(reference) => write!(f, "{reference}")?,
+                        TypeArgument::Extends(reference) => write!(f, "+{reference}")?,
+                        TypeArgument::Super(reference) => write!(f, "-{reference}")?,
+                    }
+                }
+                write!(f, ">")?;
+            }
+        }
+        write!(f, ";")
+    }
+}
+#[test]
+fn parse_signatures() {
+    let class = "<K::Ljava/lang/Comparable<TK;>;V:Ljava/lang/Object;>Ljava/util/AbstractMap<TK;TV;>;Ljava/io/Serializable;";
+    let parsed = ClassSignature::parse(class).unwrap();
+    assert_eq!(parsed.to_string(), class);
+    assert_eq!(parsed.type_parameters.len(), 2);
+    assert_eq!(&*parsed.type_parameters[0].name, "K");
+    assert!(parsed.type_parameters[0].class_bound.is_none());
+    assert_eq!(parsed.superclass.internal_name(), "java/util/AbstractMap");
+    assert_eq!(parsed.interfaces[0].internal_name(), "java/io/Serializable");
+
+    let method = "<T:Ljava/lang/Exception;>(Ljava/util/List<+Ljava/lang/Number;>;[Ljava/util/Map$Entry<TK;-TV;>;I)[TT;^TT;^Ljava/io/IOException;";
+    let parsed = MethodSignature::parse(method).unwrap();
+    assert_eq!(parsed.to_string(), method);
+    assert_eq!(parsed.parameters.len(), 3);
+    assert_eq!(parsed.parameters[2], JavaTypeSignature::Base(BaseType::Int));
+    assert_eq!(parsed.throws.len(), 2);
+    assert_eq!(
+        parsed.throws[0],
+        ReferenceTypeSignature::TypeVariable("T".into())
+    );
+
+    let field = "Lcom/example/Outer<Ljava/lang/String;>.Inner<*>;";
+    let ReferenceTypeSignature::Class(parsed) = ReferenceTypeSignature::parse(field).unwrap()
+    else {
+        panic!("Expected a class type");
+    };
+    assert_eq!(&*parsed.package, "com/example");
+    assert_eq!(parsed.segments.len(), 2);
+    assert_eq!(parsed.segments[1].type_arguments[..], [TypeArgument::Any]);
+    assert_eq!(parsed.internal_name(), "com/example/Outer$Inner");
+    assert_eq!(parsed.to_string(), field);
+
+    assert_eq!(
+        ReferenceTypeSignature::parse("I"),
+        Err(DescriptorError::UnexpectedChar('I', 0))
+    );
+    assert_eq!(
+        MethodSignature::parse("()V^[I"),
+        Err(DescriptorError::UnexpectedChar('[', 4))
+    );
+    assert!(ClassSignature::parse("<T:>Ljava/lang/Object;").is_ok());
+    assert!(ClassSignature::parse("Ljava/util/List<>;").is_err());
+    assert!(MethodSignature::parse("(TT)V").is_err());
+    let nested = |depth: usize| format!("{}Ljava/lang/Object;{}", "Ljava/util/List<".repeat(depth), ">;".repeat(depth));
+    assert!(ReferenceTypeSignature::parse(&nested(256)).is_ok());
+    assert_eq!(ReferenceTypeSignature::parse(&nested(257)), Err(DescriptorError::TooDeeplyNested));
+    assert_eq!(
+        ReferenceTypeSignature::parse(&"LA<".repeat(20000)),
+        Err(DescriptorError::TooDeeplyNested)
+    );
+}
+#[test]
+fn parse_class_file_signatures() {
+    let mut file = std::fs::File::open("test/ConstantPool.class").unwrap();
+    let class = crate::import_class_file(&mut file).unwrap();
+    let size = &class.methods()[1];
+    let signature = class.get_utf8(size.signature().unwrap()).unwrap();
+    let parsed = MethodSignature::parse(signature).unwrap();
+    let JavaTypeSignature::Reference(ReferenceTypeSignature::Class(list)) = &parsed.parameters[0]
+    else {
+        panic!("Expected a class type");
+    };
+    assert_eq!(list.internal_name(), "java/util/List");
+    assert_eq!(parsed.to_string(), signature);
+}
@@ -0,0 +1,994 @@
+use crate::attribute::{Code, CodeException};
+use crate::builder::{BuildError, ConstantPoolBuilder};
+use crate::constant_pool::{Constant, ConstantPool};
+use crate::descriptor::{BaseType, FieldType, MethodDescriptor};
+use crate::error::{ImportErrorKind, JavaImportError};
+use crate::import::{load_u16, load_u8, store_len_u16, store_u16, store_u8, ClassInfo, ConstantItem, OffsetReader};
+use crate::opcodes::OpCode;
+use crate::IString;
+/// Type of a local variable or stack entry in a [`StackMapFrame`]. `long` and `double` values take a single entry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VerificationType {
+    Top,
+    Integer,
+    Float,
+    Double,
+    Long,
+    Null,
+    /// `this` in a constructor, before the constructor of the super class was called.
+    UninitializedThis,
+    /// Instance of a class, or an array, e.g. `[I`.
+    Object(ClassInfo),
+    /// Object created by the `new` instruction at this offset, whose constructor was not called yet.
+    Uninitialized(u16),
+}
+/// Entry of a `StackMapTable`, giving the types of locals and stack entries at the start of an instruction.
+///
+/// Frames other than `Full` are relative to the locals of the previous frame, or of the method's arguments for the
+/// first frame.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StackMapFrame {
+    /// Same locals as the previous frame, and an empty stack. `extended` frames are written with a 2 byte offset
+    /// delta(`same_frame_extended`), even if it would fit in the frame type.
+    Same { offset: u16, extended: bool },
+    /// Same locals as the previous frame, and a single stack entry. `extended` frames are written with a 2 byte
+    /// offset delta(`same_locals_1_stack_item_frame_extended`), even if it would fit in the frame type.
+    SameLocals1StackItem {
+        offset: u16,
+        stack: VerificationType,
+        extended: bool,
+    },
+    /// The last `count` locals of the previous frame are removed, and the stack is empty. `count` must be 1 to 3.
+    Chop { offset: u16, count: u8 },
+    /// `locals` are added to the ones of the previous frame, and the stack is empty. There must be 1 to 3 of them.
+    Append { offset: u16, locals: Box<[VerificationType]> },
+    Full {
+        offset: u16,
+        locals: Box<[VerificationType]>,
+        stack: Box<[VerificationType]>,
+    },
+}
+impl VerificationType {
+    fn read<R: std::io::Read>(src: &mut OffsetReader<R>) -> Result<Self, JavaImportError> {
+        Ok(match load_u8(src)? {
+            0 => Self::Top,
+            1 => Self::Integer,
+            2 => Self::Float,
+            3 => Self::Double,
+            4 => Self::Long,
+            5 => Self::Null,
+            6 => Self::UninitializedThis,
+            7 => Self::Object(ClassInfo(load_u16(src)?)),
+            8 => Self::Uninitialized(load_u16(src)?),
+            _ => return Err(src.error(ImportErrorKind::InvalidStackMapFrame)),
+        })
+    }
+    fn read_list<R: std::io::Read>(
+        src: &mut OffsetReader<R>,
+        count: usize,
+    ) -> Result<Box<[Self]>, JavaImportError> {
+        (0..count).map(|_| Self::read(src)).collect()
+    }
+    fn write<W: std::io::Write>(&self, dst: &mut W) -> std::io::Result<()> {
+        match self {
+            Self::Top => store_u8(dst, 0),
+            Self::Integer => store_u8(dst, 1),
+            Self::Float => store_u8(dst, 2),
+            Self::Double => store_u8(dst, 3),
+            Self::Long => store_u8(dst, 4),
+            Self::Null => store_u8(dst, 5),
+            Self::UninitializedThis => store_u8(dst, 6),
+            Self::Object(class) => {
+                store_u8(dst, 7)?;
+                store_u16(dst, class.0)
+            }
+            Self::Uninitialized(offset) => {
+                store_u8(dst, 8)?;
+                store_u16(dst, *offset)
+            }
+        }
+    }
+    fn write_list<W: std::io::Write>(list: &[Self], dst: &mut W) -> std::io::Result<()> {
+        list.iter().try_for_each(|entry| entry.write(dst))
+    }
+}
+impl StackMapFrame {
+    /// Offset of the instruction this frame describes.
+    pub fn offset(&self) -> u16 {
+        match self {
+            Self::Same { offset, .. }
+            | Self::SameLocals1StackItem { offset, .. }
+            | Self::Chop { offset, .. }
+            | Self::Append { offset, .. }
+            | Self::Full { offset, .. } => *offset,
+        }
+    }
+    /// Reads the body of a `StackMapTable` attribute, turning offset deltas into absolute offsets.
+    pub(crate) fn read_table<R: std::io::Read>(
+        src: &mut OffsetReader<R>,
+    ) -> Result<Box<[Self]>, JavaImportError> {
+        let count = load_u16(src)? as usize;
+        let mut frames = Vec::with_capacity(count);
+        let mut previous: Option<u16> = None;
+        for _ in 0..count {
+            let frame_type = load_u8(src)?;
+            let delta = match frame_type {
+                0..=127 => frame_type as u16 % 64,
+                128..=246 => return Err(src.error(ImportErrorKind::InvalidStackMapFrame)),
+                _ => load_u16(src)?,
+            };
+            // Each frame after the first is at least one byte after the previous one.
+            let offset = match previous {
+                None => Some(delta),
+                Some(previous) => previous.checked_add(delta).and_then(|offset| offset.checked_add(1)),
+            };
+            let Some(offset) = offset else {
+                return Err(src.error(ImportErrorKind::InvalidStackMapFrame));
+            };
+            previous = Some(offset);
+            frames.push(match frame_type {
+                0..=63 | 251 => Self::Same {
+                    offset,
+                    extended: frame_type == 251,
+                },
+                64..=127 | 247 => Self::SameLocals1StackItem {
+                    offset,
+                    stack: VerificationType::read(src)?,
+                    extended: frame_type == 247,
+                },
+                248..=250 => Self::Chop {
+                    offset,
+                    count: 251 - frame_type,
+                },
+                252..=254 => Self::Append {
+                    offset,
+                    locals: VerificationType::read_list(src, frame_type as usize - 251)?,
+                },
+                _ => {
+                    let locals_count = load_u16(src)? as usize;
+                    let locals = VerificationType::read_list(src, locals_count)?;
+                    let stack_count = load_u16(src)? as usize;
+                    let stack = VerificationType::read_list(src, stack_count)?;
+                    Self::Full { offset, locals, stack }
+                }
+            });
+        }
+        Ok(frames.into())
+    }
+    /// Writes the body of a `StackMapTable` attribute. Frames are written in the shortest encoding, unless they are
+    /// marked as `extended`, so frames which were read are written back unchanged.
+    pub(crate) fn write_table<W: std::io::Write>(frames: &[Self], dst: &mut W) -> std::io::Result<()> {
+        store_len_u16(dst, frames.len())?;
+        let mut previous: Option<u16> = None;
+        for frame in frames {
+            let offset = frame.offset();
+            let delta = match previous {
+                None => offset,
+                Some(previous) => offset.wrapping_sub(previous).wrapping_sub(1),
+            };
+            previous = Some(offset);
+            match frame {
+                Self::Same { extended: false, .. } if delta < 64 => store_u8(dst, delta as u8)?,
+                Self::Same { .. } => {
+                    store_u8(dst, 251)?;
+                    store_u16(dst, delta)?;
+                }
+                Self::SameLocals1StackItem { stack, extended, .. } => {
+                    if delta < 64 && !extended {
+                        store_u8(dst, 64 + delta as u8)?;
+                    } else {
+                        store_u8(dst, 247)?;
+                        store_u16(dst, delta)?;
+                    }
+                    stack.write(dst)?;
+                }
+                Self::Chop { count, .. } => {
+                    if !(1..=3).contains(count) {
+                        return Err(std::io::Error::new(
+                            std::io::ErrorKind::InvalidInput,
+                            format!("chop frame can't remove {count} locals"),
+                        ));
+                    }
+                    store_u8(dst, 251 - count)?;
+                    store_u16(dst, delta)?;
+                }
+                Self::Append { locals, .. } => {
+                    if !(1..=3).contains(&locals.len()) {
+                        return Err(std::io::Error::new(
+                            std::io::ErrorKind::InvalidInput,
+                            format!("append frame can't add {} locals", locals.len()),
+                        ));
+                    }
+                    store_u8(dst, 251 + locals.len() as u8)?;
+                    store_u16(dst, delta)?;
+                    VerificationType::write_list(locals, dst)?;
+                }
+                Self::Full { locals, stack, .. } => {
+                    store_u8(dst, 255)?;
+                    store_u16(dst, delta)?;
+                    store_len_u16(dst, locals.len())?;
+                    VerificationType::write_list(locals, dst)?;
+                    store_len_u16(dst, stack.len())?;
+                    VerificationType::write_list(stack, dst)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+/// Type of a single local or stack slot while frames are computed. `Long` and `Double` are followed by a `Top`
+/// slot, so `dup2` and friends can move slots without knowing what they hold.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum SlotType {
+    Top,
+    Integer,
+    Float,
+    Long,
+    Double,
+    Null,
+    UninitializedThis,
+    Uninitialized(u16),
+    Object(IString),
+}
+impl SlotType {
+    fn from_field_type(field_type: &FieldType) -> Self {
+        match field_type {
+            FieldType::Base(BaseType::Float) => Self::Float,
+            FieldType::Base(BaseType::Long) => Self::Long,
+            FieldType::Base(BaseType::Double) => Self::Double,
+            FieldType::Base(_) => Self::Integer,
+            FieldType::Object(name) => Self::Object(name.clone()),
+            FieldType::Array(_) => Self::Object(field_type.to_string().into()),
+        }
+    }
+    fn from_descriptor(descriptor: &str) -> Option<Self> {
+        Some(Self::from_field_type(&FieldType::parse(descriptor).ok()?))
+    }
+    fn is_wide(&self) -> bool {
+        matches!(self, Self::Long | Self::Double)
+    }
+}
+/// Types of the locals and the stack at the start of an instruction.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct State {
+    locals: Vec<SlotType>,
+    stack: Vec<SlotType>,
+}
+impl State {
+    fn push(&mut self, slot: SlotType) {
+        let wide = slot.is_wide();
+        self.stack.push(slot);
+        if wide {
+            self.stack.push(SlotType::Top);
+        }
+    }
+    fn pop(&mut self, slots: usize, offset: u16) -> Result<(), BuildError> {
+        let Some(len) = self.stack.len().checked_sub(slots) else {
+            return Err(BuildError::StackUnderflow(offset));
+        };
+        self.stack.truncate(len);
+        Ok(())
+    }
+    fn pop_slot(&mut self, offset: u16) -> Result<SlotType, BuildError> {
+        self.stack.pop().ok_or(BuildError::StackUnderflow(offset))
+    }
+    fn local(&self, index: u16) -> SlotType {
+        self.locals.get(index as usize).cloned().unwrap_or(SlotType::Top)
+    }
+    fn store(&mut self, index: u16, slot: SlotType) {
+        let index = index as usize;
+        let end = index + 1 + slot.is_wide() as usize;
+        if self.locals.len() < end {
+            self.locals.resize(end, SlotType::Top);
+        }
+        // Overwriting the second half of a `long` or `double` invalidates the first.
+        if index > 0 && self.locals[index - 1].is_wide() {
+            self.locals[index - 1] = SlotType::Top;
+        }
+        if slot.is_wide() {
+            self.locals[index + 1] = SlotType::Top;
+        }
+        self.locals[index] = slot;
+    }
+    /// Inserts a copy of the top `copy` slots below the top `depth` slots, as done by `dup` and its variants.
+    fn dup(&mut self, depth: usize, copy: usize, offset: u16) -> Result<(), BuildError> {
+        let Some(start) = self.stack.len().checked_sub(depth) else {
+            return Err(BuildError::StackUnderflow(offset));
+        };
+        let copied = self.stack[self.stack.len() - copy..].to_vec();
+        self.stack.splice(start..start, copied);
+        Ok(())
+    }
+    /// Entries of a frame, with `long` and `double` taking a single entry.
+    fn entries(slots: &[SlotType]) -> Vec<&SlotType> {
+        let mut entries = Vec::with_capacity(slots.len());
+        let mut slots = slots.iter();
+        while let Some(slot) = slots.next() {
+            if slot.is_wide() {
+                slots.next();
+            }
+            entries.push(slot);
+        }
+        entries
+    }
+}
+/// Super types of classes, used to merge the types of locals and stack entries when stack map frames are
+/// computed. Classes it knows nothing about are treated as direct subclasses of `java/lang/Object`.
+///
+/// Closures returning the super class of a class implement it, without knowledge of interfaces.
+pub trait ClassHierarchy {
+    /// Super class of `class`, `None` for `java/lang/Object` and unknown classes.
+    fn super_class(&self, class: &str) -> Option<IString>;
+    /// Interfaces `class` directly implements, or extends if it is an interface itself.
+    fn interfaces(&self, _class: &str) -> Vec<IString> {
+        Vec::new()
+    }
+}
+impl<F: Fn(&str) -> Option<IString>> ClassHierarchy for F {
+    fn super_class(&self, class: &str) -> Option<IString> {
+        self(class)
+    }
+}
+/// Method whose frames are computed.
+pub(crate) struct FrameContext<'a> {
+    pub(crate) this_class: &'a str,
+    pub(crate) super_class: &'a str,
+    pub(crate) interfaces: &'a [IString],
+    pub(crate) method_name: &'a str,
+    pub(crate) descriptor: &'a MethodDescriptor,
+    pub(crate) is_static: bool,
+    /// Super types of classes other than `this_class`.
+    pub(crate) hierarchy: &'a dyn ClassHierarchy,
+}
+struct Analyzer<'a> {
+    ops: &'a [(OpCode, u16)],
+    pool: ConstantPool<'a>,
+    context: &'a FrameContext<'a>,
+}
+impl<'a> Analyzer<'a> {
+    fn index_of(&self, offset: u16) -> Result<usize, BuildError> {
+        self.ops
+            .binary_search_by_key(&offset, |(_, op_offset)| *op_offset)
+            .map_err(|_| BuildError::InvalidBranchTarget(offset))
+    }
+    fn initial_state(&self) -> State {
+        let mut state = State {
+            locals: Vec::new(),
+            stack: Vec::new(),
+        };
+        if !self.context.is_static {
+            let this = if self.context.method_name == "<init>" && self.context.this_class != "java/lang/Object" {
+                SlotType::UninitializedThis
+            } else {
+                SlotType::Object(self.context.this_class.into())
+            };
+            state.locals.push(this);
+        }
+        for parameter in self.context.descriptor.parameters() {
+            let slot = SlotType::from_field_type(parameter);
+            let index = state.locals.len() as u16;
+            state.store(index, slot);
+        }
+        state
+    }
+    fn super_class(&self, class: &str) -> Option<IString> {
+        if class == self.context.this_class {
+            return Some(self.context.super_class.into());
+        }
+        self.context.hierarchy.super_class(class)
+    }
+    fn interfaces(&self, class: &str) -> Vec<IString> {
+        if class == self.context.this_class {
+            return self.context.interfaces.to_vec();
+        }
+        self.context.hierarchy.interfaces(class)
+    }
+    /// Names of `class` and all of its known super classes.
+    fn super_classes(&self, class: &str) -> Vec<IString> {
+        let mut chain: Vec<IString> = vec![class.into()];
+        // The length limits guard against cycles in a broken hierarchy.
+        while chain.len() < 256 {
+            match self.super_class(chain.last().unwrap()) {
+                Some(super_class) => chain.push(super_class),
+                None => break,
+            }
+        }
+        chain
+    }
+    /// Returns true if `to` is `from`, or one of its super classes or interfaces.
+    fn is_assignable(&self, from: &str, to: &str) -> bool {
+        let mut pending: Vec<IString> = vec![from.into()];
+        let mut seen = std::collections::HashSet::new();
+        while let Some(class) = pending.pop() {
+            if *class == *to {
+                return true;
+            }
+            if seen.len() > 1024 || !seen.insert(class.clone()) {
+                continue;
+            }
+            pending.extend(self.super_class(&class));
+            pending.extend(self.interfaces(&class));
+        }
+        false
+    }
+    /// Most specific type both `a` and `b` can be assigned to, as far as the verifier is concerned.
+    fn common_super_class(&self, a: &str, b: &str) -> IString {
+        const OBJECT: &str = "java/lang/Object";
+        match (a.strip_prefix('['), b.strip_prefix('[')) {
+            (Some(a), Some(b)) => {
+                // Arrays of references merge their element types, other arrays have nothing in common.
+                let (Some(a), Some(b)) = (reference_class(a), reference_class(b)) else {
+                    return OBJECT.into();
+                };
+                let element = self.common_super_class(a, b);
+                return match element.starts_with('[') {
+                    true => format!("[{element}").into(),
+                    false => format!("[L{element};").into(),
+                };
+            }
+            (None, None) => (),
+            _ => return OBJECT.into(),
+        }
+        if self.is_assignable(b, a) {
+            return a.into();
+        }
+        if self.is_assignable(a, b) {
+            return b.into();
+        }
+        let a = self.super_classes(a);
+        self.super_classes(b)
+            .into_iter()
+            .find(|class| a.contains(class))
+            .unwrap_or_else(|| OBJECT.into())
+    }
+    fn merge_slot(&self, a: &SlotType, b: &SlotType) -> SlotType {
+        match (a, b) {
+            _ if a == b => a.clone(),
+            (SlotType::Null, SlotType::Object(_)) => b.clone(),
+            (SlotType::Object(_), SlotType::Null) => a.clone(),
+            (SlotType::Object(a), SlotType::Object(b)) => SlotType::Object(self.common_super_class(a, b)),
+            _ => SlotType::Top,
+        }
+    }
+    /// Merges `incoming` into the state of the instruction at `index`. Returns true if the state changed.
+    fn merge(&self, states: &mut [Option<State>], index: usize, incoming: &State) -> Result<bool, BuildError> {
+        let Some(state) = &mut states[index] else {
+            states[index] = Some(incoming.clone());
+            return Ok(true);
+        };
+        if state.stack.len() != incoming.stack.len() {
+            return Err(BuildError::StackMismatch(self.ops[index].1));
+        }
+        let mut merged = State {
+            locals: Vec::with_capacity(state.locals.len()),
+            stack: Vec::with_capacity(state.stack.len()),
+        };
+        for index in 0..state.locals.len().max(incoming.locals.len()) {
+            let a = state.locals.get(index).unwrap_or(&SlotType::Top);
+            let b = incoming.locals.get(index).unwrap_or(&SlotType::Top);
+            merged.locals.push(self.merge_slot(a, b));
+        }
+        for (a, b) in state.stack.iter().zip(&incoming.stack) {
+            merged.stack.push(self.merge_slot(a, b));
+        }
+        // A `long` whose second half was merged away is no longer usable.
+        for slots in [&mut merged.locals, &mut merged.stack] {
+            for index in 0..slots.len() {
+                if slots[index].is_wide() && slots.get(index + 1) != Some(&SlotType::Top) {
+                    slots[index] = SlotType::Top;
+                }
+            }
+        }
+        if merged == *state {
+            return Ok(false);
+        }
+        *state = merged;
+        Ok(true)
+    }
+    fn class_name(&self, index: u16, offset: u16) -> Result<IString, BuildError> {
+        let name = self.pool.class_name(index);
+        name.map(Into::into).ok_or(BuildError::UnknownStackEffect(offset))
+    }
+    fn field_type(&self, index: u16, offset: u16) -> Result<SlotType, BuildError> {
+        let field = self.pool.field_ref(index).ok_or(BuildError::UnknownStackEffect(offset))?;
+        SlotType::from_descriptor(field.descriptor).ok_or(BuildError::UnknownStackEffect(offset))
+    }
+    fn constant_type(&self, index: u16, offset: u16) -> Result<SlotType, BuildError> {
+        let object = |name: &str| SlotType::Object(name.into());
+        // Only the kind of the constant matters, so strings which are not valid Unicode are fine.
+        Ok(match self.pool.item(index) {
+            Some(ConstantItem::Intiger(_)) => SlotType::Integer,
+            Some(ConstantItem::Float(_)) => SlotType::Float,
+            Some(ConstantItem::Long(_)) => SlotType::Long,
+            Some(ConstantItem::Double(_)) => SlotType::Double,
+            Some(ConstantItem::ConstString { .. }) => object("java/lang/String"),
+            Some(ConstantItem::Class { .. }) => object("java/lang/Class"),
+            Some(ConstantItem::MethodType { .. }) => object("java/lang/invoke/MethodType"),
+            Some(ConstantItem::MethodHandle { .. }) => object("java/lang/invoke/MethodHandle"),
+            Some(ConstantItem::Dynamic { .. }) => match self.pool.get(index) {
+                Some(Constant::Dynamic(dynamic)) => SlotType::from_descriptor(dynamic.descriptor)
+                    .ok_or(BuildError::UnknownStackEffect(offset))?,
+                _ => return Err(BuildError::UnknownStackEffect(offset)),
+            },
+            _ => return Err(BuildError::UnknownStackEffect(offset)),
+        })
+    }
+    /// Pops the arguments of a call and pushes its result.
+    fn invoke(&self, state: &mut State, descriptor: &str, offset: u16) -> Result<(), BuildError> {
+        let descriptor = MethodDescriptor::parse(descriptor).map_err(|_| BuildError::UnknownStackEffect(offset))?;
+        state.pop(descriptor.argument_slots() as usize, offset)?;
+        if let Some(return_type) = descriptor.return_type() {
+            state.push(SlotType::from_field_type(return_type));
+        }
+        Ok(())
+    }
+    /// Applies the effect of `op` to `state`.
+    fn execute(&self, op: &OpCode, offset: u16, state: &mut State) -> Result<(), BuildError> {
+        use SlotType as T;
+        let (pops, push) = match op {
+            OpCode::Nop | OpCode::GoTo(_) | OpCode::IInc(..) | OpCode::Return => (0, None),
+            OpCode::AConstNull => (0, Some(T::Null)),
+            OpCode::IConst(_) | OpCode::BIPush(_) | OpCode::SIPush(_) | OpCode::ILoad(_) => (0, Some(T::Integer)),
+            OpCode::LConst(_) | OpCode::LLoad(_) => (0, Some(T::Long)),
+            OpCode::FConst(_) | OpCode::FLoad(_) => (0, Some(T::Float)),
+            OpCode::DConst(_) | OpCode::DLoad(_) => (0, Some(T::Double)),
+            OpCode::LoadConst(index) => (0, Some(self.constant_type(*index, offset)?)),
+            OpCode::ALoad(index) => (0, Some(state.local(*index))),
+            OpCode::IALoad | OpCode::BALoad | OpCode::CALoad | OpCode::SALoad => (2, Some(T::Integer)),
+            OpCode::LALoad => (2, Some(T::Long)),
+            OpCode::FALoad => (2, Some(T::Float)),
+            OpCode::DALoad => (2, Some(T::Double)),
+            OpCode::AALoad => {
+                state.pop(1, offset)?;
+                let element = match state.pop_slot(offset)? {
+                    T::Object(array) if array.starts_with('[') => {
+                        // A malformed class file may name an array class like `[L`.
+                        match reference_class(&array[1..]).filter(|element| !element.is_empty()) {
+                            Some(element) => T::Object(element.into()),
+                            None => return Err(BuildError::UnknownStackEffect(offset)),
+                        }
+                    }
+                    _ => T::Null,
+                };
+                (0, Some(element))
+            }
+            OpCode::IStore(index) | OpCode::FStore(index) | OpCode::AStore(index) => {
+                let slot = match op {
+                    OpCode::IStore(_) => T::Integer,
+                    OpCode::FStore(_) => T::Float,
+                    _ => state.stack.last().cloned().unwrap_or(T::Top),
+                };
+                state.pop(1, offset)?;
+                state.store(*index, slot);
+                (0, None)
+            }
+            OpCode::LStore(index) | OpCode::DStore(index) => {
+                state.pop(2, offset)?;
+                let slot = if matches!(op, OpCode::LStore(_)) { T::Long } else { T::Double };
+                state.store(*index, slot);
+                (0, None)
+            }
+            OpCode::IAStore
+            | OpCode::BAStore
+            | OpCode::CAStore
+            | OpCode::SAStore
+            | OpCode::FAStore
+            | OpCode::AAStore => (3, None),
+            OpCode::LAStore | OpCode::DAStore => (4, None),
+            OpCode::Pop
+            | OpCode::IfZero(_)
+            | OpCode::IfNotZero(_)
+            | OpCode::IfLessZero(_)
+            | OpCode::IfGreterEqualZero(_)
+            | OpCode::IfGreterZero(_)
+            | OpCode::IfLessEqualZero(_)
+            | OpCode::IfNull(_)
+            | OpCode::IfNotNull(_)
+            | OpCode::TableSwitch(_)
+            | OpCode::LookupSwitch(_)
+            | OpCode::IReturn
+            | OpCode::FReturn
+            | OpCode::AReturn
+            | OpCode::Throw
+            | OpCode::MonitorEnter
+            | OpCode::MonitorExit => (1, None),
+            OpCode::Pop2
+            | OpCode::IfICmpEq(_)
+            | OpCode::IfICmpNe(_)
+            | OpCode::IfICmpLessThan(_)
+            | OpCode::IfIGreterEqual(_)
+            | OpCode::IfICmpGreater(_)
+            | OpCode::IfICmpLessEqual(_)
+            | OpCode::IfACmpEq(_)
+            | OpCode::IfACmpNe(_)
+            | OpCode::LReturn
+            | OpCode::DReturn => (2, None),
+            OpCode::Dup => return state.dup(1, 1, offset),
+            OpCode::DupX1 => return state.dup(2, 1, offset),
+            OpCode::DupX2 => return state.dup(3, 1, offset),
+            OpCode::Dup2 => return state.dup(2, 2, offset),
+            OpCode::Dup2X1 => return state.dup(3, 2, offset),
+            OpCode::Dup2X2 => return state.dup(4, 2, offset),
+            OpCode::Swap => {
+                let (a, b) = (state.pop_slot(offset)?, state.pop_slot(offset)?);
+                state.stack.extend([a, b]);
+                return Ok(());
+            }
+            OpCode::IAdd
+            | OpCode::ISub
+            | OpCode::IMul
+            | OpCode::IDiv
+            | OpCode::IRem
+            | OpCode::IShl
+            | OpCode::IShr
+            | OpCode::IUShr
+            | OpCode::IAnd
+            | OpCode::IOr
+            | OpCode::IXOr
+            | OpCode::FCmpL
+            | OpCode::FCmpG => (2, Some(T::Integer)),
+            OpCode::LAdd | OpCode::LSub | OpCode::LMul | OpCode::LDiv | OpCode::LRem => (4, Some(T::Long)),
+            OpCode::LAnd | OpCode::LOr | OpCode::LXOr => (4, Some(T::Long)),
+            OpCode::LShl | OpCode::LShr | OpCode::LUShr => (3, Some(T::Long)),
+            OpCode::FAdd | OpCode::FSub | OpCode::FMul | OpCode::FDiv | OpCode::FRem => (2, Some(T::Float)),
+            OpCode::DAdd | OpCode::DSub | OpCode::DMul | OpCode::DDiv | OpCode::DRem => (4, Some(T::Double)),
+            OpCode::INeg | OpCode::F2I | OpCode::I2B | OpCode::I2C | OpCode::I2S => (1, Some(T::Integer)),
+            OpCode::ArrayLength | OpCode::InstanceOf(_) => (1, Some(T::Integer)),
+            OpCode::LNeg | OpCode::D2L => (2, Some(T::Long)),
+            OpCode::I2L | OpCode::F2L => (1, Some(T::Long)),
+            OpCode::FNeg | OpCode::I2F => (1, Some(T::Float)),
+            OpCode::L2F | OpCode::D2F => (2, Some(T::Float)),
+            OpCode::DNeg | OpCode::L2D => (2, Some(T::Double)),
+            OpCode::I2D | OpCode::F2D => (1, Some(T::Double)),
+            OpCode::L2I | OpCode::D2I => (2, Some(T::Integer)),
+            OpCode::LCmp | OpCode::DCmpL | OpCode::DCmpG => (4, Some(T::Integer)),
+            OpCode::Jsr(_) | OpCode::Ret(_) => return Err(BuildError::Subroutine(offset)),
+            OpCode::GetStatic(index) => (0, Some(self.field_type(*index, offset)?)),
+            OpCode::GetField(index) => (1, Some(self.field_type(*index, offset)?)),
+            OpCode::PutStatic(index) | OpCode::PutField(index) => {
+                let receiver = matches!(op, OpCode::PutField(_)) as usize;
+                let slots = if self.field_type(*index, offset)?.is_wide() { 2 } else { 1 };
+                (receiver + slots, None)
+            }
+            OpCode::InvokeStatic(index) => {
+                let method = self.pool.any_method_ref(*index);
+                let method = method.ok_or(BuildError::UnknownStackEffect(offset))?;
+                return self.invoke(state, method.descriptor, offset);
+            }
+            OpCode::InvokeDynamic(index) => {
+                let call_site = self.pool.invoke_dynamic(*index);
+                let call_site = call_site.ok_or(BuildError::UnknownStackEffect(offset))?;
+                return self.invoke(state, call_site.descriptor, offset);
+            }
+            OpCode::InvokeVirtual(index) | OpCode::InvokeSpecial(index) | OpCode::InvokeInterface(index, _) => {
+                let method = self.pool.any_method_ref(*index);
+                let method = method.ok_or(BuildError::UnknownStackEffect(offset))?;
+                let descriptor = MethodDescriptor::parse(method.descriptor);
+                let descriptor = descriptor.map_err(|_| BuildError::UnknownStackEffect(offset))?;
+                state.pop(descriptor.argument_slots() as usize, offset)?;
+                let receiver = state.pop_slot(offset)?;
+                if matches!(op, OpCode::InvokeSpecial(_)) && method.name == "<init>" {
+                    // Calling a constructor initializes every copy of the object.
+                    let initialized = match &receiver {
+                        T::UninitializedThis => T::Object(self.context.this_class.into()),
+                        T::Uninitialized(new_offset) => match &self.ops[self.index_of(*new_offset)?].0 {
+                            OpCode::New(class) => T::Object(self.class_name(*class, offset)?),
+                            _ => return Err(BuildError::UnknownStackEffect(offset)),
+                        },
+                        _ => receiver.clone(),
+                    };
+                    for slot in state.locals.iter_mut().chain(state.stack.iter_mut()) {
+                        if *slot == receiver {
+                            *slot = initialized.clone();
+                        }
+                    }
+                }
+                if let Some(return_type) = descriptor.return_type() {
+                    state.push(SlotType::from_field_type(return_type));
+                }
+                return Ok(());
+            }
+            OpCode::New(_) => (0, Some(T::Uninitialized(offset))),
+            OpCode::NewArray(element) => {
+                let element = match element {
+                    4 => 'Z',
+                    5 => 'C',
+                    6 => 'F',
+                    7 => 'D',
+                    8 => 'B',
+                    9 => 'S',
+                    10 => 'I',
+                    11 => 'J',
+                    _ => return Err(BuildError::UnknownStackEffect(offset)),
+                };
+                (1, Some(T::Object(format!("[{element}").into())))
+            }
+            OpCode::ANewArray(index) => {
+                let class = self.class_name(*index, offset)?;
+                let array = if class.starts_with('[') {
+                    format!("[{class}")
+                } else {
+                    format!("[L{class};")
+                };
+                (1, Some(T::Object(array.into())))
+            }
+            OpCode::CheckCast(index) => (1, Some(T::Object(self.class_name(*index, offset)?))),
+            OpCode::MultiANewArray(index, dimensions) => {
+                (*dimensions as usize, Some(T::Object(self.class_name(*index, offset)?)))
+            }
+        };
+        state.pop(pops, offset)?;
+        if let Some(push) = push {
+            state.push(push);
+        }
+        Ok(())
+    }
+    /// States at the start of each instruction, `None` for unreachable ones.
+    fn analyze(&self, exceptions: &[CodeException]) -> Result<Vec<Option<State>>, BuildError> {
+        let mut states = vec![None; self.ops.len()];
+        states[0] = Some(self.initial_state());
+        let mut pending = vec![0];
+        let handlers = exceptions
+            .iter()
+            .map(|exception| {
+                let catch_type = match exception.catch_type {
+                    0 => "java/lang/Throwable".into(),
+                    class => self.class_name(class, exception.handler_pc)?,
+                };
+                Ok((exception, self.index_of(exception.handler_pc)?, catch_type))
+            })
+            .collect::<Result<Vec<_>, BuildError>>()?;
+        while let Some(index) = pending.pop() {
+            let mut state = states[index].clone().expect("pending instructions have a state");
+            let (op, offset) = &self.ops[index];
+            for (exception, handler, catch_type) in &handlers {
+                if (exception.start_pc..exception.end_pc).contains(offset) {
+                    let handler_state = State {
+                        locals: state.locals.clone(),
+                        stack: vec![SlotType::Object(catch_type.clone())],
+                    };
+                    if self.merge(&mut states, *handler, &handler_state)? {
+                        pending.push(*handler);
+                    }
+                }
+            }
+            self.execute(op, *offset, &mut state)?;
+            for target in op.branch_targets(*offset) {
+                let target = self.index_of(target)?;
+                if self.merge(&mut states, target, &state)? {
+                    pending.push(target);
+                }
+            }
+            if !op.is_terminator() {
+                if index + 1 == self.ops.len() {
+                    return Err(BuildError::FallsOffEnd);
+                }
+                if self.merge(&mut states, index + 1, &state)? {
+                    pending.push(index + 1);
+                }
+            }
+        }
+        Ok(states)
+    }
+}
+/// Class name of a reference type descriptor, e.g. `java/lang/String` for `Ljava/lang/String;` and `[I` for `[I`.
+fn reference_class(descriptor: &str) -> Option<&str> {
+    match descriptor.strip_prefix('L') {
+        Some(class) => class.strip_suffix(';'),
+        None => descriptor.starts_with('[').then_some(descriptor),
+    }
+}
+/// Computes the `StackMapTable` frames of `code`, adding the classes they mention to `pool`.
+///
+/// Frames are placed at branch targets, exception handlers and instructions following an unconditional jump.
+/// Like ASM does, unreachable code is replaced by `nop`s followed by an `athrow`, whose frame has the locals of the
+/// previous frame and a `Throwable` on the stack, and is removed from the ranges of exception handlers.
+pub(crate) fn compute_frames(
+    code: &mut Code,
+    pool: &mut ConstantPoolBuilder,
+    context: &FrameContext,
+) -> Result<Box<[StackMapFrame]>, BuildError> {
+    let ops = &code.ops[..];
+    let analyzer = Analyzer {
+        ops,
+        pool: pool.pool(),
+        context,
+    };
+    let states = analyzer.analyze(&code.exceptions)?;
+    let code_length = ops.last().zip(code.opcodes.last()).map_or(0, |((op, offset), opcode)| {
+        *offset as u32 + op.encoded_size(*opcode, *offset)
+    });
+    // Start and end offset of each run of unreachable instructions.
+    let mut dead = Vec::new();
+    for (index, (_, offset)) in ops.iter().enumerate() {
+        if states[index].is_some() {
+            continue;
+        }
+        let end = ops.get(index + 1).map_or(code_length, |(_, next)| *next as u32) as u16;
+        match dead.last_mut() {
+            Some((_, dead_end)) if *dead_end == *offset => *dead_end = end,
+            _ => dead.push((*offset, end)),
+        }
+    }
+    let mut exceptions = Vec::with_capacity(code.exceptions.len());
+    for exception in code.exceptions.iter() {
+        let mut start_pc = exception.start_pc;
+        for (dead_start, dead_end) in dead.iter() {
+            if *dead_end <= start_pc || *dead_start >= exception.end_pc {
+                continue;
+            }
+            if start_pc < *dead_start {
+                exceptions.push(CodeException {
+                    start_pc,
+                    end_pc: *dead_start,
+                    ..*exception
+                });
+            }
+            start_pc = *dead_end;
+        }
+        if start_pc < exception.end_pc {
+            exceptions.push(CodeException {
+                start_pc,
+                ..*exception
+            });
+        }
+    }
+    let mut targets = Vec::new();
+    for (index, (op, offset)) in ops.iter().enumerate() {
+        if states[index].is_none() {
+            continue;
+        }
+        targets.extend(op.branch_targets(*offset));
+        if op.is_terminator() && index + 1 < ops.len() {
+            targets.push(ops[index + 1].1);
+        }
+    }
+    targets.extend(exceptions.iter().map(|exception| exception.handler_pc));
+    targets.sort_unstable();
+    targets.dedup();
+    let initial = analyzer.initial_state();
+    let mut framed: Vec<(u16, State)> = Vec::with_capacity(targets.len());
+    for offset in targets {
+        let state = match &states[analyzer.index_of(offset)?] {
+            Some(state) => state.clone(),
+            None => State {
+                locals: framed.last().map_or(&initial, |(_, previous)| previous).locals.clone(),
+                stack: vec![SlotType::Object("java/lang/Throwable".into())],
+            },
+        };
+        framed.push((offset, state));
+    }
+    if !dead.is_empty() {
+        let mut ops = Vec::with_capacity(code.ops.len());
+        let mut opcodes = Vec::with_capacity(code.opcodes.len());
+        for ((op, offset), opcode) in code.ops.iter().zip(code.opcodes.iter()) {
+            match dead.iter().find(|(start, end)| (*start..*end).contains(offset)) {
+                None => {
+                    ops.push((op.clone(), *offset));
+                    opcodes.push(*opcode);
+                }
+                Some((start, end)) if start == offset => {
+                    for nop in *start..*end - 1 {
+                        ops.push((OpCode::Nop, nop));
+                        opcodes.push(0x00);
+                    }
+                    ops.push((OpCode::Throw, *end - 1));
+                    opcodes.push(0xbf);
+                }
+                Some(_) => (),
+            }
+        }
+        code.ops = ops.into();
+        code.opcodes = opcodes.into();
+        code.max_stack = code.max_stack.max(1);
+    }
+    code.exceptions = exceptions.into();
+    let mut verification_type = |slot: &SlotType| match slot {
+        SlotType::Top => VerificationType::Top,
+        SlotType::Integer => VerificationType::Integer,
+        SlotType::Float => VerificationType::Float,
+        SlotType::Long => VerificationType::Long,
+        SlotType::Double => VerificationType::Double,
+        SlotType::Null => VerificationType::Null,
+        SlotType::UninitializedThis => VerificationType::UninitializedThis,
+        SlotType::Uninitialized(offset) => VerificationType::Uninitialized(*offset),
+        SlotType::Object(name) => VerificationType::Object(ClassInfo(pool.class(name))),
+    };
+    let mut previous: Vec<VerificationType> = State::entries(&initial.locals)
+        .into_iter()
+        .map(&mut verification_type)
+        .collect();
+    let mut frames = Vec::with_capacity(framed.len());
+    for (offset, state) in framed {
+        let mut locals: Vec<_> = State::entries(&state.locals).into_iter().map(&mut verification_type).collect();
+        while locals.last() == Some(&VerificationType::Top) {
+            locals.pop();
+        }
+        let stack: Vec<_> = State::entries(&state.stack).into_iter().map(&mut verification_type).collect();
+        let common = previous.iter().zip(&locals).take_while(|(a, b)| a == b).count();
+        let frame = match stack.len() {
+            0 if locals == previous => StackMapFrame::Same { offset, extended: false },
+            1 if locals == previous => StackMapFrame::SameLocals1StackItem {
+                offset,
+                stack: stack[0],
+                extended: false,
+            },
+            0 if common == locals.len() && (1..=3).contains(&(previous.len() - common)) => StackMapFrame::Chop {
+                offset,
+                count: (previous.len() - common) as u8,
+            },
+            0 if common == previous.len() && (1..=3).contains(&(locals.len() - common)) => StackMapFrame::Append {
+                offset,
+                locals: locals[common..].into(),
+            },
+            _ => StackMapFrame::Full {
+                offset,
+                locals: locals.clone().into(),
+                stack: stack.into(),
+            },
+        };
+        frames.push(frame);
+        previous = locals;
+    }
+    Ok(frames.into())
+}
+#[test]
+fn sieve_frames() {
+    let stack_maps = |class: &crate::JavaClassFile| -> Vec<Vec<StackMapFrame>> {
+        class
+            .methods()
+            .iter()
+            .filter_map(|method| method.code())
+            .map(|code| code.stack_map().unwrap_or_default().to_vec())
+            .collect()
+    };
+    let mut file = std::fs::File::open("test/Sieve.class").unwrap();
+    let mut class = crate::import_class_file(&mut file).unwrap();
+    let expected = stack_maps(&class);
+    let offsets: Vec<Vec<u16>> = expected
+        .iter()
+        .map(|frames| frames.iter().map(StackMapFrame::offset).collect())
+        .collect();
+    assert_eq!(offsets, [vec![], vec![8, 25], vec![], vec![2, 14, 35, 45], vec![2, 25, 31], vec![]]);
+    assert!(matches!(&expected[1][..], [StackMapFrame::Append { locals, .. }, StackMapFrame::Chop { count: 1, .. }] if locals[..] == [VerificationType::Integer]));
+    // javac chops locals that went out of scope, which the analysis does not know about, so only offsets match.
+    class.compute_frames(&|_: &str| None).unwrap();
+    let computed = stack_maps(&class);
+    assert!(computed
+        .iter()
+        .zip(&expected)
+        .all(|(computed, expected)| computed.iter().map(StackMapFrame::offset).eq(expected.iter().map(StackMapFrame::offset))));
+    assert_eq!(computed[1][0], expected[1][0]);
+}
+#[test]
+fn extended_frames() {
+    let table = [
+        0, 3, // 3 frames
+        251, 0, 5, // same_frame_extended at 5
+        247, 0, 2, 1, // same_locals_1_stack_item_frame_extended at 8, with an int
+        10, // same_frame at 19
+    ];
+    let frames = StackMapFrame::read_table(&mut OffsetReader::new(&table[..], 0)).unwrap();
+    assert_eq!(
+        frames[..],
+        [
+            StackMapFrame::Same { offset: 5, extended: true },
+            StackMapFrame::SameLocals1StackItem {
+                offset: 8,
+                stack: VerificationType::Integer,
+                extended: true
+            },
+            StackMapFrame::Same { offset: 19, extended: false }
+        ]
+    );
+    let mut written = Vec::new();
+    StackMapFrame::write_table(&frames, &mut written).unwrap();
+    assert_eq!(written, table);
+    let chop = StackMapFrame::Chop { offset: 0, count: 4 };
+    assert!(StackMapFrame::write_table(&[chop], &mut Vec::new()).is_err());
+    let append = StackMapFrame::Append {
+        offset: 0,
+        locals: Box::new([]),
+    };
+    assert!(StackMapFrame::write_table(&[append], &mut Vec::new()).is_err());
+}
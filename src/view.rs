@@ -0,0 +1,285 @@
+use crate::error::{ImportErrorKind, JavaImportError};
+use crate::mutf8::Mutf8Error;
+use std::borrow::Cow;
+use crate::import::{load_u16, load_u32, load_u8, read_header, AccessFlags, OffsetReader};
+/// Class file parsed from a byte slice, borrowing its strings and attribute contents instead of copying them.
+/// Only strings using the modified UTF-8 encoding of NUL or supplementary characters need to be copied.
+///
+/// Only the structure of the class is parsed: attributes are kept as raw bytes. Use [`crate::import_class_file`]
+/// to get a fully decoded [`crate::JavaClassFile`].
+#[derive(Debug)]
+pub struct ClassView<'a> {
+    constants: Box<[RawConstant<'a>]>,
+    access_flags: AccessFlags,
+    this_class: u16,
+    super_class: u16,
+    interfaces: Box<[u16]>,
+    fields: Box<[MemberView<'a>]>,
+    methods: Box<[MemberView<'a>]>,
+    attributes: Box<[AttributeView<'a>]>,
+}
+/// A field or method of a [`ClassView`].
+#[derive(Debug)]
+pub struct MemberView<'a> {
+    access_flags: AccessFlags,
+    name: Cow<'a, str>,
+    descriptor: Cow<'a, str>,
+    attributes: Box<[AttributeView<'a>]>,
+}
+/// An undecoded attribute.
+#[derive(Clone, Debug)]
+pub struct AttributeView<'a> {
+    name: Cow<'a, str>,
+    data: &'a [u8],
+    offset: u64,
+}
+#[derive(Clone, Debug)]
+enum RawConstant<'a> {
+    Utf8(Cow<'a, str>),
+    Class(u16),
+    Other { tag: u8, data: &'a [u8] },
+    Padding,
+}
+impl<'a> RawConstant<'a> {
+    fn read(src: &mut OffsetReader<&'a [u8]>) -> Result<Self, JavaImportError> {
+        let tag = load_u8(src)?;
+        let len = match tag {
+            1 => {
+                let length = load_u16(src)?;
+                let bytes = src.read_slice(length as usize)?;
+                return match crate::mutf8::decode(bytes) {
+                    Ok(string) => Ok(Self::Utf8(string)),
+                    // Not valid Unicode, but still valid modified UTF-8: keep it as raw bytes.
+                    Err(Mutf8Error::LoneSurrogate) => Ok(Self::Other { tag, data: bytes }),
+                    Err(Mutf8Error::Malformed) => Err(ImportErrorKind::InvalidUtf8.into()),
+                };
+            }
+            7 => return Ok(Self::Class(load_u16(src)?)),
+            8 | 16 | 19 | 20 => 2,
+            15 => 3,
+            3 | 4 | 9 | 10 | 11 | 12 | 17 | 18 => 4,
+            5 | 6 => 8,
+            _ => return Err(ImportErrorKind::InvalidConstantTag(tag).into()),
+        };
+        let data = src.read_slice(len)?;
+        Ok(Self::Other { tag, data })
+    }
+}
+impl<'a> ClassView<'a> {
+    /// Parses the structure of a class file. Fails if the file is malformed, or names used by it are not valid.
+    pub fn parse(bytes: &'a [u8]) -> Result<Self, JavaImportError> {
+        let mut src = OffsetReader::new(bytes, 0);
+        read_header(&mut src)?;
+        let constant_pool_count = load_u16(&mut src)?;
+        let mut constants = Vec::with_capacity(constant_pool_count as usize);
+        while constants.len() + 1 < constant_pool_count as usize {
+            let constant = RawConstant::read(&mut src).map_err(|err| err.at(src.offset()))?;
+            let is_wide = matches!(constant, RawConstant::Other { tag: 5 | 6, .. });
+            constants.push(constant);
+            if is_wide {
+                constants.push(RawConstant::Padding);
+            }
+        }
+        let mut class = Self {
+            constants: constants.into(),
+            access_flags: AccessFlags::read(&mut src)?,
+            this_class: 0,
+            super_class: 0,
+            interfaces: Box::default(),
+            fields: Box::default(),
+            methods: Box::default(),
+            attributes: Box::default(),
+        };
+        class.this_class = load_u16(&mut src)?;
+        if class.class_name(class.this_class).is_none() {
+            return Err(src.error(ImportErrorKind::InvalidThisClass));
+        }
+        match class.read_body(&mut src) {
+            Ok(()) => Ok(class),
+            Err(err) => Err(err.at(src.offset()).in_class(Some(class.this_class()))),
+        }
+    }
+    fn read_body(&mut self, src: &mut OffsetReader<&'a [u8]>) -> Result<(), JavaImportError> {
+        self.super_class = load_u16(src)?;
+        if self.super_class != 0 && self.class_name(self.super_class).is_none() {
+            return Err(src.error(ImportErrorKind::InvalidSuperClass));
+        }
+        let interfaces_count = load_u16(src)?;
+        let mut interfaces = Vec::with_capacity(interfaces_count as usize);
+        for _ in 0..interfaces_count {
+            let interface = load_u16(src)?;
+            if self.class_name(interface).is_none() {
+                return Err(src.error(ImportErrorKind::BadConstantIndex(interface)));
+            }
+            interfaces.push(interface);
+        }
+        self.interfaces = interfaces.into();
+        self.fields = self.read_members(src)?;
+        self.methods = self.read_members(src)?;
+        self.attributes = self.read_attributes(src)?;
+        Ok(())
+    }
+    fn read_members(&self, src: &mut OffsetReader<&'a [u8]>) -> Result<Box<[MemberView<'a>]>, JavaImportError> {
+        let count = load_u16(src)?;
+        let mut members = Vec::with_capacity(count as usize);
+        for _ in 0..count {
+            let access_flags = AccessFlags::read(src)?;
+            let name = self.read_utf8(src)?;
+            let descriptor = self.read_utf8(src).map_err(|err| err.in_member(Some(&name)))?;
+            let attributes = self.read_attributes(src).map_err(|err| err.in_member(Some(&name)))?;
+            members.push(MemberView {
+                access_flags,
+                name,
+                descriptor,
+                attributes,
+            });
+        }
+        Ok(members.into())
+    }
+    fn read_attributes(
+        &self,
+        src: &mut OffsetReader<&'a [u8]>,
+    ) -> Result<Box<[AttributeView<'a>]>, JavaImportError> {
+        let count = load_u16(src)?;
+        let mut attributes = Vec::with_capacity(count as usize);
+        for _ in 0..count {
+            let name = self.read_utf8(src)?;
+            let length = load_u32(src)?;
+            let offset = src.offset();
+            let data = src
+                .read_slice(length as usize)
+                .map_err(|err| err.in_attribute(&name))?;
+            attributes.push(AttributeView { name, data, offset });
+        }
+        Ok(attributes.into())
+    }
+    /// Reads an index of a `Utf8` constant, and resolves it.
+    fn read_utf8(&self, src: &mut OffsetReader<&'a [u8]>) -> Result<Cow<'a, str>, JavaImportError> {
+        let index = load_u16(src)?;
+        match self.constants.get((index as usize).wrapping_sub(1)) {
+            Some(RawConstant::Utf8(string)) => Ok(string.clone()),
+            _ => Err(src.error(ImportErrorKind::BadConstantIndex(index))),
+        }
+    }
+    /// Contents of the `Utf8` constant at `index`.
+    pub fn utf8(&self, index: u16) -> Option<&str> {
+        match self.constants.get((index as usize).checked_sub(1)?)? {
+            RawConstant::Utf8(string) => Some(string),
+            _ => None,
+        }
+    }
+    /// Internal name of the class referenced by the `Class` constant at `index`.
+    pub fn class_name(&self, index: u16) -> Option<&str> {
+        match self.constants.get((index as usize).checked_sub(1)?)? {
+            RawConstant::Class(name_index) => self.utf8(*name_index),
+            _ => None,
+        }
+    }
+    /// Tag and undecoded contents of the constant at `index`, for constants other than `Utf8` and `Class`.
+    /// `Utf8` constants which are not valid Unicode are also returned here.
+    pub fn raw_constant(&self, index: u16) -> Option<(u8, &'a [u8])> {
+        match self.constants.get((index as usize).checked_sub(1)?)? {
+            RawConstant::Other { tag, data } => Some((*tag, data)),
+            _ => None,
+        }
+    }
+    pub fn access_flags(&self) -> AccessFlags {
+        self.access_flags
+    }
+    /// Internal name of this class, e.g. `java/lang/String`.
+    pub fn this_class(&self) -> &str {
+        self.class_name(self.this_class).unwrap_or_default()
+    }
+    /// Internal name of the super class, `None` for `java/lang/Object` and `module-info`.
+    pub fn super_class(&self) -> Option<&str> {
+        self.class_name(self.super_class)
+    }
+    /// Internal names of the interfaces this class implements.
+    pub fn interfaces(&self) -> impl Iterator<Item = &str> + '_ {
+        self.interfaces
+            .iter()
+            .map(|interface| self.class_name(*interface).unwrap_or_default())
+    }
+    pub fn fields(&self) -> &[MemberView<'a>] {
+        &self.fields
+    }
+    pub fn methods(&self) -> &[MemberView<'a>] {
+        &self.methods
+    }
+    pub fn attributes(&self) -> &[AttributeView<'a>] {
+        &self.attributes
+    }
+    /// Contents of the first class attribute called `name`.
+    pub fn attribute(&self, name: &str) -> Option<&'a [u8]> {
+        find_attribute(&self.attributes, name)
+    }
+}
+fn find_attribute<'a>(attributes: &[AttributeView<'a>], name: &str) -> Option<&'a [u8]> {
+    attributes
+        .iter()
+        .find(|attribute| attribute.name() == name)
+        .map(|attribute| attribute.data)
+}
+impl<'a> MemberView<'a> {
+    pub fn access_flags(&self) -> AccessFlags {
+        self.access_flags
+    }
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+    pub fn descriptor(&self) -> &str {
+        &self.descriptor
+    }
+    pub fn attributes(&self) -> &[AttributeView<'a>] {
+        &self.attributes
+    }
+    /// Contents of the first attribute called `name`.
+    pub fn attribute(&self, name: &str) -> Option<&'a [u8]> {
+        find_attribute(&self.attributes, name)
+    }
+}
+impl<'a> AttributeView<'a> {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+    /// Contents of the attribute, without its name and length.
+    pub fn data(&self) -> &'a [u8] {
+        self.data
+    }
+    /// Byte offset of the contents within the class file.
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+}
+#[test]
+fn view_borrows_class_file() {
+    let bytes = std::fs::read("test/ConstantPool.class").unwrap();
+    let view = ClassView::parse(&bytes).unwrap();
+    let class = crate::import_class_file(&bytes[..]).unwrap();
+    assert_eq!(view.this_class(), "ConstantPool");
+    assert_eq!(view.super_class(), Some("java/lang/Object"));
+    let names: Vec<_> = view.methods().iter().map(MemberView::name).collect();
+    let owned: Vec<_> = class
+        .methods()
+        .iter()
+        .map(|method| class.get_utf8(method.name()).unwrap())
+        .collect();
+    assert_eq!(names, owned);
+    let field = view.fields().iter().find(|field| field.name() == "half").unwrap();
+    assert_eq!(field.descriptor(), "D");
+    assert!(field.access_flags().is_static());
+    let code = view.methods()[3].attribute("Code").unwrap();
+    let max_locals = u16::from_be_bytes([code[2], code[3]]);
+    assert_eq!(class.methods()[3].code().unwrap().max_locals(), max_locals);
+    let in_file = bytes.as_ptr_range();
+    assert!(in_file.contains(&view.this_class().as_ptr()));
+    assert!(in_file.contains(&code.as_ptr()));
+    assert_eq!(view.raw_constant(70), Some((5, &0x1234_5678_9abc_i64.to_be_bytes()[..])));
+    for len in 0..bytes.len() {
+        assert!(ClassView::parse(&bytes[..len]).is_err());
+    }
+    let bytes = std::fs::read("test/Mutf8.class").unwrap();
+    let view = ClassView::parse(&bytes).unwrap();
+    assert_eq!((view.utf8(13), view.utf8(16)), (Some("a\0b"), Some("\u{1F600}")));
+    assert_eq!(view.raw_constant(19), Some((1, &b"\xED\xA0\x80"[..])));
+}
@@ -0,0 +1,26 @@
+import java.lang.annotation.ElementType;
+import java.lang.annotation.Retention;
+import java.lang.annotation.RetentionPolicy;
+
+@Annotations.Component(name = "annotations", scope = ElementType.TYPE, tags = {"a", "b"}, factory = String.class)
+@Annotations.Marker
+public class Annotations {
+    @Retention(RetentionPolicy.RUNTIME)
+    @interface Component {
+        String name() default "";
+        ElementType scope() default ElementType.FIELD;
+        String[] tags() default {};
+        Class<?> factory() default void.class;
+        long priority() default 7L;
+    }
+    // Kept in the class file, but not visible at runtime.
+    @interface Marker {}
+    @Retention(RetentionPolicy.RUNTIME)
+    @interface Inject {
+        Marker value() default @Marker;
+    }
+    @Deprecated
+    @Inject
+    int counter;
+    void set(@Inject int value, @Marker String name) {}
+}
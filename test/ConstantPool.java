@@ -0,0 +1,18 @@
+import java.util.List;
+import java.util.function.Supplier;
+
+public class ConstantPool {
+    static long big = 0x123456789abcL;
+    static double half = 0.5;
+    String name = "ConstantPool";
+    static int size(List<String> list) {
+        return list.size();
+    }
+    static Supplier<String> supplier() {
+        return () -> "lambda";
+    }
+    public static void main(String[] args) {
+        ConstantPool pool = new ConstantPool();
+        System.out.println(pool.name + big + half + size(List.of("a")) + supplier().get());
+    }
+}
@@ -0,0 +1,21 @@
+public class Identity {
+    public static int identityInt(int x) {
+        return x;
+    }
+    public static long identityLong(long x) {
+        return x;
+    }
+    public static float identityFloat(float x) {
+        return x;
+    }
+    public static double identityDouble(double x) {
+        return x;
+    }
+    public static Object identityObject(Object x) {
+        return x;
+    }
+    public static void main(String[] args) throws Exception {
+        System.out.println(identityInt(1) + identityLong(2) + identityFloat(3.0f) + identityDouble(4.0));
+        System.out.println(identityObject("Identity"));
+    }
+}
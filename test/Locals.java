@@ -0,0 +1,15 @@
+import java.util.ArrayList;
+import java.util.List;
+import java.util.Map;
+
+public class Locals {
+    static <T> List<T> repeat(T value, int count) {
+        List<T> values = new ArrayList<>();
+        for (int i = 0; i < count; i++) {
+            values.add(value);
+        }
+        long total = count;
+        Map<String, List<T>> byName = Map.of("values" + total, values);
+        return byName.get("values");
+    }
+}
@@ -0,0 +1,5 @@
+public class Mutf8 {
+    static final String NUL = "a\0b";
+    static final String EMOJI = "😀";
+    static final String LONE = "\uD800";
+}
@@ -0,0 +1,14 @@
+public class Nesting {
+    class Inner {
+        static class Deepest {}
+    }
+    Runnable make() {
+        class Local implements Runnable {
+            public void run() {}
+        }
+        Runnable anonymous = new Runnable() {
+            public void run() {}
+        };
+        return anonymous;
+    }
+}
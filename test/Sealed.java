@@ -0,0 +1,5 @@
+public sealed interface Sealed permits Sealed.Circle, Sealed.Square, Sealed.Polygon {
+    record Circle(double radius) implements Sealed {}
+    final class Square implements Sealed {}
+    non-sealed class Polygon implements Sealed {}
+}
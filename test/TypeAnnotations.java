@@ -0,0 +1,31 @@
+import java.lang.annotation.ElementType;
+import java.lang.annotation.Retention;
+import java.lang.annotation.RetentionPolicy;
+import java.lang.annotation.Target;
+import java.util.List;
+import java.util.function.Supplier;
+
+@Retention(RetentionPolicy.RUNTIME)
+@Target(ElementType.TYPE_USE)
+@interface Nullable {}
+@Target(ElementType.TYPE_USE)
+@interface Tainted {}
+
+public class TypeAnnotations<@Nullable T extends @Nullable Object>
+        extends @Nullable Object implements @Nullable Runnable {
+    List<@Nullable String> names;
+    String @Nullable [] @Tainted [] grid;
+    public void run() {}
+    @Nullable String find(@Nullable Object key) throws @Nullable Exception {
+        @Nullable String found = (@Nullable String) key;
+        try {
+            if (key instanceof @Nullable String) {
+                Supplier<String> supplier = @Nullable String::new;
+                return new @Tainted String(found) + supplier.get();
+            }
+        } catch (@Nullable RuntimeException e) {
+            return null;
+        }
+        return List.<@Nullable String>of("a").get(0);
+    }
+}
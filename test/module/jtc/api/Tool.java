@@ -0,0 +1,14 @@
+package jtc.api;
+
+import java.io.PrintWriter;
+import java.util.spi.ToolProvider;
+
+public class Tool implements ToolProvider {
+    public String name() {
+        return "jtc";
+    }
+    public int run(PrintWriter out, PrintWriter err, String... args) {
+        return 0;
+    }
+    public static void main(String[] args) {}
+}
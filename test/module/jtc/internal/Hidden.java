@@ -0,0 +1,3 @@
+package jtc.internal;
+
+public class Hidden {}
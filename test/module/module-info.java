@@ -0,0 +1,10 @@
+module jtc.test {
+    requires java.logging;
+    requires transitive java.sql;
+    requires static java.compiler;
+    exports jtc.api;
+    exports jtc.internal to java.logging, java.sql;
+    opens jtc.internal;
+    uses java.util.spi.ToolProvider;
+    provides java.util.spi.ToolProvider with jtc.api.Tool;
+}